@@ -0,0 +1,1219 @@
+/// build.rs
+///
+/// Generates the baseline AAF class model from the reference
+/// implementation's `AAFMetaDictionary.h`.
+///
+/// The header is a list of C macro invocations. We read these into a tree
+/// of calls, collect the class and type tables out of it, and write these
+/// files into `OUT_DIR`:
+///
+/// - `class_ids.rs`: an AUID constant for every class
+/// - `type_ids.rs`: an AUID constant for every type
+/// - `enums.rs`: a Rust enum for every enumeration type, with `Undefined`
+///   for values the baseline does not define
+/// - `baseline.rs`: the class and type definitions, as constructors for
+///   `aaf::meta_dictionary`
+/// - `classes.rs`: an accessor struct for every concrete class, and an enum
+///   of its concrete classes for every abstract class
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const META_DICTIONARY_H: &str = "AAF-refimpl/AAFMetaDictionary.h";
+
+/// The AUIDs of the properties of the root object, which the header names
+/// in weak reference target paths but does not define.
+const ROOT_PROPERTIES: [(&str, [u8; 16]); 2] = [
+    (
+        "MetaDictionary",
+        [
+            0x0d, 0x01, 0x03, 0x01, 0x01, 0x01, 0x01, 0x00, 0x06, 0x0e, 0x2b, 0x34, 0x01, 0x01,
+            0x01, 0x02,
+        ],
+    ),
+    (
+        "Header",
+        [
+            0x0d, 0x01, 0x03, 0x01, 0x01, 0x02, 0x01, 0x00, 0x06, 0x0e, 0x2b, 0x34, 0x01, 0x01,
+            0x01, 0x02,
+        ],
+    ),
+];
+
+type Auid = [u8; 16];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Open,
+    Close,
+    Comma,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Word(String),
+    Call(String, Vec<Node>),
+}
+
+struct Class {
+    name: String,
+    id: Auid,
+    parent: String,
+    is_concrete: bool,
+    properties: Vec<Property>,
+}
+
+struct Property {
+    name: String,
+    id: Auid,
+    pid: u16,
+    type_name: String,
+    is_mandatory: bool,
+    is_unique_identifier: bool,
+}
+
+struct Type {
+    name: String,
+    id: Auid,
+    kind: Kind,
+}
+
+enum Kind {
+    Integer {
+        size: u8,
+        is_signed: bool,
+    },
+    Enumeration {
+        element_type: String,
+        elements: Vec<(String, i64)>,
+    },
+    Record {
+        members: Vec<(String, String)>,
+    },
+    StrongReference {
+        class: String,
+    },
+    StrongReferenceSet {
+        class: String,
+    },
+    StrongReferenceVector {
+        class: String,
+    },
+    WeakReference {
+        class: String,
+        target: Vec<(String, String)>,
+    },
+    WeakReferenceSet {
+        class: String,
+    },
+    WeakReferenceVector {
+        class: String,
+    },
+    VaryingArray {
+        element_type: String,
+    },
+    FixedArray {
+        element_type: String,
+        count: u32,
+    },
+    Set {
+        element_type: String,
+    },
+    String {
+        element_type: String,
+    },
+    Stream,
+    Rename {
+        renamed_type: String,
+    },
+    ExtendibleEnumeration {
+        elements: Vec<(String, Auid)>,
+    },
+    Indirect,
+    Opaque,
+    Character,
+}
+
+/// How a property is read by its accessor.
+enum Access {
+    Data(String),
+    /// A reference, and the handle type of the referenced class.
+    Reference(Option<String>),
+    /// A reference set or vector, and the handle type of the referenced
+    /// class.
+    Collection(Option<String>),
+    Stream,
+}
+
+fn lex(source: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut in_directive = false;
+    let mut in_comment = false;
+    for line in source.lines() {
+        // Preprocessor directives, including their continuation lines
+        if in_directive || line.trim_start().starts_with('#') {
+            in_directive = line.ends_with('\\');
+            continue;
+        }
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if in_comment {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    in_comment = false;
+                }
+                continue;
+            }
+            match c {
+                '/' if chars.peek() == Some(&'/') => break,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    in_comment = true;
+                }
+                '(' => tokens.push(Token::Open),
+                ')' => tokens.push(Token::Close),
+                ',' => tokens.push(Token::Comma),
+                '"' => {
+                    let mut s = String::new();
+                    for c in chars.by_ref() {
+                        if c == '"' {
+                            break;
+                        }
+                        s.push(c);
+                    }
+                    tokens.push(Token::Str(s));
+                }
+                c if c.is_whitespace() => {}
+                c => {
+                    let mut s = c.to_string();
+                    while let Some(&n) = chars.peek() {
+                        if n.is_alphanumeric() || n == '_' || n == '-' || n == '.' {
+                            s.push(n);
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    tokens.push(Token::Word(s));
+                }
+            }
+        }
+    }
+    tokens
+}
+
+/// Read the macro invocations in `tokens` into a list of calls.
+fn parse(tokens: &[Token]) -> Vec<Node> {
+    let mut pos = 0;
+    let mut nodes = vec![];
+    while pos < tokens.len() {
+        match parse_node(tokens, &mut pos) {
+            call @ Node::Call(..) => nodes.push(call),
+            // The header comments out the start of one AAF_CLASS_END's
+            // AUID, leaving the rest of its arguments outside of the call.
+            Node::Word(_) => {}
+        }
+    }
+    nodes
+}
+
+fn parse_node(tokens: &[Token], pos: &mut usize) -> Node {
+    let word = match &tokens[*pos] {
+        Token::Word(w) | Token::Str(w) => w.clone(),
+        _ => {
+            *pos += 1;
+            return Node::Word(String::new());
+        }
+    };
+    *pos += 1;
+    if tokens.get(*pos) != Some(&Token::Open) {
+        return Node::Word(word);
+    }
+    *pos += 1;
+
+    let mut args = vec![];
+    loop {
+        match tokens.get(*pos) {
+            Some(Token::Close) => {
+                *pos += 1;
+                break;
+            }
+            Some(Token::Comma) => *pos += 1,
+            Some(_) => args.push(parse_node(tokens, pos)),
+            None => panic!("unterminated {} in {}", word, META_DICTIONARY_H),
+        }
+    }
+    Node::Call(word, args)
+}
+
+fn word(node: &Node) -> &str {
+    match node {
+        Node::Word(w) => w,
+        Node::Call(name, _) => panic!("expected a word, found {}(...)", name),
+    }
+}
+
+fn number(node: &Node) -> i64 {
+    let w = word(node);
+    let parsed = match w.strip_prefix("0x").or_else(|| w.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => w.parse(),
+    };
+    parsed.unwrap_or_else(|_| panic!("expected a number, found {}", w))
+}
+
+fn boolean(node: &Node) -> bool {
+    match word(node) {
+        "true" | "True" => true,
+        "false" | "False" => false,
+        w => panic!("expected a boolean, found {}", w),
+    }
+}
+
+fn auid(node: &Node) -> Auid {
+    match node {
+        Node::Call(name, args) if name == "AAF_LITERAL_AUID" && args.len() == 11 => {
+            let mut bytes = [0u8; 16];
+            bytes[0..4].copy_from_slice(&(number(&args[0]) as u32).to_be_bytes());
+            bytes[4..6].copy_from_slice(&(number(&args[1]) as u16).to_be_bytes());
+            bytes[6..8].copy_from_slice(&(number(&args[2]) as u16).to_be_bytes());
+            for (i, arg) in args[3..].iter().enumerate() {
+                bytes[8 + i] = number(arg) as u8;
+            }
+            bytes
+        }
+        _ => panic!("expected an AUID, found {:?}", node),
+    }
+}
+
+/// The name of the type a type argument refers to.
+fn type_name(node: &Node) -> String {
+    match node {
+        Node::Word(w) => w.clone(),
+        Node::Call(name, args) if name == "AAF_TYPE" => word(&args[0]).to_string(),
+        Node::Call(name, args)
+            if name == "AAF_REFERENCE_TYPE" || name == "AAF_REFERENCE_TYPE_NAME" =>
+        {
+            format!("{}{}", word(&args[1]), word(&args[0]))
+        }
+        Node::Call(name, _) => panic!("expected a type, found {}(...)", name),
+    }
+}
+
+fn read_tables(nodes: &[Node]) -> (Vec<Class>, Vec<Type>) {
+    let mut classes: Vec<Class> = vec![];
+    let mut types: Vec<Type> = vec![];
+
+    for node in nodes {
+        let (name, args) = match node {
+            Node::Call(name, args) => (name.as_str(), args),
+            Node::Word(w) => panic!("unexpected {} in {}", w, META_DICTIONARY_H),
+        };
+        let simple = |kind: Kind| Type {
+            name: type_name(&args[0]),
+            id: auid(&args[1]),
+            kind,
+        };
+        match name {
+            "AAF_CLASS" => classes.push(Class {
+                name: word(&args[0]).to_string(),
+                id: auid(&args[1]),
+                parent: word(&args[2]).to_string(),
+                is_concrete: boolean(&args[3]),
+                properties: vec![],
+            }),
+            "AAF_PROPERTY" => {
+                let class = classes.last_mut().expect("AAF_PROPERTY outside of a class");
+                class.properties.push(Property {
+                    name: word(&args[0]).to_string(),
+                    id: auid(&args[1]),
+                    pid: number(&args[2]) as u16,
+                    type_name: type_name(&args[3]),
+                    is_mandatory: boolean(&args[4]),
+                    is_unique_identifier: boolean(&args[5]),
+                });
+            }
+            "AAF_TYPE_DEFINITION_INTEGER" => types.push(simple(Kind::Integer {
+                size: number(&args[2]) as u8,
+                is_signed: boolean(&args[3]),
+            })),
+            "AAF_TYPE_DEFINITION_ENUMERATION" => types.push(simple(Kind::Enumeration {
+                element_type: type_name(&args[2]),
+                elements: vec![],
+            })),
+            "AAF_TYPE_DEFINITION_ENUMERATION_MEMBER" => {
+                if let Some(Type {
+                    kind: Kind::Enumeration { elements, .. },
+                    ..
+                }) = types.last_mut()
+                {
+                    elements.push((word(&args[0]).to_string(), number(&args[1])));
+                }
+            }
+            "AAF_TYPE_DEFINITION_RECORD" => types.push(simple(Kind::Record { members: vec![] })),
+            "AAF_TYPE_DEFINITION_RECORD_FIELD" => {
+                if let Some(Type {
+                    kind: Kind::Record { members },
+                    ..
+                }) = types.last_mut()
+                {
+                    members.push((word(&args[0]).to_string(), type_name(&args[1])));
+                }
+            }
+            "AAF_TYPE_DEFINITION_STRONG_REFERENCE" => types.push(simple(Kind::StrongReference {
+                class: type_name(&args[2]),
+            })),
+            "AAF_TYPE_DEFINITION_STRONG_REFERENCE_SET" => {
+                types.push(simple(Kind::StrongReferenceSet {
+                    class: type_name(&args[2]),
+                }))
+            }
+            "AAF_TYPE_DEFINITION_STRONG_REFERENCE_VECTOR" => {
+                types.push(simple(Kind::StrongReferenceVector {
+                    class: type_name(&args[2]),
+                }))
+            }
+            "AAF_TYPE_DEFINITION_WEAK_REFERENCE" => types.push(simple(Kind::WeakReference {
+                class: type_name(&args[2]),
+                target: vec![],
+            })),
+            "AAF_TYPE_DEFINITION_WEAK_REFERENCE_MEMBER" => {
+                if let Some(Type {
+                    kind: Kind::WeakReference { target, .. },
+                    ..
+                }) = types.last_mut()
+                {
+                    target.push((word(&args[0]).to_string(), word(&args[1]).to_string()));
+                }
+            }
+            "AAF_TYPE_DEFINITION_WEAK_REFERENCE_SET" => {
+                types.push(simple(Kind::WeakReferenceSet {
+                    class: type_name(&args[2]),
+                }))
+            }
+            "AAF_TYPE_DEFINITION_WEAK_REFERENCE_VECTOR" => {
+                types.push(simple(Kind::WeakReferenceVector {
+                    class: type_name(&args[2]),
+                }))
+            }
+            "AAF_TYPE_DEFINITION_VARYING_ARRAY" => types.push(simple(Kind::VaryingArray {
+                element_type: type_name(&args[2]),
+            })),
+            "AAF_TYPE_DEFINITION_FIXED_ARRAY" => types.push(simple(Kind::FixedArray {
+                element_type: type_name(&args[2]),
+                count: number(&args[3]) as u32,
+            })),
+            "AAF_TYPE_DEFINITION_SET" => types.push(simple(Kind::Set {
+                element_type: type_name(&args[2]),
+            })),
+            "AAF_TYPE_DEFINITION_STRING" => types.push(simple(Kind::String {
+                element_type: type_name(&args[2]),
+            })),
+            "AAF_TYPE_DEFINITION_STREAM" => types.push(simple(Kind::Stream)),
+            "AAF_TYPE_DEFINITION_RENAME" => types.push(simple(Kind::Rename {
+                renamed_type: type_name(&args[2]),
+            })),
+            "AAF_TYPE_DEFINITION_EXTENDIBLE_ENUMERATION" => {
+                types.push(simple(Kind::ExtendibleEnumeration { elements: vec![] }))
+            }
+            "AAF_TYPE_DEFINITION_EXTENDIBLE_ENUMERATION_MEMBER" => {
+                if let Some(Type {
+                    kind: Kind::ExtendibleEnumeration { elements },
+                    ..
+                }) = types.last_mut()
+                {
+                    elements.push((word(&args[0]).to_string(), auid(&args[1])));
+                }
+            }
+            "AAF_TYPE_DEFINITION_INDIRECT" => types.push(simple(Kind::Indirect)),
+            "AAF_TYPE_DEFINITION_OPAQUE" => types.push(simple(Kind::Opaque)),
+            "AAF_TYPE_DEFINITION_CHARACTER" => types.push(simple(Kind::Character)),
+            // Class aliases and the predefined definition instances are not
+            // part of the class model.
+            _ => {}
+        }
+    }
+
+    (classes, types)
+}
+
+/// `name` in snake case, e.g. `CDCIDescriptor` becomes `cdci_descriptor`
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut retval = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            // A plural acronym, e.g. the `IDs` of `ChannelIDs`, is one word
+            let plural = chars.get(i + 1) == Some(&'s')
+                && chars.get(i + 2).map_or(true, |n| n.is_uppercase());
+            let next_lower = !plural && chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                retval.push('_');
+            }
+        }
+        retval.extend(c.to_lowercase());
+    }
+    retval
+}
+
+fn method_name(property: &str) -> String {
+    let name = snake_case(property);
+    match name.as_str() {
+        "type" | "ref" | "match" | "move" | "self" | "static" | "use" | "where" | "loop" => {
+            format!("r#{}", name)
+        }
+        _ => name,
+    }
+}
+
+fn uuid_literal(id: &Auid) -> String {
+    let bytes: Vec<String> = id.iter().map(|b| format!("0x{:02x}", b)).collect();
+    format!("Uuid::from_bytes([{}])", bytes.join(", "))
+}
+
+fn uuid_string(id: &Auid) -> String {
+    let hex: String = id.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+struct Model {
+    classes: Vec<Class>,
+    types: Vec<Type>,
+    class_index: HashMap<String, usize>,
+    type_index: HashMap<String, usize>,
+}
+
+impl Model {
+    fn new(classes: Vec<Class>, types: Vec<Type>) -> Self {
+        let class_index = classes
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.name.clone(), i))
+            .collect();
+        let type_index = types
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.name.clone(), i))
+            .collect();
+        Self {
+            classes,
+            types,
+            class_index,
+            type_index,
+        }
+    }
+
+    fn class(&self, name: &str) -> &Class {
+        match self.class_index.get(name) {
+            Some(&i) => &self.classes[i],
+            None => panic!("{} names undefined class {}", META_DICTIONARY_H, name),
+        }
+    }
+
+    /// The handle type of class `name`, or `None` for `InterchangeObject`,
+    /// which references are not opened as.
+    fn handle(&self, name: &str) -> Option<String> {
+        (self.class(name).name != "InterchangeObject").then(|| name.to_string())
+    }
+
+    fn type_def(&self, name: &str) -> &Type {
+        match self.type_index.get(name) {
+            Some(&i) => &self.types[i],
+            None => panic!("{} names undefined type {}", META_DICTIONARY_H, name),
+        }
+    }
+
+    fn type_id(&self, name: &str) -> String {
+        uuid_literal(&self.type_def(name).id)
+    }
+
+    fn class_id(&self, name: &str) -> String {
+        uuid_literal(&self.class(name).id)
+    }
+
+    /// The AUID of property `name` of class `class`, for weak reference
+    /// target paths.
+    fn property_id(&self, class: &str, name: &str) -> Auid {
+        if class == "Root" {
+            return ROOT_PROPERTIES
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, id)| *id)
+                .unwrap_or_else(|| panic!("unknown root property {}", name));
+        }
+        self.lineage(class)
+            .into_iter()
+            .flat_map(|c| c.properties.iter())
+            .find(|p| p.name == name)
+            .map(|p| p.id)
+            .unwrap_or_else(|| panic!("{} has no property {}", class, name))
+    }
+
+    /// Class `name` followed by its ancestors.
+    fn lineage(&self, name: &str) -> Vec<&Class> {
+        let mut retval = vec![];
+        let mut next = name;
+        while next != "Root" {
+            let class = self.class(next);
+            retval.push(class);
+            next = &class.parent;
+        }
+        retval
+    }
+
+    /// The concrete classes that are `name` or derive from it.
+    fn concrete_descendants(&self, name: &str) -> Vec<&str> {
+        self.classes
+            .iter()
+            .filter(|c| c.is_concrete && self.lineage(&c.name).iter().any(|a| a.name == name))
+            .map(|c| c.name.as_str())
+            .collect()
+    }
+
+    /// The abstract ancestors of class `name` that have an enum, nearest
+    /// first.
+    fn abstract_ancestors(&self, name: &str) -> Vec<&str> {
+        self.lineage(name)
+            .into_iter()
+            .skip(1)
+            .filter(|c| !c.is_concrete && c.name != "InterchangeObject")
+            .map(|c| c.name.as_str())
+            .collect()
+    }
+
+    /// How a property of type `name` is read, and the Rust type data
+    /// properties decode to.
+    fn access(&self, name: &str) -> Access {
+        let data = |t: &str| Access::Data(t.to_string());
+        match name {
+            "Boolean" => return data("bool"),
+            "AUID" => return data("Uuid"),
+            "MobIDType" => return data("MobID"),
+            "Rational" => return data("Rational"),
+            "ProductVersion" => return data("ProductVersion"),
+            "TimeStamp" | "VersionType" | "String" | "PositionType" | "LengthType"
+            | "JPEGTableIDType" | "PhaseFrameType" => return data(name),
+            "StringArray" => return data("Vec<String>"),
+            "AUIDArray" | "AUIDSet" => return data("Vec<Uuid>"),
+            "Int32Array" => return data("Vec<AAFInt32>"),
+            "UInt32Array" => return data("Vec<AAFUInt32>"),
+            "Int64Array" => return data("Vec<AAFInt64>"),
+            "RGBALayout" => return data("Vec<RGBAComponent>"),
+            _ => {}
+        }
+        match &self.type_def(name).kind {
+            Kind::Integer { .. } => data(&format!("AAF{}", name)),
+            Kind::Enumeration { .. } => data(name),
+            Kind::ExtendibleEnumeration { .. } => data("Uuid"),
+            Kind::Rename { renamed_type } => self.access(renamed_type),
+            Kind::StrongReference { class } | Kind::WeakReference { class, .. } => {
+                Access::Reference(self.handle(class))
+            }
+            Kind::StrongReferenceSet { class }
+            | Kind::StrongReferenceVector { class }
+            | Kind::WeakReferenceSet { class }
+            | Kind::WeakReferenceVector { class } => Access::Collection(self.handle(class)),
+            Kind::Stream => Access::Stream,
+            _ => data("Vec<u8>"),
+        }
+    }
+}
+
+fn write_class_ids(model: &Model) -> String {
+    let mut out = String::new();
+    for class in &model.classes {
+        writeln!(out, "/// `{}` {{{}}}", class.name, uuid_string(&class.id)).unwrap();
+        writeln!(
+            out,
+            "pub const {}: Uuid = {};",
+            snake_case(&class.name).to_uppercase(),
+            uuid_literal(&class.id)
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// The Rust name of an enumeration member: the part after a `Prefix_`,
+/// unless that starts with a digit, and with a leading `8mm` spelt out.
+fn variant_name(member: &str) -> String {
+    let name = match member.split_once('_') {
+        Some((prefix, rest)) if rest.starts_with(|c: char| c.is_ascii_digit()) => {
+            format!("{}{}", prefix, rest)
+        }
+        Some((_, rest)) => rest.to_string(),
+        None => member.to_string(),
+    };
+    match name.strip_prefix("8mm") {
+        Some(rest) => format!("EightMM{}", rest),
+        None => name,
+    }
+}
+
+fn write_enums(model: &Model) -> String {
+    let mut out = String::new();
+    for t in &model.types {
+        let (element_type, elements) = match &t.kind {
+            Kind::Enumeration {
+                element_type,
+                elements,
+            } if t.name != "Boolean" => (format!("AAF{}", element_type), elements),
+            _ => continue,
+        };
+        let variants: Vec<(String, i64)> = elements
+            .iter()
+            .map(|(member, value)| (variant_name(member), *value))
+            .collect();
+
+        writeln!(out, "/// `{}` {{{}}}", t.name, uuid_string(&t.id)).unwrap();
+        writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]").unwrap();
+        writeln!(out, "pub enum {} {{", t.name).unwrap();
+        for (name, value) in &variants {
+            writeln!(out, "    /// {}", value).unwrap();
+            writeln!(out, "    {},", name).unwrap();
+        }
+        writeln!(out, "    /// A value the baseline does not define.").unwrap();
+        writeln!(out, "    Undefined({}),", element_type).unwrap();
+        writeln!(out, "}}\n").unwrap();
+
+        writeln!(out, "impl {} {{", t.name).unwrap();
+        writeln!(out, "    /// The value the member is stored as.").unwrap();
+        writeln!(out, "    pub fn value(&self) -> {} {{", element_type).unwrap();
+        writeln!(out, "        match self {{").unwrap();
+        for (name, value) in &variants {
+            writeln!(out, "            Self::{} => {},", name, value).unwrap();
+        }
+        writeln!(out, "            Self::Undefined(value) => *value,").unwrap();
+        writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+
+        writeln!(out, "impl From<{}> for {} {{", element_type, t.name).unwrap();
+        writeln!(out, "    fn from(value: {}) -> Self {{", element_type).unwrap();
+        writeln!(out, "        match value {{").unwrap();
+        let mut seen = vec![];
+        for (name, value) in &variants {
+            // The first of the members with the same value is decoded.
+            if !seen.contains(value) {
+                writeln!(out, "            {} => Self::{},", value, name).unwrap();
+                seen.push(*value);
+            }
+        }
+        writeln!(out, "            value => Self::Undefined(value),").unwrap();
+        writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+
+        writeln!(out, "impl AAFFrom for {} {{", t.name).unwrap();
+        writeln!(
+            out,
+            "    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {{"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        {}::aaf_from_endian(item, endian).map(Self::from)",
+            element_type
+        )
+        .unwrap();
+        writeln!(out, "    }}\n}}\n").unwrap();
+    }
+    out
+}
+
+fn write_type_ids(model: &Model) -> String {
+    let mut out = String::new();
+    for t in &model.types {
+        writeln!(out, "/// `{}` {{{}}}", t.name, uuid_string(&t.id)).unwrap();
+        writeln!(
+            out,
+            "pub const {}: Uuid = {};",
+            snake_case(&t.name.replace("UInt", "Uint")).to_uppercase(),
+            uuid_literal(&t.id)
+        )
+        .unwrap();
+    }
+    out
+}
+
+fn write_baseline(model: &Model) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "pub(super) fn classes() -> Vec<ClassDefinition> {{").unwrap();
+    writeln!(out, "    vec![").unwrap();
+    for class in &model.classes {
+        let parent = if class.parent == "Root" {
+            uuid_literal(&class.id)
+        } else {
+            model.class_id(&class.parent)
+        };
+        writeln!(
+            out,
+            "        class({}, {:?}, {}, {}, vec![",
+            uuid_literal(&class.id),
+            class.name,
+            parent,
+            class.is_concrete
+        )
+        .unwrap();
+        for p in &class.properties {
+            writeln!(
+                out,
+                "            property({}, {:?}, {}, {}, 0x{:04x}, {}),",
+                uuid_literal(&p.id),
+                p.name,
+                model.type_id(&p.type_name),
+                !p.is_mandatory,
+                p.pid,
+                p.is_unique_identifier
+            )
+            .unwrap();
+        }
+        writeln!(out, "        ]),").unwrap();
+    }
+    writeln!(out, "    ]").unwrap();
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(out, "pub(super) fn types() -> Vec<TypeDefinition> {{").unwrap();
+    writeln!(out, "    vec![").unwrap();
+    for t in &model.types {
+        let kind = match &t.kind {
+            Kind::Integer { size, is_signed } => format!(
+                "TypeDefinitionKind::Integer {{ size: {}, is_signed: {} }}",
+                size, is_signed
+            ),
+            Kind::Enumeration {
+                element_type,
+                elements,
+            } => {
+                let elements: Vec<String> = elements
+                    .iter()
+                    .map(|(n, v)| format!("({:?}.to_string(), {})", n, v))
+                    .collect();
+                format!(
+                    "TypeDefinitionKind::Enumeration {{ element_type: {}, elements: vec![{}] }}",
+                    model.type_id(element_type),
+                    elements.join(", ")
+                )
+            }
+            Kind::Record { members } => {
+                let members: Vec<String> = members
+                    .iter()
+                    .map(|(n, t)| format!("({:?}.to_string(), {})", n, model.type_id(t)))
+                    .collect();
+                format!(
+                    "TypeDefinitionKind::Record {{ members: vec![{}] }}",
+                    members.join(", ")
+                )
+            }
+            Kind::StrongReference { class } => format!(
+                "TypeDefinitionKind::StrongObjectReference {{ referenced_class: {} }}",
+                model.class_id(class)
+            ),
+            Kind::WeakReference { class, target } => {
+                let target: Vec<String> = target
+                    .iter()
+                    .map(|(name, parent)| uuid_literal(&model.property_id(parent, name)))
+                    .collect();
+                format!(
+                    "TypeDefinitionKind::WeakObjectReference {{ referenced_class: {}, target_set: vec![{}] }}",
+                    model.class_id(class),
+                    target.join(", ")
+                )
+            }
+            Kind::StrongReferenceSet { class } => format!(
+                "TypeDefinitionKind::Set {{ element_type: {} }}",
+                model.type_id(&format!("{}StrongReference", class))
+            ),
+            Kind::StrongReferenceVector { class } => format!(
+                "TypeDefinitionKind::VariableArray {{ element_type: {} }}",
+                model.type_id(&format!("{}StrongReference", class))
+            ),
+            Kind::WeakReferenceSet { class } => format!(
+                "TypeDefinitionKind::Set {{ element_type: {} }}",
+                model.type_id(&format!("{}WeakReference", class))
+            ),
+            Kind::WeakReferenceVector { class } => format!(
+                "TypeDefinitionKind::VariableArray {{ element_type: {} }}",
+                model.type_id(&format!("{}WeakReference", class))
+            ),
+            Kind::VaryingArray { element_type } => format!(
+                "TypeDefinitionKind::VariableArray {{ element_type: {} }}",
+                model.type_id(element_type)
+            ),
+            Kind::FixedArray {
+                element_type,
+                count,
+            } => format!(
+                "TypeDefinitionKind::FixedArray {{ element_type: {}, element_count: {} }}",
+                model.type_id(element_type),
+                count
+            ),
+            Kind::Set { element_type } => format!(
+                "TypeDefinitionKind::Set {{ element_type: {} }}",
+                model.type_id(element_type)
+            ),
+            Kind::String { element_type } => format!(
+                "TypeDefinitionKind::String {{ element_type: {} }}",
+                model.type_id(element_type)
+            ),
+            Kind::Stream => "TypeDefinitionKind::Stream".to_string(),
+            Kind::Rename { renamed_type } => format!(
+                "TypeDefinitionKind::Rename {{ renamed_type: {} }}",
+                model.type_id(renamed_type)
+            ),
+            Kind::ExtendibleEnumeration { elements } => {
+                let elements: Vec<String> = elements
+                    .iter()
+                    .map(|(n, id)| format!("({:?}.to_string(), {})", n, uuid_literal(id)))
+                    .collect();
+                format!(
+                    "TypeDefinitionKind::ExtendibleEnumeration {{ elements: vec![{}] }}",
+                    elements.join(", ")
+                )
+            }
+            Kind::Indirect => "TypeDefinitionKind::Indirect".to_string(),
+            Kind::Opaque => "TypeDefinitionKind::Opaque".to_string(),
+            Kind::Character => "TypeDefinitionKind::Character".to_string(),
+        };
+        writeln!(
+            out,
+            "        type_def({}, {:?}, {}),",
+            uuid_literal(&t.id),
+            t.name,
+            kind
+        )
+        .unwrap();
+    }
+    writeln!(out, "    ]").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    out
+}
+
+/// Write the accessors of the properties of class `name` and its
+/// ancestors.
+fn write_accessors(out: &mut String, model: &Model, name: &str) {
+    writeln!(out, "impl<F: Read + Seek> {}<F> {{", name).unwrap();
+    // `InterchangeObject`'s properties are the object's class, which is
+    // its descriptor's `auid`, and `AAFObject::generation()`.
+    let lineage = model.lineage(name);
+    let properties = lineage
+        .iter()
+        .rev()
+        .filter(|c| c.name != "InterchangeObject")
+        .flat_map(|c| c.properties.iter());
+    for p in properties {
+        let optional = !p.is_mandatory;
+        let (ret, getter) = match model.access(&p.type_name) {
+            Access::Data(t) if optional => (format!("Option<{}>", t), "get_optional_data"),
+            Access::Data(t) => (t, "get_required_data"),
+            Access::Reference(Some(c)) if optional => {
+                (format!("Option<{}<F>>", c), "get_optional_object")
+            }
+            Access::Reference(Some(c)) => (format!("{}<F>", c), "get_required_object"),
+            Access::Reference(None) if optional => (
+                "Option<InterchangeObjectDescriptor>".to_string(),
+                "get_optional_reference",
+            ),
+            Access::Reference(None) => (
+                "InterchangeObjectDescriptor".to_string(),
+                "get_required_reference",
+            ),
+            Access::Collection(Some(c)) => (format!("Vec<{}<F>>", c), "get_objects"),
+            Access::Collection(None) => (
+                "Vec<InterchangeObjectDescriptor>".to_string(),
+                "get_collection",
+            ),
+            Access::Stream if optional => (
+                "Option<PropertyStream<F>>".to_string(),
+                "get_optional_stream",
+            ),
+            Access::Stream => ("PropertyStream<F>".to_string(), "get_required_stream"),
+        };
+        writeln!(
+            out,
+            "    /// `{}`, a{} `{}`
+    pub fn {}(&self) -> Result<{}> {{
+        let pid = 0x{:04x};
+        self.{}(pid)
+    }}
+",
+            p.name,
+            if optional { "n optional" } else { "" },
+            p.type_name,
+            method_name(&p.name),
+            ret,
+            p.pid,
+            getter
+        )
+        .unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+}
+
+fn write_struct(out: &mut String, model: &Model, class: &Class) {
+    let name = &class.name;
+    writeln!(
+        out,
+        "/// A `{}` object {{{}}}
+pub struct {}<F> {{
+    file: SharedAAFFile<F>,
+    object: InterchangeObjectDescriptor,
+}}
+
+impl<F> Clone for {}<F> {{
+    fn clone(&self) -> Self {{
+        Self {{
+            file: self.file.clone(),
+            object: self.object.clone(),
+        }}
+    }}
+}}
+
+impl<F: Read + Seek> AAFObject<F> for {}<F> {{
+    fn make(file: SharedAAFFile<F>, object: InterchangeObjectDescriptor) -> Result<Self> {{
+        Ok(Self {{ file, object }})
+    }}
+
+    fn file(&self) -> &SharedAAFFile<F> {{
+        &self.file
+    }}
+
+    fn descriptor(&self) -> &InterchangeObjectDescriptor {{
+        &self.object
+    }}
+}}
+",
+        name,
+        uuid_string(&class.id),
+        name,
+        name,
+        name
+    )
+    .unwrap();
+
+    write_accessors(out, model, name);
+}
+
+/// An enum of the concrete classes derived from the abstract class `class`,
+/// chosen by the class of the object it is made with.
+fn write_enum(out: &mut String, model: &Model, class: &Class) {
+    let name = &class.name;
+    let variants: Vec<&str> = model
+        .concrete_descendants(name)
+        .into_iter()
+        .chain(["Unknown"])
+        .collect();
+    let struct_name = |v: &str| {
+        if v == "Unknown" {
+            "UnknownObject".to_string()
+        } else {
+            v.to_string()
+        }
+    };
+
+    writeln!(
+        out,
+        "/// A `{}` object {{{}}}, by its concrete class",
+        name,
+        uuid_string(&class.id)
+    )
+    .unwrap();
+    writeln!(out, "pub enum {}<F> {{", name).unwrap();
+    for v in &variants {
+        if *v == "Unknown" {
+            writeln!(
+                out,
+                "    /// An object of a class derived from `{}` that is not in the baseline",
+                name
+            )
+            .unwrap();
+        }
+        writeln!(out, "    {}({}<F>),", v, struct_name(v)).unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    let arms = |body: &str| -> String {
+        variants
+            .iter()
+            .map(|v| format!("            Self::{}(o) => {},\n", v, body))
+            .collect()
+    };
+    let candidates: Vec<String> = variants
+        .iter()
+        .filter(|v| **v != "Unknown")
+        .map(|v| format!("class_ids::{}", snake_case(v).to_uppercase()))
+        .collect();
+    let make_arms: String = variants
+        .iter()
+        .filter(|v| **v != "Unknown")
+        .map(|v| {
+            format!(
+                "            Some(class_ids::{}) => Ok(Self::{}({}::make(file, object)?)),\n",
+                snake_case(v).to_uppercase(),
+                v,
+                v
+            )
+        })
+        .collect();
+    // The baseline has abstract classes with no concrete descendants.
+    let make_body = if candidates.is_empty() {
+        "        Ok(Self::Unknown(UnknownObject::make(file, object)?))\n".to_string()
+    } else {
+        format!(
+            "        let candidates = [{}];
+        match concrete_class(&file, &object.auid, &candidates)? {{
+{}            _ => Ok(Self::Unknown(UnknownObject::make(file, object)?)),
+        }}
+",
+            candidates.join(", "),
+            make_arms
+        )
+    };
+    let clone_arms: String = variants
+        .iter()
+        .map(|v| format!("            Self::{}(o) => Self::{}(o.clone()),\n", v, v))
+        .collect();
+
+    writeln!(
+        out,
+        "impl<F> Clone for {name}<F> {{
+    fn clone(&self) -> Self {{
+        match self {{
+{clone_arms}        }}
+    }}
+}}
+
+impl<F: Read + Seek> AAFObject<F> for {name}<F> {{
+    fn make(file: SharedAAFFile<F>, object: InterchangeObjectDescriptor) -> Result<Self> {{
+{make_body}    }}
+
+    fn file(&self) -> &SharedAAFFile<F> {{
+        match self {{
+{file_arms}        }}
+    }}
+
+    fn descriptor(&self) -> &InterchangeObjectDescriptor {{
+        match self {{
+{descriptor_arms}        }}
+    }}
+}}
+",
+        name = name,
+        clone_arms = clone_arms,
+        make_body = make_body,
+        file_arms = arms("o.file()"),
+        descriptor_arms = arms("o.descriptor()"),
+    )
+    .unwrap();
+
+    write_accessors(out, model, name);
+}
+
+/// Conversions from class `class` to the enums of its abstract ancestors,
+/// and back from those enums to the enum of `class` if it is abstract.
+fn write_conversions(out: &mut String, model: &Model, class: &Class) {
+    let name = &class.name;
+    for ancestor in model.abstract_ancestors(name) {
+        if class.is_concrete {
+            writeln!(
+                out,
+                "impl<F> From<{name}<F>> for {ancestor}<F> {{
+    fn from(object: {name}<F>) -> Self {{
+        Self::{name}(object)
+    }}
+}}
+",
+                name = name,
+                ancestor = ancestor
+            )
+            .unwrap();
+            continue;
+        }
+
+        let variants = model.concrete_descendants(name);
+        let up: String = variants
+            .iter()
+            .map(|v| format!("            {}::{}(o) => Self::{}(o),\n", name, v, v))
+            .collect();
+        let down: String = variants
+            .iter()
+            .map(|v| {
+                format!(
+                    "            {}::{}(o) => Ok(Self::{}(o)),\n",
+                    ancestor, v, v
+                )
+            })
+            .collect();
+        writeln!(
+            out,
+            "impl<F> From<{name}<F>> for {ancestor}<F> {{
+    fn from(object: {name}<F>) -> Self {{
+        match object {{
+{up}            {name}::Unknown(o) => Self::Unknown(o),
+        }}
+    }}
+}}
+
+impl<F> TryFrom<{ancestor}<F>> for {name}<F> {{
+    type Error = {ancestor}<F>;
+
+    /// The object as a `{name}`, or the object back if its class is not
+    /// known to derive from `{name}`.
+    fn try_from(object: {ancestor}<F>) -> std::result::Result<Self, Self::Error> {{
+        match object {{
+{down}            other => Err(other),
+        }}
+    }}
+}}
+",
+            name = name,
+            ancestor = ancestor,
+            up = up,
+            down = down
+        )
+        .unwrap();
+    }
+}
+
+fn write_classes(model: &Model) -> String {
+    let mut out = String::new();
+
+    for class in &model.classes {
+        if class.is_concrete {
+            write_struct(&mut out, model, class);
+        } else if class.name != "InterchangeObject" {
+            write_enum(&mut out, model, class);
+        }
+        write_conversions(&mut out, model, class);
+    }
+
+    out
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", META_DICTIONARY_H);
+
+    let source = fs::read_to_string(META_DICTIONARY_H)
+        .unwrap_or_else(|e| panic!("could not read {}: {}", META_DICTIONARY_H, e));
+    let (classes, types) = read_tables(&parse(&lex(&source)));
+    let model = Model::new(classes, types);
+
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is not set");
+    let out_dir = Path::new(&out_dir);
+    fs::write(out_dir.join("class_ids.rs"), write_class_ids(&model)).unwrap();
+    fs::write(out_dir.join("type_ids.rs"), write_type_ids(&model)).unwrap();
+    fs::write(out_dir.join("enums.rs"), write_enums(&model)).unwrap();
+    fs::write(out_dir.join("baseline.rs"), write_baseline(&model)).unwrap();
+    fs::write(out_dir.join("classes.rs"), write_classes(&model)).unwrap();
+}
@@ -0,0 +1,7 @@
+/// class_ids.rs
+///
+/// The AUIDs of the baseline AAF classes, generated from
+/// AAFMetaDictionary.h.
+use uuid::Uuid;
+
+include!(concat!(env!("OUT_DIR"), "/class_ids.rs"));
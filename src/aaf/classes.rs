@@ -1,112 +1,222 @@
+use crate::aaf::class_ids;
+use crate::aaf::enums::*;
+use crate::error::{AAFError, Result};
+use crate::file::SharedAAFFile;
 use crate::interchange_object::InterchangeObjectDescriptor;
-use crate::file::AAFFile;
+use crate::properties::{decode_collection, decode_reference, PropertyValue};
+use crate::stream::PropertyStream;
 use crate::types::*;
-use crate::properties::PropertyValue;
 
 use std::io::{Read, Seek};
 use uuid::Uuid;
 
+const GENERATION_PID: OMPropertyId = 0x0102;
+const HEADER_IDENTIFICATION_LIST_PID: OMPropertyId = 0x3b06;
+const CONTENT_STORAGE_MOBS_PID: OMPropertyId = 0x1901;
 
-const GENERATION_PID : OMPropertyId = 0x0102; 
+/// A typed handle on an object in an AAF file.
+///
+/// Handles share their file, so any number of them can be held at once and
+/// each can open the objects its properties refer to.
+pub trait AAFObject<F: Read + Seek>: Sized {
+    /// A handle on the object `desc` describes.
+    ///
+    /// Fails if the class of the object cannot be looked up.
+    fn make(file: SharedAAFFile<F>, desc: InterchangeObjectDescriptor) -> Result<Self>;
 
-pub trait AAFObject<F> {
-    
-    fn make(file: AAFFile<F>, desc: InterchangeObjectDescriptor) -> Self;
-    
-    fn get_property_value(&mut self, pid: OMPropertyId) -> Option<PropertyValue>;
+    /// The file the object is in.
+    fn file(&self) -> &SharedAAFFile<F>;
 
-    fn generation(&mut self) -> Option<Uuid> {
-        let pid = GENERATION_PID;
-        self.get_optional_data(pid) 
+    fn descriptor(&self) -> &InterchangeObjectDescriptor;
+
+    /// A handle on another object in the same file.
+    fn object<T: AAFObject<F>>(&self, desc: InterchangeObjectDescriptor) -> Result<T> {
+        T::make(self.file().clone(), desc)
     }
 
-    fn get_optional_data<T: AAFFrom>(&mut self, pid: OMPropertyId) -> Option<T> {
-        match self.get_property_value(pid) {
-            Some(PropertyValue::Data(b)) => { Some(b[..].aaf_into()) }
-            _ => { None }
-        }
+    fn get_property_value(&self, pid: OMPropertyId) -> Result<Option<PropertyValue>> {
+        self.file().borrow_mut().get_value(self.descriptor(), pid)
     }
 
-    fn get_required_data<T: AAFFrom>(&mut self, pid: OMPropertyId) -> T {
+    fn generation(&self) -> Result<Option<Uuid>> {
+        let pid = GENERATION_PID;
         self.get_optional_data(pid)
-            .expect(
-                &format!("Required property (pid {}) not found", pid)
-                )
     }
-}
 
-pub struct Header<F> {
-    file: AAFFile<F>,
-    object: InterchangeObjectDescriptor
-}
+    fn get_optional_data<T: AAFFrom>(&self, pid: OMPropertyId) -> Result<Option<T>> {
+        self.file().borrow_mut().get_data(self.descriptor(), pid)
+    }
 
-impl<F: Read + Seek> AAFObject<F> for Header<F> { 
+    fn get_required_data<T: AAFFrom>(&self, pid: OMPropertyId) -> Result<T> {
+        self.get_optional_data(pid)?
+            .ok_or_else(|| AAFError::MissingProperty {
+                path: self.descriptor().path.clone(),
+                pid,
+            })
+    }
 
-    fn make(file: AAFFile<F>, object: InterchangeObjectDescriptor) -> Self {
-        Self { file: file, object: object }
+    fn get_required_value(&self, pid: OMPropertyId) -> Result<PropertyValue> {
+        self.get_property_value(pid)?
+            .ok_or_else(|| AAFError::MissingProperty {
+                path: self.descriptor().path.clone(),
+                pid,
+            })
     }
 
-    fn get_property_value(&mut self, pid : OMPropertyId) -> Option<PropertyValue> {
-        self.file.get_value(&self.object, pid)
+    fn get_optional_stream(&self, pid: OMPropertyId) -> Result<Option<PropertyStream<F>>> {
+        PropertyStream::open(self.file().clone(), self.descriptor(), pid)
     }
-}
 
-impl<F> Header<F> where F: Read + Seek {
+    fn get_required_stream(&self, pid: OMPropertyId) -> Result<PropertyStream<F>> {
+        self.get_optional_stream(pid)?
+            .ok_or_else(|| AAFError::MissingProperty {
+                path: self.descriptor().path.clone(),
+                pid,
+            })
+    }
 
-    pub fn byte_order(&mut self) -> AAFUInt16 {
-        let pid = 0x3b01;
-        self.get_required_data(pid)
+    fn get_optional_reference(
+        &self,
+        pid: OMPropertyId,
+    ) -> Result<Option<InterchangeObjectDescriptor>> {
+        let value = self.get_property_value(pid)?;
+        decode_reference(value, &self.descriptor().path, pid)
     }
 
-    pub fn last_modified(&mut self) -> TimeStamp {
-        let pid = 0x3b02;
-        self.get_required_data(pid)
-     }
+    fn get_required_reference(&self, pid: OMPropertyId) -> Result<InterchangeObjectDescriptor> {
+        self.get_optional_reference(pid)?
+            .ok_or_else(|| AAFError::MissingProperty {
+                path: self.descriptor().path.clone(),
+                pid,
+            })
+    }
 
-    pub fn version(&mut self) -> VersionType {
-        let pid = 0x3b05; 
-        self.get_required_data(pid)
+    fn get_collection(&self, pid: OMPropertyId) -> Result<Vec<InterchangeObjectDescriptor>> {
+        let value = self.get_property_value(pid)?;
+        decode_collection(value, &self.descriptor().path, pid)
     }
-    
-    pub fn object_model_version(&mut self) -> Option<AAFUInt32> {
-        let pid = 0x3b07;
-        self.get_optional_data(pid)
+
+    fn get_optional_object<T: AAFObject<F>>(&self, pid: OMPropertyId) -> Result<Option<T>> {
+        self.get_optional_reference(pid)?
+            .map(|obj| self.object(obj))
+            .transpose()
     }
 
-    pub fn operational_pattern(&mut self) -> Option<Uuid> {
-        let pid = 0x3b09;
-        self.get_optional_data(pid)
+    fn get_required_object<T: AAFObject<F>>(&self, pid: OMPropertyId) -> Result<T> {
+        let obj = self.get_required_reference(pid)?;
+        self.object(obj)
     }
 
-    pub fn content(&mut self) -> ContentStorage<F> {
-        todo!()
+    fn get_objects<T: AAFObject<F>>(&self, pid: OMPropertyId) -> Result<Vec<T>> {
+        let objs = self.get_collection(pid)?;
+        objs.into_iter().map(|obj| self.object(obj)).collect()
     }
+}
 
-    pub fn dictionary(&mut self) -> Dictionary<F> {
-        todo!()
+impl<F: Read + Seek> Header<F> {
+    /// The `Identification` of the application that last modified the
+    /// file, the last in `IdentificationList`.
+    pub fn last_identification(&self) -> Result<Identification<F>> {
+        self.identification_list()?
+            .pop()
+            .ok_or_else(|| AAFError::MissingProperty {
+                path: self.object.path.clone(),
+                pid: HEADER_IDENTIFICATION_LIST_PID,
+            })
     }
+}
 
-    pub fn identification_list(&mut self) -> Vec<Identification<F>> {
-        todo!()
+impl<F: Read + Seek> ContentStorage<F> {
+    /// The mob in `Mobs` with MobID `id`, found by the set's key.
+    pub fn mob_by_id(&self, id: &MobID) -> Result<Option<Mob<F>>> {
+        let obj = self.file().borrow_mut().get_set_member(
+            self.descriptor(),
+            CONTENT_STORAGE_MOBS_PID,
+            &id.0,
+        )?;
+        obj.map(|obj| self.object(obj)).transpose()
+    }
+
+    /// The `CompositionMob`s in `Mobs`.
+    pub fn composition_mobs(&self) -> Result<Vec<CompositionMob<F>>> {
+        let mobs = self.mobs()?.into_iter().filter_map(|mob| match mob {
+            Mob::CompositionMob(mob) => Some(mob),
+            _ => None,
+        });
+        Ok(mobs.collect())
+    }
+
+    /// The `MasterMob`s in `Mobs`.
+    pub fn master_mobs(&self) -> Result<Vec<MasterMob<F>>> {
+        let mobs = self.mobs()?.into_iter().filter_map(|mob| match mob {
+            Mob::MasterMob(mob) => Some(mob),
+            _ => None,
+        });
+        Ok(mobs.collect())
+    }
+
+    /// The `SourceMob`s in `Mobs`.
+    pub fn source_mobs(&self) -> Result<Vec<SourceMob<F>>> {
+        let mobs = self.mobs()?.into_iter().filter_map(|mob| match mob {
+            Mob::SourceMob(mob) => Some(mob),
+            _ => None,
+        });
+        Ok(mobs.collect())
     }
 }
 
-pub struct MetaDictionary<F> {
-    file: AAFFile<F>,
-    object: InterchangeObjectDescriptor
+/// An object of a class that is not in the baseline and that the file's
+/// `MetaDictionary` does not derive from one of the classes asked for.
+pub struct UnknownObject<F> {
+    file: SharedAAFFile<F>,
+    object: InterchangeObjectDescriptor,
 }
 
-pub struct Dictionary<F> {
-    file: AAFFile<F>,
-    object: InterchangeObjectDescriptor
+impl<F> Clone for UnknownObject<F> {
+    fn clone(&self) -> Self {
+        Self {
+            file: self.file.clone(),
+            object: self.object.clone(),
+        }
+    }
 }
 
-pub struct ContentStorage<F> {
-    file: AAFFile<F>,
-    object: InterchangeObjectDescriptor
+impl<F: Read + Seek> AAFObject<F> for UnknownObject<F> {
+    fn make(file: SharedAAFFile<F>, object: InterchangeObjectDescriptor) -> Result<Self> {
+        Ok(Self { file, object })
+    }
+
+    fn file(&self) -> &SharedAAFFile<F> {
+        &self.file
+    }
+
+    fn descriptor(&self) -> &InterchangeObjectDescriptor {
+        &self.object
+    }
 }
 
-pub struct Identification<F> {
-    file: AAFFile<F>,
-    object: InterchangeObjectDescriptor
+/// The first of `class` and its ancestors that is one of `candidates`.
+///
+/// Classes that are not in the baseline are looked up in the file's
+/// `MetaDictionary`; `None` if none of them is a candidate.
+fn concrete_class<F: Read + Seek>(
+    file: &SharedAAFFile<F>,
+    class: &Uuid,
+    candidates: &[Uuid],
+) -> Result<Option<Uuid>> {
+    if candidates.contains(class) {
+        return Ok(Some(*class));
+    }
+    let mut file = file.borrow_mut();
+    let md = file.meta_dictionary()?;
+    Ok(md
+        .resolve_lineage(class)
+        .iter()
+        .map(|c| c.identification)
+        .find(|id| candidates.contains(id)))
 }
+
+// An accessor struct for each concrete baseline class and an enum of the
+// concrete classes of each abstract one, generated by build.rs from
+// AAFMetaDictionary.h.
+include!(concat!(env!("OUT_DIR"), "/classes.rs"));
@@ -0,0 +1,10 @@
+/// enums.rs
+///
+/// The baseline enumerated types, generated from AAFMetaDictionary.h.
+///
+/// Each decodes the values the baseline defines to a member, and any other
+/// value to `Undefined`.
+use crate::error::Result;
+use crate::types::*;
+
+include!(concat!(env!("OUT_DIR"), "/enums.rs"));
@@ -1,3 +0,0 @@
-struct AAFFileObject4k {
-
-}
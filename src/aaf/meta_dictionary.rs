@@ -0,0 +1,632 @@
+/// meta_dictionary.rs
+///
+/// The class and type definitions an AAF file was written with.
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::sync::OnceLock;
+
+use uuid::Uuid;
+
+use crate::aaf::class_ids;
+use crate::error::{AAFError, Result};
+use crate::file::AAFFile;
+use crate::interchange_object::InterchangeObjectDescriptor;
+use crate::types::*;
+
+const CLASS_DEFINITIONS_PID: OMPropertyId = 0x0003;
+const TYPE_DEFINITIONS_PID: OMPropertyId = 0x0004;
+
+const IDENTIFICATION_PID: OMPropertyId = 0x0005;
+const NAME_PID: OMPropertyId = 0x0006;
+const DESCRIPTION_PID: OMPropertyId = 0x0007;
+
+const PARENT_CLASS_PID: OMPropertyId = 0x0008;
+const PROPERTIES_PID: OMPropertyId = 0x0009;
+const IS_CONCRETE_PID: OMPropertyId = 0x000a;
+
+const TYPE_PID: OMPropertyId = 0x000b;
+const IS_OPTIONAL_PID: OMPropertyId = 0x000c;
+const LOCAL_IDENTIFICATION_PID: OMPropertyId = 0x000d;
+const IS_UNIQUE_IDENTIFIER_PID: OMPropertyId = 0x000e;
+
+const INTEGER_SIZE_PID: OMPropertyId = 0x000f;
+const INTEGER_IS_SIGNED_PID: OMPropertyId = 0x0010;
+const STRONG_REF_REFERENCED_TYPE_PID: OMPropertyId = 0x0011;
+const WEAK_REF_REFERENCED_TYPE_PID: OMPropertyId = 0x0012;
+const WEAK_REF_TARGET_SET_PID: OMPropertyId = 0x0013;
+const ENUM_ELEMENT_TYPE_PID: OMPropertyId = 0x0014;
+const ENUM_ELEMENT_NAMES_PID: OMPropertyId = 0x0015;
+const ENUM_ELEMENT_VALUES_PID: OMPropertyId = 0x0016;
+const FIXED_ARRAY_ELEMENT_TYPE_PID: OMPropertyId = 0x0017;
+const FIXED_ARRAY_ELEMENT_COUNT_PID: OMPropertyId = 0x0018;
+const VARIABLE_ARRAY_ELEMENT_TYPE_PID: OMPropertyId = 0x0019;
+const SET_ELEMENT_TYPE_PID: OMPropertyId = 0x001a;
+const STRING_ELEMENT_TYPE_PID: OMPropertyId = 0x001b;
+const RECORD_MEMBER_TYPES_PID: OMPropertyId = 0x001c;
+const RECORD_MEMBER_NAMES_PID: OMPropertyId = 0x001d;
+const RENAMED_TYPE_PID: OMPropertyId = 0x001e;
+const EXT_ENUM_ELEMENT_NAMES_PID: OMPropertyId = 0x001f;
+const EXT_ENUM_ELEMENT_VALUES_PID: OMPropertyId = 0x0020;
+
+/// A class definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassDefinition {
+    pub identification: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// The AUID of the immediate ancestor class. Root classes name
+    /// themselves as their parent.
+    pub parent: Uuid,
+    pub is_concrete: bool,
+    /// The properties this class defines, not including those it inherits.
+    pub properties: Vec<PropertyDefinition>,
+}
+
+impl ClassDefinition {
+    /// `true` if this class has no ancestor.
+    pub fn is_root(&self) -> bool {
+        self.parent == self.identification
+    }
+}
+
+/// A property definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyDefinition {
+    pub identification: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// The AUID of the property's `TypeDefinition`
+    pub type_id: Uuid,
+    pub is_optional: bool,
+    /// The pid the property is stored with in this file
+    pub local_identification: OMPropertyId,
+    pub is_unique_identifier: bool,
+}
+
+/// A type definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDefinition {
+    pub identification: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub kind: TypeDefinitionKind,
+}
+
+/// The kind of a `TypeDefinition` and its kind-specific properties.
+///
+/// Types are referred to by their AUID.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeDefinitionKind {
+    Integer {
+        size: u8,
+        is_signed: bool,
+    },
+    StrongObjectReference {
+        referenced_class: Uuid,
+    },
+    WeakObjectReference {
+        referenced_class: Uuid,
+        target_set: Vec<Uuid>,
+    },
+    Enumeration {
+        element_type: Uuid,
+        elements: Vec<(String, AAFInt64)>,
+    },
+    FixedArray {
+        element_type: Uuid,
+        element_count: u32,
+    },
+    VariableArray {
+        element_type: Uuid,
+    },
+    Set {
+        element_type: Uuid,
+    },
+    String {
+        element_type: Uuid,
+    },
+    Stream,
+    Record {
+        members: Vec<(String, Uuid)>,
+    },
+    Rename {
+        renamed_type: Uuid,
+    },
+    ExtendibleEnumeration {
+        elements: Vec<(String, Uuid)>,
+    },
+    Indirect,
+    Opaque,
+    Character,
+}
+
+/// The class and type definitions stored in a file's `MetaDictionary`.
+#[derive(Debug, Clone, Default)]
+pub struct MetaDictionary {
+    classes: HashMap<Uuid, ClassDefinition>,
+    types: HashMap<Uuid, TypeDefinition>,
+}
+
+impl MetaDictionary {
+    /// Read the `MetaDictionary` object `object` from `file`.
+    pub(crate) fn read<F: Read + Seek>(
+        file: &mut AAFFile<F>,
+        object: &InterchangeObjectDescriptor,
+    ) -> Result<Self> {
+        let mut retval = Self::default();
+
+        for class_obj in file.get_collection(object, CLASS_DEFINITIONS_PID)? {
+            let class = read_class_definition(file, &class_obj)?;
+            retval.classes.insert(class.identification, class);
+        }
+
+        for type_obj in file.get_collection(object, TYPE_DEFINITIONS_PID)? {
+            let type_def = read_type_definition(file, &type_obj)?;
+            retval.types.insert(type_def.identification, type_def);
+        }
+
+        Ok(retval)
+    }
+
+    /// The class and type definitions of the baseline AAF object model,
+    /// generated from the reference implementation's AAFMetaDictionary.h.
+    pub fn baseline() -> &'static MetaDictionary {
+        static BASELINE: OnceLock<MetaDictionary> = OnceLock::new();
+        BASELINE.get_or_init(|| MetaDictionary {
+            classes: baseline::classes()
+                .into_iter()
+                .map(|c| (c.identification, c))
+                .collect(),
+            types: baseline::types()
+                .into_iter()
+                .map(|t| (t.identification, t))
+                .collect(),
+        })
+    }
+
+    /// Add or replace the definition of a class.
+    pub(crate) fn insert_class(&mut self, class: ClassDefinition) {
+        self.classes.insert(class.identification, class);
+    }
+
+    /// Add or replace the definition of a type.
+    pub(crate) fn insert_type(&mut self, type_def: TypeDefinition) {
+        self.types.insert(type_def.identification, type_def);
+    }
+
+    /// All of the class definitions.
+    pub fn classes(&self) -> impl Iterator<Item = &ClassDefinition> {
+        self.classes.values()
+    }
+
+    /// All of the type definitions.
+    pub fn types(&self) -> impl Iterator<Item = &TypeDefinition> {
+        self.types.values()
+    }
+
+    /// The class definition with AUID `id`.
+    pub fn class(&self, id: &Uuid) -> Option<&ClassDefinition> {
+        self.classes.get(id)
+    }
+
+    /// The class definition named `name`.
+    pub fn class_by_name(&self, name: &str) -> Option<&ClassDefinition> {
+        self.classes.values().find(|c| c.name == name)
+    }
+
+    /// The type definition with AUID `id`.
+    pub fn type_def(&self, id: &Uuid) -> Option<&TypeDefinition> {
+        self.types.get(id)
+    }
+
+    /// The type definition named `name`.
+    pub fn type_by_name(&self, name: &str) -> Option<&TypeDefinition> {
+        self.types.values().find(|t| t.name == name)
+    }
+
+    /// The class `id` followed by each of its ancestors, ending with the
+    /// root class.
+    ///
+    /// Ancestors that are not defined in this dictionary end the list.
+    pub fn lineage(&self, id: &Uuid) -> Vec<&ClassDefinition> {
+        let mut retval: Vec<&ClassDefinition> = vec![];
+        let mut next = self.class(id);
+        while let Some(class) = next {
+            if retval
+                .iter()
+                .any(|c| c.identification == class.identification)
+            {
+                break;
+            }
+            retval.push(class);
+            next = if class.is_root() {
+                None
+            } else {
+                self.class(&class.parent)
+            };
+        }
+        retval
+    }
+
+    /// `true` if `id` is `ancestor` or one of its descendants.
+    pub fn is_kind_of(&self, id: &Uuid, ancestor: &Uuid) -> bool {
+        self.lineage(id)
+            .iter()
+            .any(|c| c.identification == *ancestor)
+    }
+
+    /// The type definition with AUID `id`, from this dictionary or else the
+    /// baseline.
+    pub fn resolve_type(&self, id: &Uuid) -> Option<&TypeDefinition> {
+        self.type_def(id).or_else(|| Self::baseline().type_def(id))
+    }
+
+    /// The class `id` followed by each of its ancestors, each from this
+    /// dictionary or else the baseline.
+    pub fn resolve_lineage(&self, id: &Uuid) -> Vec<&ClassDefinition> {
+        let baseline = Self::baseline();
+        let mut retval: Vec<&ClassDefinition> = vec![];
+        let mut next = self.class(id).or_else(|| baseline.class(id));
+        while let Some(class) = next {
+            if retval
+                .iter()
+                .any(|c| c.identification == class.identification)
+            {
+                break;
+            }
+            retval.push(class);
+            next = if class.is_root() {
+                None
+            } else {
+                self.class(&class.parent)
+                    .or_else(|| baseline.class(&class.parent))
+            };
+        }
+        retval
+    }
+
+    /// The definition of the property `pid` of class `id`, searching the
+    /// class's ancestors in this dictionary or else the baseline.
+    ///
+    /// Files often define only the classes they use, so an ancestor missing
+    /// from this dictionary is looked for in the baseline.
+    pub fn resolve_property(&self, id: &Uuid, pid: OMPropertyId) -> Option<&PropertyDefinition> {
+        let baseline = Self::baseline();
+        let mut next = *id;
+        for _ in 0..self.classes.len() + baseline.classes.len() {
+            let defined = [self.class(&next), baseline.class(&next)];
+            let found = defined
+                .iter()
+                .flatten()
+                .flat_map(|c| c.properties.iter())
+                .find(|p| p.local_identification == pid);
+            if found.is_some() {
+                return found;
+            }
+            let class = defined.iter().flatten().next()?;
+            if class.is_root() {
+                break;
+            }
+            next = class.parent;
+        }
+        None
+    }
+
+    /// The property that identifies the objects of class `id` in the sets
+    /// that hold them, searching the class's ancestors in this dictionary
+    /// and the baseline.
+    pub fn resolve_unique_identifier(&self, id: &Uuid) -> Option<&PropertyDefinition> {
+        let baseline = Self::baseline();
+        self.resolve_lineage(id)
+            .into_iter()
+            .flat_map(|c| {
+                let defined = [
+                    self.class(&c.identification),
+                    baseline.class(&c.identification),
+                ];
+                defined
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|c| c.properties.iter())
+            })
+            .find(|p| p.is_unique_identifier)
+    }
+
+    /// Every property of class `id`, including the properties it inherits.
+    pub fn all_properties(&self, id: &Uuid) -> Vec<&PropertyDefinition> {
+        self.lineage(id)
+            .into_iter()
+            .flat_map(|c| c.properties.iter())
+            .collect()
+    }
+
+    /// The definition of the property `pid` of class `id`, searching the
+    /// class's ancestors.
+    pub fn property(&self, id: &Uuid, pid: OMPropertyId) -> Option<&PropertyDefinition> {
+        self.lineage(id)
+            .into_iter()
+            .flat_map(|c| c.properties.iter())
+            .find(|p| p.local_identification == pid)
+    }
+}
+
+mod baseline {
+    use super::*;
+
+    fn class(
+        identification: Uuid,
+        name: &str,
+        parent: Uuid,
+        is_concrete: bool,
+        properties: Vec<PropertyDefinition>,
+    ) -> ClassDefinition {
+        ClassDefinition {
+            identification,
+            name: name.to_string(),
+            description: None,
+            parent,
+            is_concrete,
+            properties,
+        }
+    }
+
+    fn property(
+        identification: Uuid,
+        name: &str,
+        type_id: Uuid,
+        is_optional: bool,
+        local_identification: OMPropertyId,
+        is_unique_identifier: bool,
+    ) -> PropertyDefinition {
+        PropertyDefinition {
+            identification,
+            name: name.to_string(),
+            description: None,
+            type_id,
+            is_optional,
+            local_identification,
+            is_unique_identifier,
+        }
+    }
+
+    fn type_def(identification: Uuid, name: &str, kind: TypeDefinitionKind) -> TypeDefinition {
+        TypeDefinition {
+            identification,
+            name: name.to_string(),
+            description: None,
+            kind,
+        }
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/baseline.rs"));
+}
+
+fn required<F, T>(
+    file: &mut AAFFile<F>,
+    object: &InterchangeObjectDescriptor,
+    pid: OMPropertyId,
+) -> Result<T>
+where
+    F: Read + Seek,
+    T: AAFFrom,
+{
+    file.get_data(object, pid)?
+        .ok_or_else(|| AAFError::MissingProperty {
+            path: object.path.clone(),
+            pid,
+        })
+}
+
+/// The `Identification` of the definition a weak reference property refers
+/// to.
+fn referenced_id<F: Read + Seek>(
+    file: &mut AAFFile<F>,
+    object: &InterchangeObjectDescriptor,
+    pid: OMPropertyId,
+) -> Result<Uuid> {
+    match file.get_reference(object, pid)? {
+        Some(target) => required(file, &target, IDENTIFICATION_PID),
+        None => Err(AAFError::MissingProperty {
+            path: object.path.clone(),
+            pid,
+        }),
+    }
+}
+
+fn read_class_definition<F: Read + Seek>(
+    file: &mut AAFFile<F>,
+    object: &InterchangeObjectDescriptor,
+) -> Result<ClassDefinition> {
+    let mut properties = vec![];
+    for prop_obj in file.get_collection(object, PROPERTIES_PID)? {
+        properties.push(read_property_definition(file, &prop_obj)?);
+    }
+
+    Ok(ClassDefinition {
+        identification: required(file, object, IDENTIFICATION_PID)?,
+        name: required(file, object, NAME_PID)?,
+        description: file.get_data(object, DESCRIPTION_PID)?,
+        parent: referenced_id(file, object, PARENT_CLASS_PID)?,
+        is_concrete: required(file, object, IS_CONCRETE_PID)?,
+        properties,
+    })
+}
+
+fn read_property_definition<F: Read + Seek>(
+    file: &mut AAFFile<F>,
+    object: &InterchangeObjectDescriptor,
+) -> Result<PropertyDefinition> {
+    Ok(PropertyDefinition {
+        identification: required(file, object, IDENTIFICATION_PID)?,
+        name: required(file, object, NAME_PID)?,
+        description: file.get_data(object, DESCRIPTION_PID)?,
+        type_id: required(file, object, TYPE_PID)?,
+        is_optional: required(file, object, IS_OPTIONAL_PID)?,
+        local_identification: required(file, object, LOCAL_IDENTIFICATION_PID)?,
+        is_unique_identifier: file
+            .get_data(object, IS_UNIQUE_IDENTIFIER_PID)?
+            .unwrap_or(false),
+    })
+}
+
+fn read_type_definition<F: Read + Seek>(
+    file: &mut AAFFile<F>,
+    object: &InterchangeObjectDescriptor,
+) -> Result<TypeDefinition> {
+    let kind = match object.auid {
+        class_ids::TYPE_DEFINITION_INTEGER => TypeDefinitionKind::Integer {
+            size: required(file, object, INTEGER_SIZE_PID)?,
+            is_signed: required(file, object, INTEGER_IS_SIGNED_PID)?,
+        },
+        class_ids::TYPE_DEFINITION_STRONG_OBJECT_REFERENCE => {
+            TypeDefinitionKind::StrongObjectReference {
+                referenced_class: referenced_id(file, object, STRONG_REF_REFERENCED_TYPE_PID)?,
+            }
+        }
+        class_ids::TYPE_DEFINITION_WEAK_OBJECT_REFERENCE => {
+            TypeDefinitionKind::WeakObjectReference {
+                referenced_class: referenced_id(file, object, WEAK_REF_REFERENCED_TYPE_PID)?,
+                target_set: required(file, object, WEAK_REF_TARGET_SET_PID)?,
+            }
+        }
+        class_ids::TYPE_DEFINITION_ENUMERATION => {
+            let names: Vec<String> = required(file, object, ENUM_ELEMENT_NAMES_PID)?;
+            let values: Vec<AAFInt64> = required(file, object, ENUM_ELEMENT_VALUES_PID)?;
+            TypeDefinitionKind::Enumeration {
+                element_type: referenced_id(file, object, ENUM_ELEMENT_TYPE_PID)?,
+                elements: names.into_iter().zip(values).collect(),
+            }
+        }
+        class_ids::TYPE_DEFINITION_FIXED_ARRAY => TypeDefinitionKind::FixedArray {
+            element_type: referenced_id(file, object, FIXED_ARRAY_ELEMENT_TYPE_PID)?,
+            element_count: required(file, object, FIXED_ARRAY_ELEMENT_COUNT_PID)?,
+        },
+        class_ids::TYPE_DEFINITION_VARIABLE_ARRAY => TypeDefinitionKind::VariableArray {
+            element_type: referenced_id(file, object, VARIABLE_ARRAY_ELEMENT_TYPE_PID)?,
+        },
+        class_ids::TYPE_DEFINITION_SET => TypeDefinitionKind::Set {
+            element_type: referenced_id(file, object, SET_ELEMENT_TYPE_PID)?,
+        },
+        class_ids::TYPE_DEFINITION_STRING => TypeDefinitionKind::String {
+            element_type: referenced_id(file, object, STRING_ELEMENT_TYPE_PID)?,
+        },
+        class_ids::TYPE_DEFINITION_STREAM => TypeDefinitionKind::Stream,
+        class_ids::TYPE_DEFINITION_RECORD => {
+            let names: Vec<String> = required(file, object, RECORD_MEMBER_NAMES_PID)?;
+            let mut types = vec![];
+            for member_type in file.get_collection(object, RECORD_MEMBER_TYPES_PID)? {
+                types.push(required(file, &member_type, IDENTIFICATION_PID)?);
+            }
+            TypeDefinitionKind::Record {
+                members: names.into_iter().zip(types).collect(),
+            }
+        }
+        class_ids::TYPE_DEFINITION_RENAME => TypeDefinitionKind::Rename {
+            renamed_type: referenced_id(file, object, RENAMED_TYPE_PID)?,
+        },
+        class_ids::TYPE_DEFINITION_EXTENDIBLE_ENUMERATION => {
+            let names: Vec<String> = required(file, object, EXT_ENUM_ELEMENT_NAMES_PID)?;
+            let values: Vec<Uuid> = required(file, object, EXT_ENUM_ELEMENT_VALUES_PID)?;
+            TypeDefinitionKind::ExtendibleEnumeration {
+                elements: names.into_iter().zip(values).collect(),
+            }
+        }
+        class_ids::TYPE_DEFINITION_INDIRECT => TypeDefinitionKind::Indirect,
+        class_ids::TYPE_DEFINITION_OPAQUE => TypeDefinitionKind::Opaque,
+        class_ids::TYPE_DEFINITION_CHARACTER => TypeDefinitionKind::Character,
+        _ => {
+            return Err(AAFError::UnexpectedValue {
+                path: object.path.clone(),
+                pid: TYPE_DEFINITIONS_PID,
+                expected: "a TypeDefinition",
+            })
+        }
+    };
+
+    Ok(TypeDefinition {
+        identification: required(file, object, IDENTIFICATION_PID)?,
+        name: required(file, object, NAME_PID)?,
+        description: file.get_data(object, DESCRIPTION_PID)?,
+        kind,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_meta_dictionary() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let mut f = AAFFile::open(test_path).unwrap();
+        let md = f.meta_dictionary().unwrap();
+
+        let header = md.class_by_name("Header").unwrap();
+        assert!(header.is_concrete);
+        let byte_order = md.property(&header.identification, 0x3b01).unwrap();
+        assert_eq!(byte_order.name, "ByteOrder");
+        assert!(!byte_order.is_optional);
+
+        let lineage: Vec<&str> = md
+            .lineage(&header.identification)
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(lineage, vec!["Header", "InterchangeObject"]);
+
+        let boolean = md.type_by_name("Boolean").unwrap();
+        match &boolean.kind {
+            TypeDefinitionKind::Enumeration { elements, .. } => {
+                assert_eq!(
+                    elements,
+                    &vec![("False".to_string(), 0), ("True".to_string(), 1)]
+                );
+            }
+            k => panic!("Boolean decoded as {:?}", k),
+        }
+
+        let rational = md.type_by_name("Rational").unwrap();
+        match &rational.kind {
+            TypeDefinitionKind::Record { members } => {
+                let names: Vec<&str> = members.iter().map(|m| m.0.as_str()).collect();
+                assert_eq!(names, vec!["Numerator", "Denominator"]);
+            }
+            k => panic!("Rational decoded as {:?}", k),
+        }
+    }
+
+    #[test]
+    fn test_baseline() {
+        let baseline = MetaDictionary::baseline();
+        assert_eq!(baseline.classes().count(), 102);
+
+        let header = baseline.class(&class_ids::HEADER).unwrap();
+        assert_eq!(header.name, "Header");
+        let lineage: Vec<&str> = baseline
+            .lineage(&class_ids::SOURCE_MOB)
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(lineage, vec!["SourceMob", "Mob", "InterchangeObject"]);
+
+        // The classes a file defines agree with the baseline
+        let mut f = AAFFile::open("testmedia/AAF_Test_1/AAF_Test_1.aaf").unwrap();
+        let md = f.meta_dictionary().unwrap();
+        for class in md.classes() {
+            if let Some(b) = baseline.class(&class.identification) {
+                assert_eq!(class.parent, b.parent, "{}", class.name);
+                // Dynamic pids are allocated by the file
+                for p in class
+                    .properties
+                    .iter()
+                    .filter(|p| p.local_identification < 0x8000)
+                {
+                    let bp = baseline
+                        .property(&b.identification, p.local_identification)
+                        .unwrap();
+                    assert_eq!(p.identification, bp.identification, "{}", p.name);
+                }
+            }
+        }
+    }
+}
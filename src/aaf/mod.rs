@@ -1,2 +1,6 @@
+pub mod class_ids;
 pub mod classes;
-
+pub mod enums;
+pub mod meta_dictionary;
+pub mod type_ids;
+pub mod values;
@@ -0,0 +1,7 @@
+/// type_ids.rs
+///
+/// The AUIDs of the baseline AAF types, generated from
+/// AAFMetaDictionary.h.
+use uuid::Uuid;
+
+include!(concat!(env!("OUT_DIR"), "/type_ids.rs"));
@@ -0,0 +1,523 @@
+/// values.rs
+///
+/// Property values decoded according to their type definitions.
+use std::path::PathBuf;
+
+use uuid::Uuid;
+
+use crate::aaf::meta_dictionary::{MetaDictionary, TypeDefinition, TypeDefinitionKind};
+use crate::aaf::type_ids;
+use crate::error::{AAFError, Result};
+use crate::interchange_object::InterchangeObjectDescriptor;
+use crate::properties::PropertyValue;
+use crate::types::*;
+
+/// A property value decoded according to its `TypeDefinition`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AAFValue {
+    UInt8(AAFUInt8),
+    UInt16(AAFUInt16),
+    UInt32(AAFUInt32),
+    UInt64(AAFUInt64),
+    Int8(AAFInt8),
+    Int16(AAFInt16),
+    Int32(AAFInt32),
+    Int64(AAFInt64),
+    Rational(Rational),
+    AUID(Uuid),
+    MobID(MobID),
+    Character(char),
+    String(String),
+    /// An enumeration value and the name of its element, if it has one.
+    Enumeration {
+        name: Option<String>,
+        value: AAFInt64,
+    },
+    /// An extendible enumeration value and the name of its element, if the
+    /// dictionary names it.
+    ExtendibleEnumeration {
+        name: Option<String>,
+        value: Uuid,
+    },
+    /// A record's members, in order.
+    Record(Vec<(String, AAFValue)>),
+    /// A fixed or variable array.
+    Array(Vec<AAFValue>),
+    Set(Vec<AAFValue>),
+    /// A value stored with its own type.
+    Indirect {
+        type_id: Uuid,
+        value: Box<AAFValue>,
+    },
+    /// A value stored with its own type, which is not decoded.
+    Opaque {
+        type_id: Uuid,
+        bytes: Vec<u8>,
+    },
+    /// The path of a stream property's stream.
+    Stream(PathBuf),
+    /// The object a strong or weak reference refers to.
+    Object(InterchangeObjectDescriptor),
+    /// The objects a strong or weak reference vector or set refers to.
+    Objects(Vec<InterchangeObjectDescriptor>),
+    /// A property of a stored form this reader does not recognize.
+    Unknown {
+        stored_form: OMStoredForm,
+        bytes: Vec<u8>,
+    },
+}
+
+impl AAFValue {
+    /// The value of a property that is not stored as data.
+    ///
+    /// A weak reference stored as the unique id of the object it refers to
+    /// is that id.
+    pub(crate) fn from_property_value(value: PropertyValue) -> Option<Self> {
+        match value {
+            PropertyValue::Data(_) | PropertyValue::UniqueObjectId(_) => None,
+            PropertyValue::Stream(path) | PropertyValue::OpaqueStream(path) => {
+                Some(AAFValue::Stream(path))
+            }
+            PropertyValue::StoredObjectId(id) => match id.len() {
+                32 => id.aaf_into().ok().map(AAFValue::MobID),
+                _ => id.aaf_into().ok().map(AAFValue::AUID),
+            },
+            PropertyValue::Unknown { stored_form, bytes } => {
+                Some(AAFValue::Unknown { stored_form, bytes })
+            }
+            PropertyValue::Single(obj) | PropertyValue::Reference(obj) => {
+                Some(AAFValue::Object(obj))
+            }
+            PropertyValue::Vector(objs)
+            | PropertyValue::Set(objs)
+            | PropertyValue::ReferenceVector(objs)
+            | PropertyValue::ReferenceSet(objs) => Some(AAFValue::Objects(objs)),
+        }
+    }
+
+    /// Decode `bytes`, stored in `endian` byte order, as a `type_def`,
+    /// resolving the types it refers to in `dictionary`.
+    ///
+    /// A type that is defined in terms of itself, which only a corrupt
+    /// dictionary has, is an `InvalidValue`.
+    pub(crate) fn decode(
+        dictionary: &MetaDictionary,
+        type_def: &TypeDefinition,
+        bytes: &[u8],
+        endian: Endian,
+    ) -> Result<Self> {
+        Self::decode_within(dictionary, type_def, bytes, endian, &mut vec![])
+    }
+
+    /// Decode as `decode` does a value that is part of values of the types
+    /// `enclosing`, which it cannot itself be of.
+    fn decode_within(
+        dictionary: &MetaDictionary,
+        type_def: &TypeDefinition,
+        bytes: &[u8],
+        endian: Endian,
+        enclosing: &mut Vec<Uuid>,
+    ) -> Result<Self> {
+        if enclosing.contains(&type_def.identification) {
+            return invalid("a type defined in terms of itself", bytes);
+        }
+        enclosing.push(type_def.identification);
+        let retval = Self::decode_type(dictionary, type_def, bytes, endian, enclosing);
+        enclosing.pop();
+        retval
+    }
+
+    fn decode_type(
+        dictionary: &MetaDictionary,
+        type_def: &TypeDefinition,
+        bytes: &[u8],
+        endian: Endian,
+        enclosing: &mut Vec<Uuid>,
+    ) -> Result<Self> {
+        match type_def.identification {
+            type_ids::AUID => return bytes.aaf_into_endian(endian).map(AAFValue::AUID),
+            type_ids::MOB_ID_TYPE => return bytes.aaf_into_endian(endian).map(AAFValue::MobID),
+            type_ids::RATIONAL => return bytes.aaf_into_endian(endian).map(AAFValue::Rational),
+            _ => {}
+        }
+
+        match &type_def.kind {
+            TypeDefinitionKind::Integer { size, is_signed } => {
+                decode_integer(*size, *is_signed, bytes, endian)
+            }
+            TypeDefinitionKind::Enumeration {
+                element_type,
+                elements,
+            } => {
+                let value = match Self::decode_within(
+                    dictionary,
+                    lookup(dictionary, element_type)?,
+                    bytes,
+                    endian,
+                    enclosing,
+                )? {
+                    AAFValue::UInt8(v) => v as AAFInt64,
+                    AAFValue::UInt16(v) => v as AAFInt64,
+                    AAFValue::UInt32(v) => v as AAFInt64,
+                    AAFValue::UInt64(v) => v as AAFInt64,
+                    AAFValue::Int8(v) => v as AAFInt64,
+                    AAFValue::Int16(v) => v as AAFInt64,
+                    AAFValue::Int32(v) => v as AAFInt64,
+                    AAFValue::Int64(v) => v,
+                    _ => return invalid("Enumeration", bytes),
+                };
+                let name = elements
+                    .iter()
+                    .find(|(_, v)| *v == value)
+                    .map(|(n, _)| n.clone());
+                Ok(AAFValue::Enumeration { name, value })
+            }
+            TypeDefinitionKind::ExtendibleEnumeration { elements } => {
+                let value: Uuid = bytes.aaf_into_endian(endian)?;
+                let name = elements
+                    .iter()
+                    .find(|(_, v)| *v == value)
+                    .map(|(n, _)| n.clone());
+                Ok(AAFValue::ExtendibleEnumeration { name, value })
+            }
+            TypeDefinitionKind::Record { members } => {
+                let mut retval = vec![];
+                let mut rest = bytes;
+                for (name, member_type) in members {
+                    let member_type = lookup(dictionary, member_type)?;
+                    let size = match size_within(dictionary, member_type, enclosing) {
+                        Some(size) if size <= rest.len() => size,
+                        _ => return invalid("Record", bytes),
+                    };
+                    let value = Self::decode_within(
+                        dictionary,
+                        member_type,
+                        &rest[..size],
+                        endian,
+                        enclosing,
+                    )?;
+                    retval.push((name.clone(), value));
+                    rest = &rest[size..];
+                }
+                Ok(AAFValue::Record(retval))
+            }
+            TypeDefinitionKind::FixedArray { element_type, .. }
+            | TypeDefinitionKind::VariableArray { element_type } => {
+                let element_type = lookup(dictionary, element_type)?;
+                if let TypeDefinitionKind::String { .. } = element_type.kind {
+                    let strings: Vec<String> = bytes.aaf_into_endian(endian)?;
+                    return Ok(AAFValue::Array(
+                        strings.into_iter().map(AAFValue::String).collect(),
+                    ));
+                }
+                decode_elements(dictionary, element_type, bytes, endian, enclosing)
+                    .map(AAFValue::Array)
+            }
+            TypeDefinitionKind::Set { element_type } => {
+                let element_type = lookup(dictionary, element_type)?;
+                decode_elements(dictionary, element_type, bytes, endian, enclosing)
+                    .map(AAFValue::Set)
+            }
+            TypeDefinitionKind::String { element_type } => {
+                match lookup(dictionary, element_type)?.kind {
+                    TypeDefinitionKind::Character => {
+                        bytes.aaf_into_endian(endian).map(AAFValue::String)
+                    }
+                    _ => {
+                        let element_type = lookup(dictionary, element_type)?;
+                        decode_elements(dictionary, element_type, bytes, endian, enclosing)
+                            .map(AAFValue::Array)
+                    }
+                }
+            }
+            TypeDefinitionKind::Character => {
+                let unit: AAFUInt16 = bytes.aaf_into_endian(endian)?;
+                let c = char::from_u32(unit as u32).unwrap_or(char::REPLACEMENT_CHARACTER);
+                Ok(AAFValue::Character(c))
+            }
+            TypeDefinitionKind::Rename { renamed_type } => Self::decode_within(
+                dictionary,
+                lookup(dictionary, renamed_type)?,
+                bytes,
+                endian,
+                enclosing,
+            ),
+            TypeDefinitionKind::Indirect => {
+                // The value has its own type, and is shorter than this one,
+                // so it may be of any type.
+                let (type_id, value, endian) = split_indirect(bytes)?;
+                let value = Self::decode(dictionary, lookup(dictionary, &type_id)?, value, endian)?;
+                Ok(AAFValue::Indirect {
+                    type_id,
+                    value: Box::new(value),
+                })
+            }
+            TypeDefinitionKind::Opaque => {
+                let (type_id, value, _) = split_indirect(bytes)?;
+                Ok(AAFValue::Opaque {
+                    type_id,
+                    bytes: value.to_vec(),
+                })
+            }
+            TypeDefinitionKind::Stream
+            | TypeDefinitionKind::StrongObjectReference { .. }
+            | TypeDefinitionKind::WeakObjectReference { .. } => invalid("a data type", bytes),
+        }
+    }
+}
+
+/// Values serialize as the plain data they hold: integers as numbers,
+/// enumerations by name where they have one, records as maps of their
+/// members, and AUIDs and MobIDs as strings.
+#[cfg(feature = "serde")]
+impl serde::Serialize for AAFValue {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        match self {
+            AAFValue::UInt8(v) => serializer.serialize_u8(*v),
+            AAFValue::UInt16(v) => serializer.serialize_u16(*v),
+            AAFValue::UInt32(v) => serializer.serialize_u32(*v),
+            AAFValue::UInt64(v) => serializer.serialize_u64(*v),
+            AAFValue::Int8(v) => serializer.serialize_i8(*v),
+            AAFValue::Int16(v) => serializer.serialize_i16(*v),
+            AAFValue::Int32(v) => serializer.serialize_i32(*v),
+            AAFValue::Int64(v) => serializer.serialize_i64(*v),
+            AAFValue::Rational(v) => v.serialize(serializer),
+            AAFValue::AUID(v) => v.serialize(serializer),
+            AAFValue::MobID(v) => v.serialize(serializer),
+            AAFValue::Character(v) => serializer.serialize_char(*v),
+            AAFValue::String(v) => serializer.serialize_str(v),
+            AAFValue::Enumeration { name, value } => match name {
+                Some(name) => serializer.serialize_str(name),
+                None => serializer.serialize_i64(*value),
+            },
+            AAFValue::ExtendibleEnumeration { name, value } => match name {
+                Some(name) => serializer.serialize_str(name),
+                None => value.serialize(serializer),
+            },
+            AAFValue::Record(members) => {
+                let mut map = serializer.serialize_map(Some(members.len()))?;
+                for (name, member) in members {
+                    map.serialize_entry(name, member)?;
+                }
+                map.end()
+            }
+            AAFValue::Array(members) | AAFValue::Set(members) => members.serialize(serializer),
+            AAFValue::Indirect { type_id, value } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("type", type_id)?;
+                map.serialize_entry("value", value)?;
+                map.end()
+            }
+            AAFValue::Opaque { type_id, bytes } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("type", type_id)?;
+                map.serialize_entry("bytes", bytes)?;
+                map.end()
+            }
+            AAFValue::Stream(path) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("$stream", path)?;
+                map.end()
+            }
+            AAFValue::Object(object) => object.serialize(serializer),
+            AAFValue::Objects(objects) => objects.serialize(serializer),
+            AAFValue::Unknown { stored_form, bytes } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("stored_form", stored_form)?;
+                map.serialize_entry("bytes", bytes)?;
+                map.end()
+            }
+        }
+    }
+}
+
+fn invalid<T>(type_name: &'static str, bytes: &[u8]) -> Result<T> {
+    Err(AAFError::InvalidValue {
+        type_name,
+        len: bytes.len(),
+    })
+}
+
+/// The type `id` in `dictionary`, or an `UndefinedType` without the path and
+/// pid of the property, which the caller knows and fills in.
+fn lookup<'a>(dictionary: &'a MetaDictionary, id: &Uuid) -> Result<&'a TypeDefinition> {
+    dictionary
+        .resolve_type(id)
+        .ok_or_else(|| AAFError::UndefinedType {
+            path: PathBuf::new(),
+            pid: 0,
+            type_id: *id,
+        })
+}
+
+/// The stored size of a value of `type_def`, a part of values of the types
+/// `enclosing`, or `None` if values of the type vary in size or the type is
+/// defined in terms of itself.
+fn size_within(
+    dictionary: &MetaDictionary,
+    type_def: &TypeDefinition,
+    enclosing: &mut Vec<Uuid>,
+) -> Option<usize> {
+    if enclosing.contains(&type_def.identification) {
+        return None;
+    }
+    enclosing.push(type_def.identification);
+    let mut size_of = |id: &Uuid| size_within(dictionary, dictionary.resolve_type(id)?, enclosing);
+    let retval = match &type_def.kind {
+        TypeDefinitionKind::Integer { size, .. } => Some(*size as usize),
+        TypeDefinitionKind::Enumeration { element_type, .. } => size_of(element_type),
+        TypeDefinitionKind::ExtendibleEnumeration { .. } => Some(16),
+        TypeDefinitionKind::Record { members } => members
+            .iter()
+            .try_fold(0usize, |sum, (_, t)| sum.checked_add(size_of(t)?)),
+        TypeDefinitionKind::FixedArray {
+            element_type,
+            element_count,
+        } => size_of(element_type).and_then(|s| s.checked_mul(*element_count as usize)),
+        TypeDefinitionKind::Rename { renamed_type } => size_of(renamed_type),
+        TypeDefinitionKind::Character => Some(2),
+        _ => None,
+    };
+    enclosing.pop();
+    retval
+}
+
+fn decode_integer(size: u8, is_signed: bool, bytes: &[u8], endian: Endian) -> Result<AAFValue> {
+    if bytes.len() < size as usize {
+        return invalid("Integer", bytes);
+    }
+    let bytes = &bytes[..size as usize];
+    match (size, is_signed) {
+        (1, false) => Ok(AAFValue::UInt8(bytes[0])),
+        (2, false) => bytes.aaf_into_endian(endian).map(AAFValue::UInt16),
+        (4, false) => bytes.aaf_into_endian(endian).map(AAFValue::UInt32),
+        (8, false) => bytes.aaf_into_endian(endian).map(AAFValue::UInt64),
+        (1, true) => Ok(AAFValue::Int8(bytes[0] as AAFInt8)),
+        (2, true) => bytes.aaf_into_endian(endian).map(AAFValue::Int16),
+        (4, true) => bytes.aaf_into_endian(endian).map(AAFValue::Int32),
+        (8, true) => bytes.aaf_into_endian(endian).map(AAFValue::Int64),
+        _ => invalid("Integer", bytes),
+    }
+}
+
+fn decode_elements(
+    dictionary: &MetaDictionary,
+    element_type: &TypeDefinition,
+    bytes: &[u8],
+    endian: Endian,
+    enclosing: &mut Vec<Uuid>,
+) -> Result<Vec<AAFValue>> {
+    match size_within(dictionary, element_type, enclosing) {
+        Some(size) if size > 0 && bytes.len() % size == 0 => bytes
+            .chunks(size)
+            .map(|c| AAFValue::decode_within(dictionary, element_type, c, endian, enclosing))
+            .collect(),
+        _ => invalid("Array", bytes),
+    }
+}
+
+/// Split an indirect or opaque value into its type, its value and the byte
+/// order of both.
+///
+/// These begin with a byte order byte followed by the AUID of the value's
+/// type.
+fn split_indirect(bytes: &[u8]) -> Result<(Uuid, &[u8], Endian)> {
+    let endian = match bytes.first().copied().and_then(Endian::from_byte_order) {
+        Some(endian) if bytes.len() >= 17 => endian,
+        _ => return invalid("Indirect", bytes),
+    };
+    Ok((bytes[1..17].aaf_into_endian(endian)?, &bytes[17..], endian))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_def(id: u128, kind: TypeDefinitionKind) -> TypeDefinition {
+        TypeDefinition {
+            identification: Uuid::from_u128(id),
+            name: format!("Type{}", id),
+            description: None,
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_decode_self_referential_types() {
+        let mut md = MetaDictionary::default();
+        // Two renames of each other, and a record that contains itself.
+        let rename = type_def(
+            1,
+            TypeDefinitionKind::Rename {
+                renamed_type: Uuid::from_u128(2),
+            },
+        );
+        md.insert_type(rename.clone());
+        md.insert_type(type_def(
+            2,
+            TypeDefinitionKind::Rename {
+                renamed_type: Uuid::from_u128(1),
+            },
+        ));
+        let record = type_def(
+            3,
+            TypeDefinitionKind::Record {
+                members: vec![("Inner".to_string(), Uuid::from_u128(3))],
+            },
+        );
+        md.insert_type(record.clone());
+        let array = type_def(
+            4,
+            TypeDefinitionKind::VariableArray {
+                element_type: Uuid::from_u128(3),
+            },
+        );
+        md.insert_type(array.clone());
+
+        for t in [&rename, &record, &array] {
+            assert!(matches!(
+                AAFValue::decode(&md, t, &[0; 8], Endian::Little),
+                Err(AAFError::InvalidValue { .. })
+            ));
+        }
+
+        // A type may appear more than once in a value, if not within itself.
+        let pair = type_def(
+            5,
+            TypeDefinitionKind::Record {
+                members: vec![
+                    ("A".to_string(), type_ids::UINT16),
+                    ("B".to_string(), type_ids::UINT16),
+                ],
+            },
+        );
+        md.insert_type(pair.clone());
+        assert_eq!(
+            AAFValue::decode(&md, &pair, &[1, 0, 2, 0], Endian::Little).unwrap(),
+            AAFValue::Record(vec![
+                ("A".to_string(), AAFValue::UInt16(1)),
+                ("B".to_string(), AAFValue::UInt16(2)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_decode_undefined_type() {
+        let md = MetaDictionary::default();
+        let array = type_def(
+            1,
+            TypeDefinitionKind::VariableArray {
+                element_type: Uuid::from_u128(2),
+            },
+        );
+        assert!(matches!(
+            AAFValue::decode(&md, &array, &[0; 8], Endian::Little),
+            Err(AAFError::UndefinedType { type_id, .. }) if type_id == Uuid::from_u128(2)
+        ));
+    }
+}
@@ -0,0 +1,371 @@
+/// descriptors.rs
+///
+/// The geometry of digital images and the WAVE and AIFC headers that audio
+/// descriptors keep as their `Summary`.
+use std::io::{Read, Seek};
+
+use crate::aaf::classes::*;
+use crate::aaf::enums::RGBAComponentKind;
+use crate::error::{AAFError, Result};
+use crate::essence::{AudioFormat, BroadcastExtension};
+use crate::types::*;
+
+const AIFC_DESCRIPTOR_SUMMARY_PID: OMPropertyId = 0x3101;
+const WAVE_DESCRIPTOR_SUMMARY_PID: OMPropertyId = 0x3801;
+
+/// An area of an image, in pixels, and its offset from the area it is
+/// part of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageGeometry {
+    pub width: AAFUInt32,
+    pub height: AAFUInt32,
+    pub x_offset: AAFInt32,
+    pub y_offset: AAFInt32,
+}
+
+impl<F: Read + Seek> DigitalImageDescriptor<F> {
+    /// The area of the image that is stored.
+    pub fn stored_geometry(&self) -> Result<ImageGeometry> {
+        Ok(ImageGeometry {
+            width: self.stored_width()?,
+            height: self.stored_height()?,
+            x_offset: 0,
+            y_offset: 0,
+        })
+    }
+
+    /// The area of the stored image that was sampled from the source,
+    /// which is all of it unless the descriptor says otherwise.
+    pub fn sampled_geometry(&self) -> Result<ImageGeometry> {
+        let stored = self.stored_geometry()?;
+        Ok(ImageGeometry {
+            width: self.sampled_width()?.unwrap_or(stored.width),
+            height: self.sampled_height()?.unwrap_or(stored.height),
+            x_offset: self.sampled_x_offset()?.unwrap_or(0),
+            y_offset: self.sampled_y_offset()?.unwrap_or(0),
+        })
+    }
+
+    /// The area of the sampled image that is meant to be displayed, which
+    /// is all of it unless the descriptor says otherwise.
+    pub fn display_geometry(&self) -> Result<ImageGeometry> {
+        let sampled = self.sampled_geometry()?;
+        Ok(ImageGeometry {
+            width: self.display_width()?.unwrap_or(sampled.width),
+            height: self.display_height()?.unwrap_or(sampled.height),
+            x_offset: self.display_x_offset()?.unwrap_or(0),
+            y_offset: self.display_y_offset()?.unwrap_or(0),
+        })
+    }
+
+    /// The number of bits each colour component of a pixel is stored in:
+    /// the `ComponentWidth` of a CDCI or MPEG descriptor, or the size of the
+    /// widest colour component of an RGBA descriptor's pixel layout.
+    pub fn component_depth(&self) -> Result<Option<AAFUInt32>> {
+        Ok(match self {
+            Self::CDCIDescriptor(d) => Some(d.component_width()?),
+            Self::MPEGVideoDescriptor(d) => Some(d.component_width()?),
+            Self::RGBADescriptor(d) => d
+                .pixel_layout()?
+                .iter()
+                .filter(|c| {
+                    matches!(
+                        c.code,
+                        RGBAComponentKind::CompRed
+                            | RGBAComponentKind::CompGreen
+                            | RGBAComponentKind::CompBlue
+                    )
+                })
+                .map(|c| c.size as AAFUInt32)
+                .max(),
+            Self::Unknown(_) => None,
+        })
+    }
+}
+
+/// The header of a WAVE file, as a `WAVEDescriptor` keeps it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaveSummary {
+    /// `1` for PCM.
+    pub format_tag: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub average_bytes_per_second: u32,
+    pub block_align: u16,
+    pub bits_per_sample: u16,
+    pub broadcast_extension: Option<BroadcastExtension>,
+    /// The length of the samples, from the header of the `data` chunk.
+    pub data_len: Option<u32>,
+}
+
+impl WaveSummary {
+    /// Parse a RIFF WAVE header, which must have a `fmt ` chunk.
+    pub fn parse(summary: &[u8]) -> Option<Self> {
+        if summary.get(0..4) != Some(b"RIFF") || summary.get(8..12) != Some(b"WAVE") {
+            return None;
+        }
+        let chunks = chunks(summary, false);
+        let fmt = chunks.iter().find(|(id, _)| id == b"fmt ")?.1;
+        if fmt.len() < 16 {
+            return None;
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes(fmt[i..i + 4].try_into().unwrap());
+        Some(Self {
+            format_tag: u16_at(0),
+            channels: u16_at(2),
+            sample_rate: u32_at(4),
+            average_bytes_per_second: u32_at(8),
+            block_align: u16_at(12),
+            bits_per_sample: u16_at(14),
+            broadcast_extension: chunks
+                .iter()
+                .find(|(id, _)| id == b"bext")
+                .and_then(|(_, data)| BroadcastExtension::parse(data)),
+            data_len: data_chunk_len(summary, b"data", false),
+        })
+    }
+
+    /// The layout of the samples, if they are PCM.
+    pub fn audio_format(&self) -> Option<AudioFormat> {
+        // WAVE_FORMAT_PCM, or WAVE_FORMAT_EXTENSIBLE, which is assumed to be
+        // PCM.
+        if !matches!(self.format_tag, 0x0001 | 0xfffe) {
+            return None;
+        }
+        Some(AudioFormat {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: self.bits_per_sample,
+        })
+    }
+}
+
+/// The header of an AIFF or AIFF-C file, as an `AIFCDescriptor` keeps it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AifcSummary {
+    pub channels: u16,
+    pub frames: u32,
+    pub bits_per_sample: u16,
+    pub sample_rate: u32,
+    /// The compression type of an AIFF-C file, such as `NONE` or `sowt`, or
+    /// `None` for an AIFF file.
+    pub compression: Option<[u8; 4]>,
+}
+
+impl AifcSummary {
+    /// Parse an AIFF or AIFF-C header, which must have a `COMM` chunk.
+    pub fn parse(summary: &[u8]) -> Option<Self> {
+        let is_aifc = match (summary.get(0..4), summary.get(8..12)) {
+            (Some(b"FORM"), Some(b"AIFC")) => true,
+            (Some(b"FORM"), Some(b"AIFF")) => false,
+            _ => return None,
+        };
+        let comm = chunks(summary, true)
+            .into_iter()
+            .find(|(id, _)| id == b"COMM")?
+            .1;
+        if comm.len() < 18 {
+            return None;
+        }
+        Some(Self {
+            channels: u16::from_be_bytes([comm[0], comm[1]]),
+            frames: u32::from_be_bytes(comm[2..6].try_into().unwrap()),
+            bits_per_sample: u16::from_be_bytes([comm[6], comm[7]]),
+            sample_rate: from_extended(comm[8..18].try_into().unwrap()),
+            compression: match is_aifc {
+                true => comm.get(18..22).map(|c| c.try_into().unwrap()),
+                false => None,
+            },
+        })
+    }
+
+    /// The layout of the samples, and whether they are big-endian, if they
+    /// are uncompressed.
+    pub fn audio_format(&self) -> Option<(AudioFormat, bool)> {
+        let big_endian = match self.compression.as_ref() {
+            None | Some(b"NONE") | Some(b"twos") => true,
+            Some(b"sowt") => false,
+            Some(_) => return None,
+        };
+        let format = AudioFormat {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: self.bits_per_sample,
+        };
+        Some((format, big_endian))
+    }
+}
+
+impl<F: Read + Seek> WAVEDescriptor<F> {
+    /// `Summary`, parsed.
+    pub fn wave_summary(&self) -> Result<WaveSummary> {
+        WaveSummary::parse(&self.summary()?).ok_or_else(|| AAFError::InvalidProperty {
+            path: self.descriptor().path.clone(),
+            pid: WAVE_DESCRIPTOR_SUMMARY_PID,
+            type_name: "WAVE summary",
+        })
+    }
+}
+
+impl<F: Read + Seek> AIFCDescriptor<F> {
+    /// `Summary`, parsed.
+    pub fn aifc_summary(&self) -> Result<AifcSummary> {
+        AifcSummary::parse(&self.summary()?).ok_or_else(|| AAFError::InvalidProperty {
+            path: self.descriptor().path.clone(),
+            pid: AIFC_DESCRIPTOR_SUMMARY_PID,
+            type_name: "AIFC summary",
+        })
+    }
+}
+
+/// The chunks of a RIFF or IFF form, by their ids.
+///
+/// Chunks that run past the end of `form` are cut short rather than left
+/// out, as the `data` chunk of an essence descriptor's summary is.
+pub(crate) fn chunks(form: &[u8], big_endian: bool) -> Vec<([u8; 4], &[u8])> {
+    let mut chunks = vec![];
+    let mut offset = 12;
+    while offset + 8 <= form.len() {
+        let id = form[offset..offset + 4].try_into().unwrap();
+        let len = chunk_len(form[offset + 4..offset + 8].try_into().unwrap(), big_endian);
+        let start = offset + 8;
+        let end = start.saturating_add(len as usize).min(form.len());
+        chunks.push((id, &form[start..end]));
+        offset = start.saturating_add(len as usize + len as usize % 2);
+    }
+    chunks
+}
+
+pub(crate) fn chunk_len(bytes: [u8; 4], big_endian: bool) -> u32 {
+    match big_endian {
+        true => u32::from_be_bytes(bytes),
+        false => u32::from_le_bytes(bytes),
+    }
+}
+
+/// The declared length of the first chunk with id `id`, which may be longer
+/// than what follows it in `form`.
+fn data_chunk_len(form: &[u8], id: &[u8; 4], big_endian: bool) -> Option<u32> {
+    let mut offset = 12;
+    while offset + 8 <= form.len() {
+        let len = chunk_len(form[offset + 4..offset + 8].try_into().unwrap(), big_endian);
+        if &form[offset..offset + 4] == id {
+            return Some(len);
+        }
+        offset = (offset + 8).saturating_add(len as usize + len as usize % 2);
+    }
+    None
+}
+
+/// An 80-bit IEEE 754 extended precision number, as AIFF stores sample
+/// rates, rounded down to an integer.
+pub(crate) fn from_extended(bytes: [u8; 10]) -> u32 {
+    let exponent = u16::from_be_bytes([bytes[0], bytes[1]]) & 0x7fff;
+    let mantissa = u64::from_be_bytes(bytes[2..].try_into().unwrap());
+    match exponent.checked_sub(16383) {
+        Some(shift) if shift < 32 => (mantissa >> (63 - shift)) as u32,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aaf::enums::TapeFormatType;
+    use crate::file::AAFFile;
+
+    #[test]
+    fn test_wave_summary() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let content = AAFFile::open(test_path)
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+
+        let mut summaries = 0;
+        for mob in content.source_mobs().unwrap() {
+            let descriptor = match mob.essence_description().unwrap() {
+                EssenceDescriptor::WAVEDescriptor(d) => d,
+                _ => continue,
+            };
+            let summary = descriptor.wave_summary().unwrap();
+            assert_eq!(summary.format_tag, 1);
+            assert_eq!(summary.block_align, 3);
+            assert_eq!(summary.average_bytes_per_second, 144_000);
+            assert_eq!(summary.data_len, Some(6003));
+            assert_eq!(summary.broadcast_extension, None);
+            assert_eq!(
+                summary.audio_format(),
+                Some(AudioFormat {
+                    channels: 1,
+                    sample_rate: 48000,
+                    bits_per_sample: 24,
+                })
+            );
+            let rate = descriptor.sample_rate().unwrap();
+            assert_eq!(
+                rate.numerator as u32,
+                summary.sample_rate * rate.denominator as u32
+            );
+            summaries += 1;
+        }
+        assert_eq!(summaries, 1);
+    }
+
+    #[test]
+    fn test_enumerations() {
+        assert_eq!(TapeFormatType::from(5), TapeFormatType::EightMMFormat);
+        assert_eq!(TapeFormatType::EightMMFormat.value(), 5);
+        assert_eq!(TapeFormatType::from(42), TapeFormatType::Undefined(42));
+        assert_eq!(TapeFormatType::Undefined(42).value(), 42);
+        assert_eq!(
+            Vec::<RGBAComponent>::aaf_from(&[0x52, 8, 0x47, 8, 0x42, 8, 0, 0]).unwrap(),
+            vec![
+                RGBAComponent {
+                    code: RGBAComponentKind::CompRed,
+                    size: 8
+                },
+                RGBAComponent {
+                    code: RGBAComponentKind::CompGreen,
+                    size: 8
+                },
+                RGBAComponent {
+                    code: RGBAComponentKind::CompBlue,
+                    size: 8
+                },
+                RGBAComponent {
+                    code: RGBAComponentKind::CompNull,
+                    size: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aifc_summary() {
+        let mut summary =
+            b"FORM\0\0\0\x30AIFCFVER\0\0\0\x04\xa2\x80\x51\x40COMM\0\0\0\x18".to_vec();
+        summary.extend_from_slice(&[0, 2, 0, 0, 0x10, 0, 0, 16]);
+        summary.extend_from_slice(&[0x40, 0x0e, 0xbb, 0x80, 0, 0, 0, 0, 0, 0]);
+        summary.extend_from_slice(b"sowt\0\0");
+        let parsed = AifcSummary::parse(&summary).unwrap();
+        assert_eq!(
+            parsed,
+            AifcSummary {
+                channels: 2,
+                frames: 4096,
+                bits_per_sample: 16,
+                sample_rate: 48000,
+                compression: Some(*b"sowt"),
+            }
+        );
+        assert_eq!(
+            parsed.audio_format().map(|(_, big_endian)| big_endian),
+            Some(false)
+        );
+        assert!(AifcSummary::parse(b"RIFF\0\0\0\0WAVE").is_none());
+    }
+}
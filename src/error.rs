@@ -0,0 +1,216 @@
+/// error.rs
+///
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use crate::types::{OMByteOrder, OMPropertyId, OMPropertyTag};
+
+/// An error encountered while reading an AAF file.
+///
+/// Errors that arise from a particular stream or object carry the storage
+/// `path` they were found at and, where it applies, the `pid` of the
+/// property being read.
+#[derive(Debug)]
+pub enum AAFError {
+    /// An I/O error from the underlying compound file.
+    Io { path: PathBuf, source: io::Error },
+
+    /// A stream ended before all of its declared contents could be read.
+    TruncatedStream { path: PathBuf },
+
+    /// A stream began with a byte order mark that is not supported.
+    BadByteOrder {
+        path: PathBuf,
+        byte_order: OMByteOrder,
+    },
+
+    /// No object exists at the given storage path.
+    MissingObject { path: PathBuf },
+
+    /// A collection property's index stream could not be found.
+    MissingIndexStream { path: PathBuf, pid: OMPropertyId },
+
+    /// A required property was not present on an object.
+    MissingProperty { path: PathBuf, pid: OMPropertyId },
+
+    /// A property's value was not of the kind the caller expected, for
+    /// instance a data value where an object reference was required.
+    UnexpectedValue {
+        path: PathBuf,
+        pid: OMPropertyId,
+        expected: &'static str,
+    },
+
+    /// A weak reference's tag does not appear in the referenced properties
+    /// table.
+    BadWeakReferenceTag {
+        path: PathBuf,
+        pid: OMPropertyId,
+        tag: OMPropertyTag,
+    },
+
+    /// A weak reference's key did not match any object in its target set.
+    DanglingWeakReference { path: PathBuf, pid: OMPropertyId },
+
+    /// An index or table stream is internally inconsistent.
+    InconsistentIndex { path: PathBuf, reason: &'static str },
+
+    /// Neither the file's MetaDictionary nor the baseline defines a property
+    /// of the object's class with this pid.
+    UndefinedProperty { path: PathBuf, pid: OMPropertyId },
+
+    /// Neither the file's MetaDictionary nor the baseline defines the class
+    /// of an object.
+    UndefinedClass { path: PathBuf, class_id: Uuid },
+
+    /// Neither the file's MetaDictionary nor the baseline defines the type
+    /// of a property.
+    UndefinedType {
+        path: PathBuf,
+        pid: OMPropertyId,
+        type_id: Uuid,
+    },
+
+    /// A data value was too short or malformed for the type it was decoded as.
+    InvalidValue { type_name: &'static str, len: usize },
+
+    /// A property's data could not be decoded as the type the caller asked for.
+    InvalidProperty {
+        path: PathBuf,
+        pid: OMPropertyId,
+        type_name: &'static str,
+    },
+
+    /// Text could not be parsed as a MobID.
+    InvalidMobID { text: String },
+
+    /// Embedded essence is not in a form that can be exported.
+    UnsupportedEssence { path: PathBuf, reason: &'static str },
+
+    /// A file being opened is not an AAF file.
+    NotAAFFile { reason: &'static str },
+
+    /// An AAF-XML document is malformed or does not match the object model,
+    /// at the given line.
+    InvalidXml { line: u32, reason: String },
+}
+
+/// A `Result` with an `AAFError`.
+pub type Result<T> = std::result::Result<T, AAFError>;
+
+impl AAFError {
+    /// Wrap an `io::Error` raised while reading the stream at `path`.
+    ///
+    /// Reads that run off the end of a stream are reported as
+    /// `TruncatedStream`.
+    pub fn from_io(path: &Path, source: io::Error) -> Self {
+        if source.kind() == io::ErrorKind::UnexpectedEof {
+            AAFError::TruncatedStream { path: path.into() }
+        } else {
+            AAFError::Io {
+                path: path.into(),
+                source,
+            }
+        }
+    }
+}
+
+impl fmt::Display for AAFError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "I/O error at {:?}: {}", path, source),
+            Self::TruncatedStream { path } => write!(f, "Stream {:?} is truncated", path),
+            Self::BadByteOrder { path, byte_order } => write!(
+                f,
+                "Stream {:?} has unsupported byte order 0x{:02x}",
+                path, byte_order
+            ),
+            Self::MissingObject { path } => write!(f, "No object at {:?}", path),
+            Self::MissingIndexStream { path, pid } => write!(
+                f,
+                "Index stream {:?} for property 0x{:04x} not found",
+                path, pid
+            ),
+            Self::MissingProperty { path, pid } => {
+                write!(f, "Required property 0x{:04x} not found on {:?}", pid, path)
+            }
+            Self::UnexpectedValue {
+                path,
+                pid,
+                expected,
+            } => write!(
+                f,
+                "Property 0x{:04x} of {:?} is not {}",
+                pid, path, expected
+            ),
+            Self::BadWeakReferenceTag { path, pid, tag } => write!(
+                f,
+                "Weak reference 0x{:04x} of {:?} has unknown tag {}",
+                pid, path, tag
+            ),
+            Self::DanglingWeakReference { path, pid } => write!(
+                f,
+                "Weak reference 0x{:04x} of {:?} does not resolve to an object",
+                pid, path
+            ),
+            Self::InconsistentIndex { path, reason } => {
+                write!(f, "Index {:?} is inconsistent: {}", path, reason)
+            }
+            Self::UndefinedProperty { path, pid } => {
+                write!(f, "Property 0x{:04x} of {:?} has no definition", pid, path)
+            }
+            Self::UndefinedClass { path, class_id } => {
+                write!(f, "Object {:?} has undefined class {}", path, class_id)
+            }
+            Self::UndefinedType { path, pid, type_id } => write!(
+                f,
+                "Property 0x{:04x} of {:?} has undefined type {}",
+                pid, path, type_id
+            ),
+            Self::InvalidValue { type_name, len } => {
+                write!(f, "{} bytes cannot be decoded as {}", len, type_name)
+            }
+            Self::InvalidProperty {
+                path,
+                pid,
+                type_name,
+            } => write!(
+                f,
+                "Property 0x{:04x} of {:?} cannot be decoded as {}",
+                pid, path, type_name
+            ),
+            Self::InvalidMobID { text } => write!(f, "{:?} is not a MobID", text),
+            Self::UnsupportedEssence { path, reason } => {
+                write!(f, "Essence of {:?} cannot be exported: {}", path, reason)
+            }
+            Self::NotAAFFile { reason } => write!(f, "Not an AAF file: {}", reason),
+            Self::InvalidXml { line, reason } => {
+                write!(f, "Invalid AAF-XML at line {}: {}", line, reason)
+            }
+        }
+    }
+}
+
+impl Error for AAFError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Attach a storage path to an `io::Result`.
+pub(crate) trait IoResultExt<T> {
+    fn at_path(self, path: &Path) -> Result<T>;
+}
+
+impl<T> IoResultExt<T> for io::Result<T> {
+    fn at_path(self, path: &Path) -> Result<T> {
+        self.map_err(|e| AAFError::from_io(path, e))
+    }
+}
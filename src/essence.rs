@@ -0,0 +1,736 @@
+/// essence.rs
+///
+/// Audio essence embedded in `EssenceData` objects, written out as
+/// standalone WAV and AIFF files.
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::aaf::classes::*;
+use crate::descriptors::chunk_len;
+use crate::error::{AAFError, IoResultExt, Result};
+use crate::source_chain::{SourceResolver, TapeTimecode};
+use crate::stream::{PropertyStream, StreamByteOrder};
+use crate::types::*;
+
+const BEXT_LEN: usize = 602;
+const COPY_LEN: usize = 1 << 20;
+
+/// A file format embedded audio can be written as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFileFormat {
+    Wav,
+    Aiff,
+}
+
+impl AudioFileFormat {
+    /// The usual file name extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Aiff => "aif",
+        }
+    }
+}
+
+/// The layout of uncompressed PCM samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+}
+
+impl AudioFormat {
+    /// The number of bytes each sample is stored in.
+    pub fn bytes_per_sample(&self) -> usize {
+        (self.bits_per_sample as usize + 7) / 8
+    }
+
+    /// The number of bytes each frame of samples, one for each channel, is
+    /// stored in.
+    pub fn block_align(&self) -> usize {
+        self.bytes_per_sample() * self.channels as usize
+    }
+
+    /// Whether a frame fits in the 16-bit block align of a WAV file, and
+    /// so in the buffer samples are copied through.
+    fn in_range(&self) -> bool {
+        u16::try_from(self.block_align()).is_ok()
+    }
+}
+
+/// The broadcast extension (`bext`) chunk of a Broadcast Wave file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BroadcastExtension {
+    pub description: String,
+    pub originator: String,
+    pub originator_reference: String,
+    /// `yyyy-mm-dd`
+    pub origination_date: String,
+    /// `hh:mm:ss`
+    pub origination_time: String,
+    /// The position of the first sample since midnight, in samples.
+    pub time_reference: u64,
+    pub umid: Option<MobID>,
+    pub coding_history: String,
+}
+
+impl BroadcastExtension {
+    /// Parse the data of a `bext` chunk.
+    pub(crate) fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < BEXT_LEN {
+            return None;
+        }
+        let text = |start: usize, len: usize| {
+            let field = &data[start..start + len];
+            let end = field.iter().position(|b| *b == 0).unwrap_or(len);
+            String::from_utf8_lossy(&field[..end]).into_owned()
+        };
+        let umid = &data[348..380];
+        Some(Self {
+            description: text(0, 256),
+            originator: text(256, 32),
+            originator_reference: text(288, 32),
+            origination_date: text(320, 10),
+            origination_time: text(330, 8),
+            time_reference: u64::from_le_bytes(data[338..346].try_into().unwrap()),
+            umid: match umid.iter().any(|b| *b != 0) {
+                true => Some(MobID::from_umid_bytes(umid.try_into().unwrap())),
+                false => None,
+            },
+            coding_history: text(BEXT_LEN, data.len() - BEXT_LEN),
+        })
+    }
+
+    /// The data of a version 1 `bext` chunk.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0; BEXT_LEN];
+        let mut text = |start: usize, len: usize, value: &str| {
+            let bytes = &value.as_bytes()[..value.len().min(len)];
+            data[start..start + bytes.len()].copy_from_slice(bytes);
+        };
+        text(0, 256, &self.description);
+        text(256, 32, &self.originator);
+        text(288, 32, &self.originator_reference);
+        text(320, 10, &self.origination_date);
+        text(330, 8, &self.origination_time);
+        data[338..346].copy_from_slice(&self.time_reference.to_le_bytes());
+        data[346..348].copy_from_slice(&1u16.to_le_bytes());
+        if let Some(umid) = &self.umid {
+            data[348..380].copy_from_slice(&umid.umid_bytes());
+        }
+        data.extend_from_slice(self.coding_history.as_bytes());
+        data
+    }
+}
+
+/// The audio essence of an `EssenceData` object.
+pub struct EmbeddedAudio<F> {
+    /// The `SourceMob` whose essence it is.
+    pub source_mob: SourceMob<F>,
+    pub format: AudioFormat,
+    /// The broadcast extension from a `WAVEDescriptor`'s summary, or one
+    /// made from the `SourceMob`'s name, creation time and tape timecode.
+    pub broadcast_extension: Option<BroadcastExtension>,
+    data: PropertyStream<F>,
+    samples_start: u64,
+    samples_len: u64,
+    big_endian: bool,
+}
+
+/// The audio essence of every `EssenceData` object in `content`.
+pub fn embedded_audio<F: Read + Seek>(
+    content: &ContentStorage<F>,
+) -> Result<Vec<EmbeddedAudio<F>>> {
+    let resolver = SourceResolver::new(content)?;
+    content
+        .essence_data()?
+        .iter()
+        .map(|essence| EmbeddedAudio::open(&resolver, essence))
+        .collect()
+}
+
+impl<F: Read + Seek> EmbeddedAudio<F> {
+    /// Open the essence of `essence`, described by the `SourceMob` with
+    /// its MobID.
+    pub fn open(resolver: &SourceResolver<F>, essence: &EssenceData<F>) -> Result<Self> {
+        let path = essence.descriptor().path.clone();
+        let unsupported = |reason| AAFError::UnsupportedEssence {
+            path: path.clone(),
+            reason,
+        };
+        let source_mob = match resolver.mob(&essence.mob_id()?) {
+            Some(Mob::SourceMob(mob)) => mob.clone(),
+            _ => return Err(unsupported("no SourceMob describes it")),
+        };
+
+        let mut data = essence.data()?;
+        // PCM samples are little-endian unless the stream says otherwise.
+        let pcm_big_endian = data.byte_order() == StreamByteOrder::Big;
+        let (format, big_endian, mut broadcast_extension) =
+            match source_mob.essence_description()? {
+                EssenceDescriptor::WAVEDescriptor(d) => {
+                    let summary = d.wave_summary()?;
+                    let format = summary
+                        .audio_format()
+                        .ok_or_else(|| unsupported("its WAVE summary is not PCM"))?;
+                    (format, false, summary.broadcast_extension)
+                }
+                EssenceDescriptor::AIFCDescriptor(d) => {
+                    let (format, big_endian) = d
+                        .aifc_summary()?
+                        .audio_format()
+                        .ok_or_else(|| unsupported("its AIFC summary is compressed"))?;
+                    (format, big_endian, None)
+                }
+                EssenceDescriptor::PCMDescriptor(d) => (
+                    pcm_format(
+                        d.channels()?,
+                        d.quantization_bits()?,
+                        d.audio_sampling_rate()?,
+                    )
+                    .ok_or_else(|| unsupported("its format is out of range"))?,
+                    pcm_big_endian,
+                    None,
+                ),
+                EssenceDescriptor::AES3PCMDescriptor(d) => (
+                    pcm_format(
+                        d.channels()?,
+                        d.quantization_bits()?,
+                        d.audio_sampling_rate()?,
+                    )
+                    .ok_or_else(|| unsupported("its format is out of range"))?,
+                    pcm_big_endian,
+                    None,
+                ),
+                _ => return Err(unsupported("it is not PCM audio")),
+            };
+        if format.block_align() == 0 || format.sample_rate == 0 {
+            return Err(unsupported("its format is incomplete"));
+        }
+        if !format.in_range() {
+            return Err(unsupported("its format is out of range"));
+        }
+        if broadcast_extension.is_none() {
+            // The broadcast extension is only metadata, so the samples can
+            // still be extracted without it.
+            broadcast_extension = make_broadcast_extension(resolver, &source_mob, format)
+                .ok()
+                .flatten();
+        }
+
+        let (samples_start, samples_len) = find_samples(&mut data).at_path(&path)?;
+        Ok(Self {
+            source_mob,
+            format,
+            broadcast_extension,
+            data,
+            samples_start,
+            samples_len,
+            big_endian,
+        })
+    }
+
+    /// The format the essence was stored for: AIFF for big-endian
+    /// samples, WAV otherwise.
+    pub fn file_format(&self) -> AudioFileFormat {
+        match self.big_endian {
+            true => AudioFileFormat::Aiff,
+            false => AudioFileFormat::Wav,
+        }
+    }
+
+    /// The number of frames of samples.
+    pub fn frames(&self) -> u64 {
+        self.samples_len / self.format.block_align() as u64
+    }
+
+    /// Write the essence to `out` as a file in `file_format`.
+    ///
+    /// A WAV file includes the broadcast extension, if there is one.
+    /// Errors writing to `out` are reported against the path of the
+    /// essence's stream.
+    pub fn write<W: Write>(&mut self, file_format: AudioFileFormat, out: &mut W) -> Result<()> {
+        let path = self.data.path().to_path_buf();
+        if self.samples_len > u32::MAX as u64 - 1024 {
+            return Err(AAFError::UnsupportedEssence {
+                path,
+                reason: "it is too long for a WAV or AIFF file",
+            });
+        }
+        if !self.format.in_range() {
+            return Err(AAFError::UnsupportedEssence {
+                path,
+                reason: "its format is out of range",
+            });
+        }
+        match file_format {
+            AudioFileFormat::Wav => {
+                let header = self
+                    .wav_header()
+                    .map_err(|reason| AAFError::UnsupportedEssence {
+                        path: path.clone(),
+                        reason,
+                    })?;
+                self.write_wav(&header, out)
+            }
+            AudioFileFormat::Aiff => self.write_aiff(out),
+        }
+        .at_path(&path)
+    }
+
+    /// The chunks of a WAV file up to the samples of its `data` chunk, or
+    /// why the essence does not fit in one.
+    fn wav_header(&self) -> std::result::Result<Vec<u8>, &'static str> {
+        let too_long = "it is too long for a WAV file";
+        let bext = self.broadcast_extension.as_ref().map(|b| b.to_bytes());
+        let mut riff_len = 4 + 8 + 16 + 8 + self.samples_len + self.samples_len % 2;
+        if let Some(bext) = &bext {
+            riff_len += 8 + bext.len() as u64 + bext.len() as u64 % 2;
+        }
+        let riff_len = u32::try_from(riff_len).map_err(|_| too_long)?;
+        let format = self.format;
+        let out_of_range = "its format is out of range for a WAV file";
+        let block_align = u16::try_from(format.block_align()).map_err(|_| out_of_range)?;
+        let bytes_per_second = format
+            .sample_rate
+            .checked_mul(block_align as u32)
+            .ok_or(out_of_range)?;
+
+        let mut header = vec![];
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&riff_len.to_le_bytes());
+        header.extend_from_slice(b"WAVE");
+        header.extend_from_slice(b"fmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&format.channels.to_le_bytes());
+        header.extend_from_slice(&format.sample_rate.to_le_bytes());
+        header.extend_from_slice(&bytes_per_second.to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&format.bits_per_sample.to_le_bytes());
+        if let Some(bext) = &bext {
+            header.extend_from_slice(b"bext");
+            header.extend_from_slice(&(bext.len() as u32).to_le_bytes());
+            header.extend_from_slice(bext);
+            if bext.len() % 2 == 1 {
+                header.push(0);
+            }
+        }
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&(self.samples_len as u32).to_le_bytes());
+        Ok(header)
+    }
+
+    fn write_wav<W: Write>(&mut self, header: &[u8], out: &mut W) -> io::Result<()> {
+        out.write_all(header)?;
+        self.copy_samples(out, AudioFileFormat::Wav)?;
+        if self.samples_len % 2 == 1 {
+            out.write_all(&[0])?;
+        }
+        Ok(())
+    }
+
+    fn write_aiff<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        let samples_len = self.samples_len as u32;
+        let form_len = 4 + 8 + 18 + 8 + 8 + samples_len + samples_len % 2;
+        let format = self.format;
+
+        out.write_all(b"FORM")?;
+        out.write_all(&form_len.to_be_bytes())?;
+        out.write_all(b"AIFF")?;
+        out.write_all(b"COMM")?;
+        out.write_all(&18u32.to_be_bytes())?;
+        out.write_all(&format.channels.to_be_bytes())?;
+        out.write_all(&(self.frames() as u32).to_be_bytes())?;
+        out.write_all(&format.bits_per_sample.to_be_bytes())?;
+        out.write_all(&to_extended(format.sample_rate))?;
+        out.write_all(b"SSND")?;
+        out.write_all(&(8 + samples_len).to_be_bytes())?;
+        out.write_all(&[0; 8])?;
+        self.copy_samples(out, AudioFileFormat::Aiff)?;
+        if samples_len % 2 == 1 {
+            out.write_all(&[0])?;
+        }
+        Ok(())
+    }
+
+    /// Copy the samples to `out` as `file_format` stores them: reversing
+    /// the bytes of each, or for 8-bit samples, which are unsigned in WAV
+    /// and signed in AIFF, flipping the sign bit, if the essence was stored
+    /// for the other format.
+    fn copy_samples<W: Write>(
+        &mut self,
+        out: &mut W,
+        file_format: AudioFileFormat,
+    ) -> io::Result<()> {
+        let convert = file_format != self.file_format();
+        let sample_len = self.format.bytes_per_sample();
+        // Whole frames, of which `open` and `write` ensure at least one fits
+        let block_align = self.format.block_align();
+        let mut buffer = vec![0; COPY_LEN - COPY_LEN % block_align];
+        let mut remaining = self.samples_len;
+        self.data.seek(SeekFrom::Start(self.samples_start))?;
+        while remaining > 0 {
+            let count = remaining.min(buffer.len() as u64) as usize;
+            let chunk = &mut buffer[..count];
+            self.data.read_exact(chunk)?;
+            if convert && sample_len == 1 {
+                chunk.iter_mut().for_each(|sample| *sample ^= 0x80);
+            } else if convert {
+                chunk
+                    .chunks_exact_mut(sample_len)
+                    .for_each(|sample| sample.reverse());
+            }
+            out.write_all(chunk)?;
+            remaining -= count as u64;
+        }
+        Ok(())
+    }
+}
+
+/// The offset and length of the samples in `data`, which is either a whole
+/// WAV or AIFF file or the samples alone.
+fn find_samples<R: Read + Seek>(data: &mut R) -> io::Result<(u64, u64)> {
+    let len = data.seek(SeekFrom::End(0))?;
+    let mut header = [0; 12];
+    data.seek(SeekFrom::Start(0))?;
+    if len < 12 || data.read_exact(&mut header).is_err() {
+        return Ok((0, len));
+    }
+    let (big_endian, samples_id) = match (&header[..4], &header[8..]) {
+        (b"RIFF", b"WAVE") => (false, b"data"),
+        (b"FORM", b"AIFF") | (b"FORM", b"AIFC") => (true, b"SSND"),
+        _ => return Ok((0, len)),
+    };
+
+    let mut offset = 12;
+    while offset + 8 <= len {
+        let mut chunk = [0; 8];
+        data.seek(SeekFrom::Start(offset))?;
+        data.read_exact(&mut chunk)?;
+        let chunk_len = chunk_len(chunk[4..].try_into().unwrap(), big_endian) as u64;
+        if &chunk[..4] == samples_id {
+            let mut start = offset + 8;
+            let mut samples_len = chunk_len.min(len - start);
+            if big_endian {
+                let mut ssnd = [0; 4];
+                data.read_exact(&mut ssnd)?;
+                let skip = (8 + u32::from_be_bytes(ssnd) as u64).min(samples_len);
+                start += skip;
+                samples_len -= skip;
+            }
+            return Ok((start, samples_len));
+        }
+        offset += 8 + chunk_len + chunk_len % 2;
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "no sample data chunk",
+    ))
+}
+
+/// The format of PCM samples a `PCMDescriptor` describes, or `None` if a
+/// value is out of the range of the format's.
+fn pcm_format(
+    channels: AAFUInt32,
+    quantization_bits: AAFUInt32,
+    rate: Rational,
+) -> Option<AudioFormat> {
+    let sample_rate = match rate.denominator {
+        0 => 0.0,
+        d => (rate.numerator as f64 / d as f64).round(),
+    };
+    if !(0.0..=u32::MAX as f64).contains(&sample_rate) {
+        return None;
+    }
+    Some(AudioFormat {
+        channels: u16::try_from(channels).ok()?,
+        sample_rate: sample_rate as u32,
+        bits_per_sample: u16::try_from(quantization_bits).ok()?,
+    })
+}
+
+/// A broadcast extension made from `mob`'s name, creation time and the
+/// timecode of the tape it was recorded from, or `None` if it has neither
+/// a name nor a tape timecode.
+///
+/// A source reference chain that cannot be followed gives no timecode.
+fn make_broadcast_extension<F: Read + Seek>(
+    resolver: &SourceResolver<F>,
+    mob: &SourceMob<F>,
+    format: AudioFormat,
+) -> Result<Option<BroadcastExtension>> {
+    let mob_id = mob.mob_id()?;
+    let mut timecode = None;
+    for slot in mob.slots()? {
+        if let MobSlot::TimelineMobSlot(s) = slot {
+            timecode = resolver
+                .resolve(Some(mob_id), s.slot_id()?, 0, s.edit_rate()?)
+                .ok()
+                .and_then(|chain| chain.tape_timecode);
+            break;
+        }
+    }
+    let name = mob.name()?;
+    if name.is_none() && timecode.is_none() {
+        return Ok(None);
+    }
+
+    let created = mob.creation_time()?;
+    let (year, month, day) = created.date;
+    let (hour, minute, second, _) = created.time;
+    Ok(Some(BroadcastExtension {
+        description: name.unwrap_or_default(),
+        origination_date: format!("{:04}-{:02}-{:02}", year, month, day),
+        origination_time: format!("{:02}:{:02}:{:02}", hour, minute, second),
+        time_reference: timecode.map_or(0, |tc| timecode_samples(tc, format.sample_rate)),
+        umid: Some(mob_id),
+        ..Default::default()
+    }))
+}
+
+/// The number of samples at `sample_rate` since midnight at `timecode`.
+fn timecode_samples(timecode: TapeTimecode, sample_rate: u32) -> u64 {
+    let frame = timecode.frame.max(0) as u128;
+    let fps = timecode.fps.max(1) as u128;
+    // Drop-frame timecode counts frames at 1000/1001 of its nominal rate.
+    let (numerator, denominator) = match timecode.drop {
+        true => (1001, 1000 * fps),
+        false => (1, fps),
+    };
+    (frame * sample_rate as u128 * numerator / denominator) as u64
+}
+
+/// An 80-bit IEEE 754 extended precision number, as AIFF stores sample
+/// rates.
+fn to_extended(value: u32) -> [u8; 10] {
+    let mut bytes = [0; 10];
+    if value != 0 {
+        let shift = value.leading_zeros();
+        let exponent = 16383 + 31 - shift as u16;
+        let mantissa = (value as u64) << (32 + shift);
+        bytes[..2].copy_from_slice(&exponent.to_be_bytes());
+        bytes[2..].copy_from_slice(&mantissa.to_be_bytes());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptors::{chunks, from_extended, AifcSummary, WaveSummary};
+    use crate::file::AAFFile;
+    use std::io::Cursor;
+
+    fn test_audio() -> Vec<EmbeddedAudio<std::fs::File>> {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1_Embedded.aaf";
+        let content = AAFFile::open(test_path)
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+        embedded_audio(&content).unwrap()
+    }
+
+    #[test]
+    fn test_extended() {
+        for rate in [1, 8000, 44100, 48000, 96000, 192000] {
+            assert_eq!(from_extended(to_extended(rate)), rate);
+        }
+        assert_eq!(
+            to_extended(44100),
+            [0x40, 0x0e, 0xac, 0x44, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_broadcast_extension() {
+        let bext = BroadcastExtension {
+            description: "Take 1".into(),
+            origination_date: "2021-03-04".into(),
+            origination_time: "12:34:56".into(),
+            time_reference: 172_800_000,
+            umid: Some(
+                "urn:smpte:umid:060a2b34.01010105.01010f10.13000000.140c27a5.87e80080.9acc4f71.bfe13e00"
+                    .parse()
+                    .unwrap(),
+            ),
+            coding_history: "A=PCM,F=48000,W=24,M=mono".into(),
+            ..Default::default()
+        };
+        let bytes = bext.to_bytes();
+        assert_eq!(bytes.len(), BEXT_LEN + bext.coding_history.len());
+        // SMPTE 330M order, as the URN is written
+        assert_eq!(
+            bytes[348..380],
+            [
+                0x06, 0x0a, 0x2b, 0x34, 0x01, 0x01, 0x01, 0x05, 0x01, 0x01, 0x0f, 0x10, 0x13, 0x00,
+                0x00, 0x00, 0x14, 0x0c, 0x27, 0xa5, 0x87, 0xe8, 0x00, 0x80, 0x9a, 0xcc, 0x4f, 0x71,
+                0xbf, 0xe1, 0x3e, 0x00,
+            ]
+        );
+        assert_eq!(BroadcastExtension::parse(&bytes), Some(bext));
+    }
+
+    #[test]
+    fn test_write_wav() {
+        let mut audio = test_audio();
+        assert_eq!(audio.len(), 1);
+        let audio = &mut audio[0];
+        assert_eq!(
+            audio.format,
+            AudioFormat {
+                channels: 1,
+                sample_rate: 48000,
+                bits_per_sample: 24,
+            }
+        );
+        assert_eq!(audio.file_format(), AudioFileFormat::Wav);
+        assert_eq!(audio.frames(), 2001);
+
+        let mut wav = vec![];
+        audio.write(AudioFileFormat::Wav, &mut wav).unwrap();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes(wav[4..8].try_into().unwrap()) as usize,
+            wav.len() - 8
+        );
+        assert_eq!(&wav[8..12], b"WAVE");
+
+        let summary = WaveSummary::parse(&wav).unwrap();
+        assert_eq!(summary.audio_format(), Some(audio.format));
+        assert_eq!(summary.data_len, Some(6003));
+        assert_eq!(summary.broadcast_extension, audio.broadcast_extension);
+        let chunks = chunks(&wav, false);
+        let data = chunks.iter().find(|(id, _)| id == b"data").unwrap().1;
+        assert_eq!(data.len(), 6003);
+        assert_eq!(
+            find_samples(&mut Cursor::new(&wav)).unwrap(),
+            (
+                (data.as_ptr() as usize - wav.as_ptr() as usize) as u64,
+                6003
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_out_of_range() {
+        let rate = Rational {
+            numerator: 48000,
+            denominator: 1,
+        };
+        assert!(pcm_format(2, 24, rate).is_some());
+        assert_eq!(pcm_format(70000, 24, rate), None);
+        assert_eq!(pcm_format(2, 70000, rate), None);
+        let negative = Rational {
+            numerator: -48000,
+            denominator: 1,
+        };
+        assert_eq!(pcm_format(2, 24, negative), None);
+
+        let audio = &mut test_audio()[0];
+        for format in [
+            AudioFormat {
+                channels: u16::MAX,
+                sample_rate: 48000,
+                bits_per_sample: 32,
+            },
+            AudioFormat {
+                channels: 2,
+                sample_rate: u32::MAX,
+                bits_per_sample: 24,
+            },
+        ] {
+            audio.format = format;
+            let mut wav = vec![];
+            assert!(matches!(
+                audio.write(AudioFileFormat::Wav, &mut wav),
+                Err(AAFError::UnsupportedEssence { .. })
+            ));
+            assert!(wav.is_empty());
+        }
+
+        audio.format = AudioFormat {
+            channels: u16::MAX,
+            sample_rate: 48000,
+            bits_per_sample: u16::MAX,
+        };
+        let mut aiff = vec![];
+        assert!(matches!(
+            audio.write(AudioFileFormat::Aiff, &mut aiff),
+            Err(AAFError::UnsupportedEssence { .. })
+        ));
+        assert!(aiff.is_empty());
+    }
+
+    #[test]
+    fn test_write_aiff() {
+        let audio = &mut test_audio()[0];
+        let mut aiff = vec![];
+        audio.write(AudioFileFormat::Aiff, &mut aiff).unwrap();
+        assert_eq!(&aiff[..4], b"FORM");
+        assert_eq!(
+            u32::from_be_bytes(aiff[4..8].try_into().unwrap()) as usize,
+            aiff.len() - 8
+        );
+        assert_eq!(&aiff[8..12], b"AIFF");
+
+        let summary = AifcSummary::parse(&aiff).unwrap();
+        assert_eq!(summary.audio_format(), Some((audio.format, true)));
+        assert_eq!(summary.frames, 2001);
+        let chunks = chunks(&aiff, true);
+        let ssnd = chunks.iter().find(|(id, _)| id == b"SSND").unwrap().1;
+        assert_eq!(ssnd.len(), 8 + 6003);
+    }
+
+    #[test]
+    fn test_write_8_bit() {
+        let audio = &mut test_audio()[0];
+        audio.format = AudioFormat {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 8,
+        };
+        let mut wav = vec![];
+        audio.write(AudioFileFormat::Wav, &mut wav).unwrap();
+        let mut aiff = vec![];
+        audio.write(AudioFileFormat::Aiff, &mut aiff).unwrap();
+
+        // Unsigned in WAV, signed in AIFF
+        let wav_chunks = chunks(&wav, false);
+        let data = wav_chunks.iter().find(|(id, _)| id == b"data").unwrap().1;
+        let aiff_chunks = chunks(&aiff, true);
+        let ssnd = &aiff_chunks.iter().find(|(id, _)| id == b"SSND").unwrap().1[8..];
+        assert_eq!(data.len(), 6003);
+        assert!(data.iter().zip(ssnd).all(|(w, a)| *w == a ^ 0x80));
+        assert!(data.iter().zip(ssnd).any(|(w, a)| w != a));
+    }
+
+    #[test]
+    fn test_broken_tape_chain() {
+        // AAF_Test_1_Embedded, with the tape's slot origin so large that
+        // following a reference into it overflows.
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1_Embedded.aaf";
+        let mut xml = AAFFile::open(test_path).unwrap().to_xml_string().unwrap();
+        let tape = xml.find("<TapeDescriptor>").unwrap();
+        let origin = tape + xml[tape..].find("<Origin>0</Origin>").unwrap();
+        xml.replace_range(
+            origin..origin + "<Origin>0</Origin>".len(),
+            &format!("<Origin>{}</Origin>", i64::MAX),
+        );
+
+        let content = AAFFile::from_xml(&xml, std::path::Path::new("."))
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+        let audio = &mut embedded_audio(&content).unwrap()[0];
+        assert_eq!(audio.frames(), 2001);
+        let bext = audio.broadcast_extension.as_ref().unwrap();
+        assert_eq!(bext.time_reference, 0);
+        let mut wav = vec![];
+        audio.write(AudioFileFormat::Wav, &mut wav).unwrap();
+    }
+}
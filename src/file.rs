@@ -1,167 +1,707 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs::File;
 /// file.rs
 ///
-use std::io;
-use std::io::{Cursor, Read, Seek};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::ReadBytesExt;
 use cfb;
+use uuid::Uuid;
 
 use crate::aaf::classes::{AAFObject, Header};
+use crate::aaf::meta_dictionary::MetaDictionary;
+use crate::aaf::values::AAFValue;
+use crate::error::{AAFError, IoResultExt, Result};
 use crate::interchange_object::InterchangeObjectDescriptor;
+use crate::object_iterator::{InterchangeObjects, TraversalOrder};
 use crate::properties::*;
-use crate::types::*;
+use crate::property_cache::{PropertyCache, DEFAULT_PROPERTY_CACHE_LIMIT};
 use crate::property_iterator::AAFPropertyIterator;
+use crate::stream::{PropertyStreamReader, StreamByteOrder};
+use crate::types::*;
 
 const AAF_FILE_HEADER_PID: OMPropertyId = 0x0002;
 const AAF_FILE_METADICTIONARY_PID: OMPropertyId = 0x0001;
-// AAF File uuid b3b398a5-1c90-11d4-8053-080036210804
+
+/// The class of the root storage of an AAF file,
+/// b3b398a5-1c90-11d4-8053-080036210804.
+pub(crate) const AAF_ROOT_CLASS: Uuid = Uuid::from_bytes([
+    0xb3, 0xb3, 0x98, 0xa5, 0x1c, 0x90, 0x11, 0xd4, 0x80, 0x53, 0x08, 0x00, 0x36, 0x21, 0x08, 0x04,
+]);
+
+/// The magic number a compound file begins with.
+const CFB_MAGIC: [u8; 8] = [0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1];
+
+/// The signature an AAF writer puts in the header of a compound file, in
+/// the class id field the compound file format reserves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AAFSignature {
+    /// AAF in a compound file with 512-byte sectors.
+    Aaf512Binary,
+    /// AAF in a compound file with 4096-byte sectors.
+    Aaf4KBinary,
+    /// AAF read from an AAF-XML document into a compound file in memory.
+    AafXml,
+}
+
+impl AAFSignature {
+    /// The signature as it is stored, an AUID beginning `AAFB` for 512-byte
+    /// sectors.
+    pub fn to_bytes(self) -> [u8; 16] {
+        let kind = match self {
+            Self::Aaf512Binary => 0x42,
+            Self::Aaf4KBinary => 0x92,
+            Self::AafXml => 0x58,
+        };
+        [
+            0x41, 0x41, 0x46, kind, 0x0d, 0x00, 0x4f, 0x4d, 0x06, 0x0e, 0x2b, 0x34, 0x01, 0x01,
+            0x01, 0xff,
+        ]
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        [Self::Aaf512Binary, Self::Aaf4KBinary, Self::AafXml]
+            .into_iter()
+            .find(|s| s.to_bytes() == bytes)
+    }
+}
+
+/// How an AAF file is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileFormat {
+    /// The sector size of the compound file, 512 or 4096 bytes.
+    pub sector_size: usize,
+    /// The signature in the file's header, or `None` if its writer left it
+    /// out.
+    pub signature: Option<AAFSignature>,
+}
+
+/// A member of a strong reference set and the key it is indexed by
+type KeyedObject = (Vec<u8>, InterchangeObjectDescriptor);
+
+/// An `AAFFile` shared by the handles of the objects in it.
+pub type SharedAAFFile<F> = Rc<RefCell<AAFFile<F>>>;
 
 /// An AAF file.
 pub struct AAFFile<F> {
     f: cfb::CompoundFile<F>,
+    format: FileFormat,
     weakref_table: Vec<Vec<OMPropertyId>>,
+    meta_dictionary: Option<MetaDictionary>,
+    property_cache: PropertyCache,
+    weak_reference_targets: HashMap<OMPropertyTag, HashMap<Vec<u8>, InterchangeObjectDescriptor>>,
+    /// The members of the strong reference sets `get_set_member` has looked
+    /// in, by the path of the set's object and the set's pid.
+    set_members: HashMap<(PathBuf, OMPropertyId), HashMap<Vec<u8>, InterchangeObjectDescriptor>>,
 }
 
 impl<F> AAFFile<F> {
     /// An object at a path.
     ///
-    /// Panics: If `path` does not exist in storage
-    fn object(&self, path: PathBuf) -> InterchangeObjectDescriptor {
-        self.f
-            .entry(path)
-            .map(|entry| InterchangeObjectDescriptor {
+    /// Errors: If `path` does not exist in storage
+    fn object(&self, path: PathBuf) -> Result<InterchangeObjectDescriptor> {
+        match self.f.entry(&path) {
+            Ok(entry) => Ok(InterchangeObjectDescriptor {
                 auid: *entry.clsid(),
                 path: entry.path().into(),
-            })
-            .expect("Failed to locate object by path")
+            }),
+            Err(_) => Err(AAFError::MissingObject { path }),
+        }
+    }
+
+    /// The sector size and signature of the file.
+    pub fn format(&self) -> FileFormat {
+        self.format
     }
 
     /// The root object.
     pub fn root_object(&self) -> InterchangeObjectDescriptor {
-        self.object(PathBuf::from("/"))
+        let entry = self.f.root_entry();
+        InterchangeObjectDescriptor {
+            auid: *entry.clsid(),
+            path: entry.path().into(),
+        }
     }
 }
 
 impl AAFFile<File> {
     /// Open an AAF file at `path`
-    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<AAFFile<File>> {
-        let cfb = cfb::open(path)?;
-        Ok(Self::with_cfb(cfb))
+    ///
+    /// Errors: `NotAAFFile` if the file is not a compound file, or is a
+    /// compound file without an AAF signature or root class.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<AAFFile<File>> {
+        let path = path.as_ref();
+        let file = File::open(path).at_path(path)?;
+        Self::read_from(file, path)
     }
 }
 
 impl<F: Read + Seek> AAFFile<F> {
+    /// Read an AAF file from `inner`, such as a `Cursor` over a file held
+    /// in memory.
+    ///
+    /// Errors: As for `open`.
+    pub fn from_reader(inner: F) -> Result<Self> {
+        Self::read_from(inner, Path::new("/"))
+    }
+
+    /// Read an AAF file from `inner`, reporting I/O errors against `path`.
+    fn read_from(mut inner: F, path: &Path) -> Result<Self> {
+        let not_cfb = AAFError::NotAAFFile {
+            reason: "it is not a compound file",
+        };
+        let mut header = [0u8; 24];
+        inner.seek(SeekFrom::Start(0)).at_path(path)?;
+        match inner.read_exact(&mut header) {
+            Ok(()) if header[0..8] == CFB_MAGIC => {}
+            Ok(()) => return Err(not_cfb),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(not_cfb),
+            Err(e) => return Err(AAFError::from_io(path, e)),
+        }
+        let signature = AAFSignature::from_bytes(&header[8..24]);
+        inner.seek(SeekFrom::Start(0)).at_path(path)?;
+        let cfb = cfb::CompoundFile::open(inner).at_path(path)?;
+        Self::with_cfb(cfb, signature)
+    }
+
     /// Walk the AAF object graph
     ///
     ///
-    pub fn walk_properties(&mut self) -> AAFPropertyIterator<F> {
-        AAFPropertyIterator::new(self, self.root_object())
+    pub fn walk_properties(&mut self) -> AAFPropertyIterator<'_, F> {
+        let root = self.root_object();
+        AAFPropertyIterator::new(self, root)
     }
 
-    pub fn header(mut self) -> Header<F> {
-        if let Some(PropertyValue::Single(obj)) =
-            self.get_value(&self.root_object(), AAF_FILE_HEADER_PID)
-        {
-            Header::make(self, obj)
-        } else {
-            panic!()
-        }
+    /// Iterate over every object in the strong reference tree, depth-first.
+    pub fn objects(&mut self) -> InterchangeObjects<'_, F> {
+        let root = self.root_object();
+        InterchangeObjects::new(self, root)
+    }
+
+    /// Iterate over every object in the strong reference tree,
+    /// breadth-first.
+    pub fn objects_breadth_first(&mut self) -> InterchangeObjects<'_, F> {
+        let root = self.root_object();
+        InterchangeObjects::with_order(self, root, TraversalOrder::BreadthFirst)
+    }
+
+    /// The file's `Header`, through which its other objects are reached.
+    ///
+    /// The file is shared by the handles of all the objects opened from the
+    /// header.
+    pub fn header(mut self) -> Result<Header<F>> {
+        let root = self.root_object();
+        let obj = self.get_object(&root, AAF_FILE_HEADER_PID)?;
+        Header::make(Rc::new(RefCell::new(self)), obj)
     }
 
-    // pub fn meta_dictionary(&mut self) -> MetaDictionary<F> {
-    //     if let Some(PropertyValue::Single(obj)) =
-    //         self.get_value(&self.root_object(), AAF_FILE_METADICTIONARY_PID) {
-    //         MetaDictionary {
-    //             file: self,
-    //             object: obj
-    //         }
-    //     } else {
-    //         panic!()
-    //     }
-    // }
+    /// The file's `MetaDictionary`, the class and type definitions the file
+    /// was written with.
+    ///
+    /// The dictionary is read the first time it is asked for.
+    pub fn meta_dictionary(&mut self) -> Result<&MetaDictionary> {
+        let md = match self.meta_dictionary.take() {
+            Some(md) => md,
+            None => {
+                let root = self.root_object();
+                let obj = self.get_object(&root, AAF_FILE_METADICTIONARY_PID)?;
+                MetaDictionary::read(self, &obj)?
+            }
+        };
+        Ok(self.meta_dictionary.insert(md))
+    }
+
+    /// Use `md` as the file's `MetaDictionary` instead of reading it from
+    /// the file.
+    pub(crate) fn set_meta_dictionary(&mut self, md: MetaDictionary) {
+        self.meta_dictionary = Some(md);
+    }
+
+    /// Bound the memory used to cache objects' properties to about `limit`
+    /// bytes. A limit of zero disables the cache.
+    ///
+    /// The default is `DEFAULT_PROPERTY_CACHE_LIMIT`.
+    pub fn set_property_cache_limit(&mut self, limit: usize) {
+        self.property_cache.set_limit(limit);
+    }
+
+    /// The estimated memory used by the property cache, in bytes.
+    pub fn property_cache_size(&self) -> usize {
+        self.property_cache.size()
+    }
 
     /// All of the `OMPropertyId`s available in the AAFFile for the given object
-    pub fn all_property_ids(&mut self, object: &InterchangeObjectDescriptor) -> Vec<OMPropertyId> {
-        let props = self.raw_properties(&object);
-        props.into_iter().map(|p| p.pid).collect()
+    pub fn all_property_ids(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+    ) -> Result<Vec<OMPropertyId>> {
+        let props = self.raw_properties(object)?;
+        Ok(props.iter().map(|p| p.pid).collect())
     }
 
     /// Get the value of an object property.
+    ///
+    /// Returns `Ok(None)` if the object does not have the property.
     pub fn get_value(
         &mut self,
         object: &InterchangeObjectDescriptor,
         pid: OMPropertyId,
-    ) -> Option<PropertyValue> {
-        let prop = self.raw_property_by_pid(object, pid)?;
-        self.resolve_property_value(object, &prop)
+    ) -> Result<Option<PropertyValue>> {
+        match self.raw_property_by_pid(object, pid)? {
+            Some(prop) => self.resolve_property_value(object, &prop).map(Some),
+            None => Ok(None),
+        }
     }
 
-    /// A new `AAFFile` with a `cfb::CompoundFile`
-    fn with_cfb(mut cfb: cfb::CompoundFile<F>) -> Self {
-        let weakref_table = Self::weak_refs_table(&mut cfb);
-        // let session = Session { };
-        Self {
-            f: cfb,
-            weakref_table: weakref_table,
-            // session,
+    /// Get the value of a data property decoded as a `T`.
+    ///
+    /// Returns `Ok(None)` if the object does not have the property.
+    pub fn get_data<T: AAFFrom>(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+    ) -> Result<Option<T>> {
+        let property = match self.raw_property_by_pid(object, pid)? {
+            Some(property) => property,
+            None => return Ok(None),
+        };
+        let value = self.resolve_property_value(object, &property)?;
+        decode_data(Some(value), property.endian, &object.path, pid)
+    }
+
+    /// Get the value of a property decoded according to its type definition
+    /// in the file's `MetaDictionary` or the baseline.
+    ///
+    /// Returns `Ok(None)` if the object does not have the property.
+    pub fn get_typed_value(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+    ) -> Result<Option<AAFValue>> {
+        let raw = match self.raw_property_by_pid(object, pid)? {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        let bytes = match self.resolve_property_value(object, &raw)? {
+            PropertyValue::Data(bytes) | PropertyValue::UniqueObjectId(bytes) => bytes,
+            value => return Ok(AAFValue::from_property_value(value)),
+        };
+
+        let md = self.meta_dictionary()?;
+        let property =
+            md.resolve_property(&object.auid, pid)
+                .ok_or_else(|| AAFError::UndefinedProperty {
+                    path: object.path.clone(),
+                    pid,
+                })?;
+        let type_def =
+            md.resolve_type(&property.type_id)
+                .ok_or_else(|| AAFError::UndefinedType {
+                    path: object.path.clone(),
+                    pid,
+                    type_id: property.type_id,
+                })?;
+
+        match AAFValue::decode(md, type_def, &bytes, raw.endian) {
+            Ok(value) => Ok(Some(value)),
+            Err(AAFError::InvalidValue { type_name, .. }) => Err(AAFError::InvalidProperty {
+                path: object.path.clone(),
+                pid,
+                type_name,
+            }),
+            Err(AAFError::UndefinedType { type_id, .. }) => Err(AAFError::UndefinedType {
+                path: object.path.clone(),
+                pid,
+                type_id,
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get the object a strong or weak reference property refers to.
+    ///
+    /// Returns `Ok(None)` if the object does not have the property.
+    pub fn get_reference(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+    ) -> Result<Option<InterchangeObjectDescriptor>> {
+        let value = self.get_value(object, pid)?;
+        decode_reference(value, &object.path, pid)
+    }
+
+    /// Get the members of a strong or weak reference vector or set.
+    ///
+    /// Returns an empty `Vec` if the object does not have the property.
+    pub fn get_collection(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+    ) -> Result<Vec<InterchangeObjectDescriptor>> {
+        let value = self.get_value(object, pid)?;
+        decode_collection(value, &object.path, pid)
+    }
+
+    /// Get the member of a strong reference set with the key `key`, from
+    /// the set's index.
+    ///
+    /// AUID and MobID keys are compared as a little-endian file stores
+    /// them, whatever the byte order of the file.
+    ///
+    /// The set's index is read the first time a set is looked in.
+    ///
+    /// Returns `Ok(None)` if the object does not have the property or the
+    /// set has no member with the key.
+    pub fn get_set_member(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+        key: &[u8],
+    ) -> Result<Option<InterchangeObjectDescriptor>> {
+        let set = (object.path.clone(), pid);
+        if !self.set_members.contains_key(&set) {
+            let property = match self.raw_property_by_pid(object, pid)? {
+                Some(p) if p.stored_form == SF_STRONG_OBJECT_REF_SET => p,
+                Some(_) => {
+                    return Err(AAFError::UnexpectedValue {
+                        path: object.path.clone(),
+                        pid,
+                        expected: "a strong reference set",
+                    })
+                }
+                None => return Ok(None),
+            };
+            let (_, members) = self.strong_set_members(object, &property)?;
+            self.set_members
+                .insert(set.clone(), members.into_iter().collect());
         }
+
+        Ok(self
+            .set_members
+            .get(&set)
+            .and_then(|members| members.get(key))
+            .cloned())
+    }
+
+    /// A new `AAFFile` with a `cfb::CompoundFile`, and the signature from
+    /// its header.
+    ///
+    /// A file without a signature must have an AAF root class.
+    pub(crate) fn with_cfb(
+        mut cfb: cfb::CompoundFile<F>,
+        signature: Option<AAFSignature>,
+    ) -> Result<Self> {
+        if signature.is_none() && *cfb.root_entry().clsid() != AAF_ROOT_CLASS {
+            return Err(AAFError::NotAAFFile {
+                reason: "it has neither an AAF signature nor an AAF root class",
+            });
+        }
+        let sector_size = match cfb.version() {
+            cfb::Version::V3 => 512,
+            cfb::Version::V4 => 4096,
+        };
+        let weakref_table = Self::weak_refs_table(&mut cfb)?;
+        Ok(Self {
+            f: cfb,
+            format: FileFormat {
+                sector_size,
+                signature,
+            },
+            weakref_table,
+            meta_dictionary: None,
+            property_cache: PropertyCache::new(DEFAULT_PROPERTY_CACHE_LIMIT),
+            weak_reference_targets: HashMap::new(),
+            set_members: HashMap::new(),
+        })
     }
 
     /// Retrive and parse the `referenced properties` table for a given cfb file
-    fn weak_refs_table(f: &mut cfb::CompoundFile<F>) -> Vec<Vec<OMPropertyId>> {
-        let ref_props_stream = f
-            .open_stream(PathBuf::from("/referenced properties"))
-            .expect("Failed to open referenced properties stream");
+    fn weak_refs_table(f: &mut cfb::CompoundFile<F>) -> Result<Vec<Vec<OMPropertyId>>> {
+        let path = Path::new("/referenced properties");
+        let ref_props_stream = f.open_stream(path).at_path(path)?;
+
+        Ok(ReferencedPropertiesTable::from_stream(ref_props_stream, path)?.pid_paths)
+    }
+
+    /// The objects `object` owns through each of its strong reference
+    /// properties, in property order.
+    pub(crate) fn strong_references(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+    ) -> Result<Vec<(OMPropertyId, Vec<InterchangeObjectDescriptor>)>> {
+        let props = self.raw_properties(object)?;
+        let mut retval = vec![];
+        for property in props.iter() {
+            let owned = match property.stored_form {
+                SF_STRONG_OBJECT_REF | SF_STRONG_OBJECT_REF_VECTOR | SF_STRONG_OBJECT_REF_SET => {
+                    self.resolve_property_value(object, property)?
+                }
+                _ => continue,
+            };
+            match owned {
+                PropertyValue::Single(obj) => retval.push((property.pid, vec![obj])),
+                PropertyValue::Vector(objs) | PropertyValue::Set(objs) => {
+                    retval.push((property.pid, objs))
+                }
+                _ => {}
+            }
+        }
+        Ok(retval)
+    }
+
+    /// The objects `object` refers to through each of its weak reference
+    /// properties, in property order, or why a property's references could
+    /// not be resolved.
+    pub(crate) fn weak_references(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+    ) -> Result<Vec<(OMPropertyId, Result<Vec<InterchangeObjectDescriptor>>)>> {
+        let props = self.raw_properties(object)?;
+        let mut retval = vec![];
+        for property in props.iter() {
+            let referenced = match property.stored_form {
+                SF_WEAK_OBJECT_REF | SF_WEAK_OBJECT_REF_VECTOR | SF_WEAK_OBJECT_REF_SET => {
+                    self.resolve_property_value(object, property)
+                }
+                _ => continue,
+            };
+            match referenced {
+                Ok(PropertyValue::Reference(obj)) => retval.push((property.pid, Ok(vec![obj]))),
+                Ok(PropertyValue::ReferenceVector(objs))
+                | Ok(PropertyValue::ReferenceSet(objs)) => retval.push((property.pid, Ok(objs))),
+                Ok(_) => {}
+                Err(e) => retval.push((property.pid, Err(e))),
+            }
+        }
+        Ok(retval)
+    }
+
+    /// Open the stream of a stream property for reading.
+    ///
+    /// Returns `Ok(None)` if the object does not have the property.
+    pub fn open_stream_property(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+    ) -> Result<Option<PropertyStreamReader<'_, F>>> {
+        match self.stream_property(object, pid)? {
+            Some((path, byte_order)) => self.open_stream(path, byte_order).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Open the stream at `path` of a stream property, whose data is in
+    /// `byte_order`.
+    pub(crate) fn open_stream(
+        &mut self,
+        path: PathBuf,
+        byte_order: StreamByteOrder,
+    ) -> Result<PropertyStreamReader<'_, F>> {
+        match self.f.open_stream(&path) {
+            Ok(stream) => Ok(PropertyStreamReader::new(stream, path, byte_order)),
+            Err(_) => Err(AAFError::MissingObject { path }),
+        }
+    }
+
+    /// The path and byte order of the stream of a stream property.
+    ///
+    /// Returns `Ok(None)` if the object does not have the property.
+    pub(crate) fn stream_property(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+    ) -> Result<Option<(PathBuf, StreamByteOrder)>> {
+        let property = match self.raw_property_by_pid(object, pid)? {
+            Some(p) if p.stored_form == SF_DATA_STREAM => p,
+            Some(p) if p.stored_form == SF_OPAQUE_STREAM => {
+                let path = self.opaque_stream_path(object, &p);
+                return Ok(Some((path, StreamByteOrder::Unspecified)));
+            }
+            Some(_) => {
+                return Err(AAFError::UnexpectedValue {
+                    path: object.path.clone(),
+                    pid,
+                    expected: "a stream",
+                })
+            }
+            None => return Ok(None),
+        };
+        let prefix = property.raw_value.first().copied().unwrap_or_default();
+        let byte_order =
+            StreamByteOrder::from_prefix(prefix).ok_or_else(|| AAFError::BadByteOrder {
+                path: object.path.clone(),
+                byte_order: prefix,
+            })?;
+        let path = object.path.join(property.raw_stream_name());
+        Ok(Some((path, byte_order)))
+    }
+
+    /// The path of the stream of an opaque stream property of `object`:
+    /// whichever of the property's possible stream names is a stream in
+    /// the object's storage, or the name as stored if neither is.
+    fn opaque_stream_path(
+        &self,
+        object: &InterchangeObjectDescriptor,
+        property: &RawProperty,
+    ) -> PathBuf {
+        let [stored, prefixed] = property
+            .raw_opaque_stream_names()
+            .map(|name| object.path.join(name));
+        if !self.f.is_stream(&stored) && self.f.is_stream(&prefixed) {
+            prefixed
+        } else {
+            stored
+        }
+    }
+
+    /// The strong object referenced by `pid` on `object`
+    pub(crate) fn get_object(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+    ) -> Result<InterchangeObjectDescriptor> {
+        match self.get_value(object, pid)? {
+            Some(PropertyValue::Single(obj)) => Ok(obj),
+            Some(_) => Err(AAFError::UnexpectedValue {
+                path: object.path.clone(),
+                pid,
+                expected: "a strong object reference",
+            }),
+            None => Err(AAFError::MissingProperty {
+                path: object.path.clone(),
+                pid,
+            }),
+        }
+    }
+
+    fn resolve_weak_reference(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+        weak_ref: WeakObjectReference,
+    ) -> Result<InterchangeObjectDescriptor> {
+        if !self.weak_reference_targets.contains_key(&weak_ref.tag) {
+            let targets = self.weak_reference_target_set(object, pid, &weak_ref)?;
+            self.weak_reference_targets.insert(weak_ref.tag, targets);
+        }
 
-        ReferencedPropertiesTable::from_stream(ref_props_stream).pid_paths
+        self.weak_reference_targets
+            .get(&weak_ref.tag)
+            .and_then(|targets| targets.get(&weak_ref.identification))
+            .cloned()
+            .ok_or_else(|| AAFError::DanglingWeakReference {
+                path: object.path.clone(),
+                pid,
+            })
     }
 
-    fn resolve_weak_reference(&mut self, weak_ref: WeakObjectReference) -> PropertyValue {
-        let pid_path = self.weakref_table[weak_ref.tag as usize].to_vec();
+    /// The members of the strong reference set a weak reference's tag
+    /// refers to, by key.
+    fn weak_reference_target_set(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+        weak_ref: &WeakObjectReference,
+    ) -> Result<HashMap<Vec<u8>, InterchangeObjectDescriptor>> {
+        let pid_path = match self.weakref_table.get(weak_ref.tag as usize) {
+            Some(p) if !p.is_empty() => p.to_vec(),
+            _ => {
+                return Err(AAFError::BadWeakReferenceTag {
+                    path: object.path.clone(),
+                    pid,
+                    tag: weak_ref.tag,
+                })
+            }
+        };
 
         let mut obj = self.root_object();
 
         for pid in &pid_path[0..pid_path.len() - 1] {
-            let p1 = self.raw_property_by_pid(&obj, *pid).unwrap();
-            let pv = self.resolve_property_value(&obj, &p1).unwrap();
-            obj = pv.unwrap_object();
+            obj = self.get_object(&obj, *pid)?;
         }
 
-        let pfinal = self
-            .raw_property_by_pid(&obj, pid_path[pid_path.len() - 1])
-            .unwrap();
-        let found = self
-            .resolve_property_value(&obj, &pfinal)
-            .unwrap()
-            .unwrap_set()
+        let set_pid = pid_path[pid_path.len() - 1];
+        let set_property = match self.raw_property_by_pid(&obj, set_pid)? {
+            Some(p) if p.stored_form == SF_STRONG_OBJECT_REF_SET => p,
+            Some(_) => {
+                return Err(AAFError::UnexpectedValue {
+                    path: obj.path,
+                    pid: set_pid,
+                    expected: "a strong reference set",
+                })
+            }
+            None => {
+                return Err(AAFError::DanglingWeakReference {
+                    path: object.path.clone(),
+                    pid,
+                })
+            }
+        };
+
+        let (key_pid, members) = self.strong_set_members(&obj, &set_property)?;
+        if key_pid == weak_ref.key_pid {
+            return Ok(members.into_iter().collect());
+        }
+
+        // The set is keyed on a different property than the reference, so
+        // read the reference's key from each member.
+        let mut retval = HashMap::new();
+        for (_, member) in members {
+            if let Some(ident) = self.raw_property_by_pid(&member, weak_ref.key_pid)? {
+                retval.insert(normalize_key(ident.raw_value, ident.endian), member);
+            }
+        }
+        Ok(retval)
+    }
+
+    /// The members of a strong reference set, with the key each is indexed
+    /// by, and the pid of the key property.
+    fn strong_set_members(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        property: &RawProperty,
+    ) -> Result<(OMPropertyId, Vec<KeyedObject>)> {
+        let decoded_name = property.raw_string_value();
+        let (index_path, index_stream) = self.open_index(object, property)?;
+        let set_index = StrongSetReferenceIndex::from_istream(index_stream, property.endian)
+            .at_path(&index_path)?;
+
+        let members = set_index
+            .members(decoded_name, &object.path)
             .into_iter()
-            .find(|i| {
-                let ident = self
-                    .raw_property_by_pid(&i, weak_ref.key_pid)
-                    .unwrap()
-                    .raw_value;
-                *ident == weak_ref.identification
-            })
-            .unwrap();
+            .map(|(key, path)| Ok((key, self.object(path)?)))
+            .collect::<Result<_>>()?;
 
-        PropertyValue::Reference(found)
+        Ok((set_index.key_pid, members))
     }
 
     /// All of the raw properties for a given InterchangeObjectDescriptor
-    fn raw_properties(&mut self, object: &InterchangeObjectDescriptor) -> Vec<RawProperty> {
+    fn raw_properties(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+    ) -> Result<Arc<Vec<RawProperty>>> {
+        if let Some(props) = self.property_cache.get(&object.path) {
+            return Ok(props);
+        }
+
         let properties_path = object.path.join("properties");
-        let mut stream = self.f.open_stream(&properties_path).expect(&format!(
-            "Failed to open `properties` stream for object {:?}",
-            object
-        ));
+        let mut stream = self
+            .f
+            .open_stream(&properties_path)
+            .at_path(&properties_path)?;
 
         let mut buf: Vec<u8> = vec![];
-        stream
-            .read_to_end(&mut buf)
-            .expect("Error reading properties IStream");
-        RawProperty::from_properties_istream(&mut buf)
+        stream.read_to_end(&mut buf).at_path(&properties_path)?;
+        let props = Arc::new(RawProperty::from_properties_istream(
+            &buf,
+            &properties_path,
+        )?);
+        self.property_cache.insert(&object.path, props.clone());
+        Ok(props)
     }
 
     /// Retrive a raw property for an InterchangeObjectDescriptor
@@ -169,88 +709,122 @@ impl<F: Read + Seek> AAFFile<F> {
         &mut self,
         object: &InterchangeObjectDescriptor,
         pid: OMPropertyId,
-    ) -> Option<RawProperty> {
-        self.raw_properties(object)
-            .into_iter()
+    ) -> Result<Option<RawProperty>> {
+        Ok(self
+            .raw_properties(object)?
+            .iter()
             .find(|p| p.pid == pid)
+            .cloned())
     }
 
-    fn resolve_property_value(
+    /// Open the index stream of a collection property
+    fn open_index(
         &mut self,
         object: &InterchangeObjectDescriptor,
         property: &RawProperty,
-    ) -> Option<PropertyValue> {
-        let raw_data = Self::raw_property_by_pid(self, object, property.pid)?.raw_value;
+    ) -> Result<(PathBuf, cfb::Stream<'_, F>)> {
+        let index_path = property.index_path(&object.path);
+        match self.f.open_stream(&index_path) {
+            Ok(stream) => Ok((index_path, stream)),
+            Err(_) => Err(AAFError::MissingIndexStream {
+                path: index_path,
+                pid: property.pid,
+            }),
+        }
+    }
 
+    fn resolve_property_value(
+        &mut self,
+        object: &InterchangeObjectDescriptor,
+        property: &RawProperty,
+    ) -> Result<PropertyValue> {
         match property.stored_form {
-            SF_DATA => Some(PropertyValue::Data(raw_data)),
+            SF_DATA => Ok(PropertyValue::Data(property.raw_value.clone())),
             SF_DATA_STREAM => {
-                let decoded_name = property.raw_string_value();
+                let decoded_name = property.raw_stream_name();
                 let ref_path = object.path.join(decoded_name);
-                Some(PropertyValue::Stream(ref_path))
+                Ok(PropertyValue::Stream(ref_path))
             }
             SF_STRONG_OBJECT_REF => {
                 let decoded_name = property.raw_string_value();
                 let ref_path = object.path.join(decoded_name);
-                Some(PropertyValue::Single(self.object(ref_path)))
+                Ok(PropertyValue::Single(self.object(ref_path)?))
             }
             SF_STRONG_OBJECT_REF_VECTOR => {
                 let decoded_name = property.raw_string_value();
-                let index_name = format!("{} index", decoded_name);
-                let index_path = object.path.join(index_name);
-                let index_stream = self.f.open_stream(index_path).unwrap();
-                let vector_index = StrongVectorReferenceIndex::from_istream(index_stream);
+                let (index_path, index_stream) = self.open_index(object, property)?;
+                let vector_index =
+                    StrongVectorReferenceIndex::from_istream(index_stream, property.endian)
+                        .at_path(&index_path)?;
 
                 let members = vector_index
                     .member_paths(decoded_name, &object.path)
                     .into_iter()
                     .map(|path| self.object(path))
-                    .collect();
+                    .collect::<Result<_>>()?;
 
-                Some(PropertyValue::Vector(members))
+                Ok(PropertyValue::Vector(members))
             }
             SF_STRONG_OBJECT_REF_SET => {
-                let decoded_name = property.raw_string_value();
-                let index_name = format!("{} index", decoded_name);
-                let index_path = object.path.join(index_name);
-                let index_stream = self.f.open_stream(index_path).unwrap();
-                let set_index = StrongSetReferenceIndex::from_istream(index_stream);
-
-                let members = set_index
-                    .member_paths(decoded_name, &object.path)
-                    .into_iter()
-                    .map(|path| self.object(path))
-                    .collect();
-
-                Some(PropertyValue::Set(members))
+                let (_, members) = self.strong_set_members(object, property)?;
+                Ok(PropertyValue::Set(
+                    members.into_iter().map(|(_, member)| member).collect(),
+                ))
             }
             SF_WEAK_OBJECT_REF => {
-                let weak_ref = WeakObjectReference::from_data(&property.raw_value);
-                Some(self.resolve_weak_reference(weak_ref))
+                let weak_ref = WeakObjectReference::from_data(&property.raw_value, property.endian)
+                    .at_path(&object.path.join("properties"))?;
+                let found = self.resolve_weak_reference(object, property.pid, weak_ref)?;
+                Ok(PropertyValue::Reference(found))
             }
             SF_WEAK_OBJECT_REF_VECTOR | SF_WEAK_OBJECT_REF_SET => {
-                let decoded_name = property.raw_string_value();
-                let index_name = format!("{} index", decoded_name);
-                let index_path = object.path.join(index_name);
-                let index_stream = self.f.open_stream(index_path).unwrap();
+                let (index_path, index_stream) = self.open_index(object, property)?;
                 let weak_vec_refs =
-                    WeakCollectionReference::from_istream(index_stream).into_weak_references();
+                    WeakCollectionReference::from_istream(index_stream, property.endian)
+                        .at_path(&index_path)?
+                        .into_weak_references();
 
                 let refs = weak_vec_refs
                     .into_iter()
-                    .map(|r| self.resolve_weak_reference(r).unwrap_reference())
-                    .collect();
+                    .map(|r| self.resolve_weak_reference(object, property.pid, r))
+                    .collect::<Result<_>>()?;
                 if property.stored_form == SF_WEAK_OBJECT_REF_VECTOR {
-                    Some(PropertyValue::ReferenceVector(refs))
+                    Ok(PropertyValue::ReferenceVector(refs))
                 } else {
-                    Some(PropertyValue::ReferenceSet(refs))
+                    Ok(PropertyValue::ReferenceSet(refs))
                 }
             }
-            _ => panic!("Unrecgonized stored form found."),
+            SF_OPAQUE_STREAM => Ok(PropertyValue::OpaqueStream(
+                self.opaque_stream_path(object, property),
+            )),
+            SF_UNIQUE_OBJ_ID => Ok(PropertyValue::UniqueObjectId(property.raw_value.clone())),
+            SF_WEAK_OBJECT_STORED_OBJ_ID => Ok(PropertyValue::StoredObjectId(normalize_key(
+                property.raw_value.clone(),
+                property.endian,
+            ))),
+            stored_form => Ok(PropertyValue::Unknown {
+                stored_form,
+                bytes: property.raw_value.clone(),
+            }),
         }
     }
 }
 
+/// A set or weak reference key in the byte order of a little-endian file,
+/// so that keys read from files of either byte order compare equal.
+///
+/// Keys are AUIDs or, for mobs, MobIDs, which end with an AUID.
+fn normalize_key(mut key: Vec<u8>, endian: Endian) -> Vec<u8> {
+    if endian == Endian::Big {
+        match key.len() {
+            16 => swap_auid(&mut key),
+            32 => swap_auid(&mut key[16..]),
+            _ => {}
+        }
+    }
+    key
+}
+
 struct StrongVectorReferenceIndex {
     _entry_count: u32,
     _first_free_key: u32,
@@ -259,25 +833,24 @@ struct StrongVectorReferenceIndex {
 }
 
 impl StrongVectorReferenceIndex {
-    fn from_istream<T: Read + Seek>(mut stream: T) -> Self {
-        let entry_count = stream.read_u32::<LittleEndian>().unwrap() as usize;
-        let first_free_key = stream.read_u32::<LittleEndian>().unwrap();
-        let last_free_key = stream.read_u32::<LittleEndian>().unwrap();
+    fn from_istream<T: Read>(mut stream: T, endian: Endian) -> io::Result<Self> {
+        let entry_count = endian.read_u32(&mut stream)? as usize;
+        let first_free_key = endian.read_u32(&mut stream)?;
+        let last_free_key = endian.read_u32(&mut stream)?;
 
-        let mut local_keys = vec![0u32; entry_count];
-        for i in 0..entry_count {
-            let entry = stream.read_u32::<LittleEndian>().unwrap();
-            local_keys[i] = entry;
+        let mut local_keys = vec![];
+        for _ in 0..entry_count {
+            local_keys.push(endian.read_u32(&mut stream)?);
         }
-        StrongVectorReferenceIndex {
+        Ok(StrongVectorReferenceIndex {
             _entry_count: entry_count as u32,
             _first_free_key: first_free_key,
             _last_free_key: last_free_key,
             local_keys,
-        }
+        })
     }
 
-    fn member_paths(&self, property_name: String, parent_path: &PathBuf) -> Vec<PathBuf> {
+    fn member_paths(&self, property_name: String, parent_path: &Path) -> Vec<PathBuf> {
         self.local_keys
             .iter()
             .map(|i| {
@@ -290,56 +863,57 @@ impl StrongVectorReferenceIndex {
 
 struct StrongSetReferenceIndexEntry {
     local_key: u32,
-    reference_count: u32,
+    _reference_count: u32,
     identification: Vec<u8>,
 }
 
 struct StrongSetReferenceIndex {
     _entry_count: u32,
-    first_free_key: u32,
-    last_free_key: u32,
+    _first_free_key: u32,
+    _last_free_key: u32,
     key_pid: OMPropertyId,
-    key_size: OMKeySize,
+    _key_size: OMKeySize,
     local_keys: Vec<StrongSetReferenceIndexEntry>,
 }
 
 impl StrongSetReferenceIndex {
-    fn from_istream<T: Read + Seek>(mut stream: T) -> Self {
-        let entry_count = stream.read_u32::<LittleEndian>().unwrap() as usize;
-        let first_free_key = stream.read_u32::<LittleEndian>().unwrap();
-        let last_free_key = stream.read_u32::<LittleEndian>().unwrap();
-        let key_pid = stream.read_u16::<LittleEndian>().unwrap() as OMPropertyId;
-        let key_size = stream.read_u8().unwrap() as OMKeySize;
+    fn from_istream<T: Read>(mut stream: T, endian: Endian) -> io::Result<Self> {
+        let entry_count = endian.read_u32(&mut stream)? as usize;
+        let first_free_key = endian.read_u32(&mut stream)?;
+        let last_free_key = endian.read_u32(&mut stream)?;
+        let key_pid = endian.read_u16(&mut stream)? as OMPropertyId;
+        let key_size = stream.read_u8()? as OMKeySize;
 
         let mut local_keys: Vec<StrongSetReferenceIndexEntry> = vec![];
         for _ in 0..entry_count {
-            let local_key = stream.read_u32::<LittleEndian>().unwrap();
-            let reference_count = stream.read_u32::<LittleEndian>().unwrap();
+            let local_key = endian.read_u32(&mut stream)?;
+            let reference_count = endian.read_u32(&mut stream)?;
             let mut identification = vec![0; key_size as usize];
-            stream.read_exact(&mut identification).unwrap();
+            stream.read_exact(&mut identification)?;
             let obj = StrongSetReferenceIndexEntry {
                 local_key,
-                reference_count,
-                identification,
+                _reference_count: reference_count,
+                identification: normalize_key(identification, endian),
             };
             local_keys.push(obj);
         }
-        Self {
+        Ok(Self {
             _entry_count: entry_count as u32,
-            first_free_key,
-            last_free_key,
+            _first_free_key: first_free_key,
+            _last_free_key: last_free_key,
             key_pid,
-            key_size,
+            _key_size: key_size,
             local_keys,
-        }
+        })
     }
 
-    fn member_paths(&self, property_name: String, parent_path: &PathBuf) -> Vec<PathBuf> {
+    /// The key and storage path of each member of the set
+    fn members(&self, property_name: String, parent_path: &Path) -> Vec<(Vec<u8>, PathBuf)> {
         self.local_keys
             .iter()
             .map(|i| {
                 let member_name = format!("{}{{{:x}}}", property_name, i.local_key);
-                parent_path.join(member_name)
+                (i.identification.clone(), parent_path.join(member_name))
             })
             .collect()
     }
@@ -353,30 +927,28 @@ struct WeakObjectReference {
 }
 
 impl WeakObjectReference {
-    fn from_data(data: &[u8]) -> Self {
+    fn from_data(data: &[u8], endian: Endian) -> io::Result<Self> {
         let cursor = Cursor::new(data);
-        Self::from_istream(cursor)
+        Self::from_istream(cursor, endian)
     }
-    fn from_istream<T: Read + Seek>(mut stream: T) -> Self {
-        let tag = stream.read_u16::<LittleEndian>().unwrap() as OMPropertyTag;
-        let key_pid = stream.read_u16::<LittleEndian>().unwrap() as OMPropertyId;
-        let key_size = stream.read_u8().unwrap() as OMKeySize;
+    fn from_istream<T: Read>(mut stream: T, endian: Endian) -> io::Result<Self> {
+        let tag = endian.read_u16(&mut stream)? as OMPropertyTag;
+        let key_pid = endian.read_u16(&mut stream)? as OMPropertyId;
+        let key_size = stream.read_u8()? as OMKeySize;
         let mut identification = vec![0u8; key_size as usize];
-        stream
-            .read_exact(&mut identification)
-            .expect("Failed to read reference identification length");
+        stream.read_exact(&mut identification)?;
 
-        WeakObjectReference {
+        Ok(WeakObjectReference {
             tag,
             key_pid,
             _key_size: key_size,
-            identification,
-        }
+            identification: normalize_key(identification, endian),
+        })
     }
 }
 
 struct WeakCollectionReference {
-    entry_count: u32,
+    _entry_count: u32,
     tag: OMPropertyTag,
     key_pid: OMPropertyId,
     key_size: OMKeySize,
@@ -384,30 +956,28 @@ struct WeakCollectionReference {
 }
 
 impl WeakCollectionReference {
-    fn from_istream<T: Read + Seek>(mut stream: T) -> Self {
-        let entry_count = stream.read_u32::<LittleEndian>().unwrap();
-        let tag = stream.read_u16::<LittleEndian>().unwrap() as OMPropertyTag;
-        let key_pid = stream.read_u16::<LittleEndian>().unwrap() as OMPropertyId;
-        let key_size = stream.read_u8().unwrap() as OMKeySize;
+    fn from_istream<T: Read>(mut stream: T, endian: Endian) -> io::Result<Self> {
+        let entry_count = endian.read_u32(&mut stream)?;
+        let tag = endian.read_u16(&mut stream)? as OMPropertyTag;
+        let key_pid = endian.read_u16(&mut stream)? as OMPropertyId;
+        let key_size = stream.read_u8()? as OMKeySize;
 
         let mut identification_list = vec![];
 
         for _ in 0..entry_count {
             let mut identification = vec![0u8; key_size as usize];
-            stream
-                .read_exact(&mut identification)
-                .expect("Failed to read all WeakVectorReference fields");
+            stream.read_exact(&mut identification)?;
 
-            identification_list.push(identification);
+            identification_list.push(normalize_key(identification, endian));
         }
 
-        WeakCollectionReference {
-            entry_count,
+        Ok(WeakCollectionReference {
+            _entry_count: entry_count,
             tag,
             key_pid,
             key_size,
             identification_list,
-        }
+        })
     }
 
     fn into_weak_references(self) -> Vec<WeakObjectReference> {
@@ -425,25 +995,28 @@ impl WeakCollectionReference {
 }
 
 struct ReferencedPropertiesTable {
-    byte_order: OMByteOrder,
-    path_count: OMPropertyCount,
-    pid_count: u32,
+    _byte_order: OMByteOrder,
+    _path_count: OMPropertyCount,
+    _pid_count: u32,
     pid_paths: Vec<Vec<OMPropertyId>>,
 }
 
 impl ReferencedPropertiesTable {
-    pub fn from_stream<T: Read + Seek>(mut stream: T) -> Self {
-        let byte_order = stream.read_u8().unwrap() as OMByteOrder;
-        assert_eq!(byte_order, 0x4c, "BOM is invalid");
+    pub fn from_stream<T: Read>(mut stream: T, path: &Path) -> Result<Self> {
+        let byte_order = stream.read_u8().at_path(path)? as OMByteOrder;
+        let endian = Endian::from_byte_order(byte_order).ok_or_else(|| AAFError::BadByteOrder {
+            path: path.into(),
+            byte_order,
+        })?;
 
-        let path_count = stream.read_u16::<LittleEndian>().unwrap() as OMPropertyCount;
-        let pid_count = stream.read_u32::<LittleEndian>().unwrap();
+        let path_count = endian.read_u16(&mut stream).at_path(path)? as OMPropertyCount;
+        let pid_count = endian.read_u32(&mut stream).at_path(path)?;
 
         let mut pid_paths: Vec<Vec<OMPropertyId>> = vec![];
         let mut this_path: Vec<OMPropertyId> = vec![];
 
         for _ in 0..pid_count {
-            let this_pid = stream.read_u16::<LittleEndian>().unwrap() as OMPropertyId;
+            let this_pid = endian.read_u16(&mut stream).at_path(path)? as OMPropertyId;
 
             if this_pid == 0x0000u16 {
                 pid_paths.push(this_path);
@@ -453,31 +1026,34 @@ impl ReferencedPropertiesTable {
             }
         }
 
-        assert_eq!(
-            path_count as usize,
-            pid_paths.len(),
-            "Weak ref table has inconsistent length"
-        );
+        if path_count as usize != pid_paths.len() {
+            return Err(AAFError::InconsistentIndex {
+                path: path.into(),
+                reason: "Weak ref table has inconsistent length",
+            });
+        }
 
-        Self {
-            byte_order,
-            path_count,
-            pid_count,
+        Ok(Self {
+            _byte_order: byte_order,
+            _path_count: path_count,
+            _pid_count: pid_count,
             pid_paths,
-        }
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use uuid::Uuid;
+    use crate::aaf::class_ids;
+    use crate::aaf::classes::{Component, ContentStorage, Mob, MobSlot, Segment};
+    use crate::object_iterator::ObjectEntry;
 
     #[test]
     fn test_get_root() {
         let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
         let comp = cfb::open(test_path).unwrap();
-        let f = AAFFile::with_cfb(comp);
+        let f = AAFFile::with_cfb(comp, None).unwrap();
         let _root = f.root_object();
     }
 
@@ -485,15 +1061,15 @@ mod tests {
     fn test_get_properties() {
         let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
         let comp = cfb::open(test_path).unwrap();
-        let mut f = AAFFile::with_cfb(comp);
+        let mut f = AAFFile::with_cfb(comp, None).unwrap();
         let root = f.root_object();
 
-        let props = f.raw_properties(&root);
+        let props = f.raw_properties(&root).unwrap();
 
         assert_eq!(props.len(), 2, "Incorrect number of properties detected");
 
-        let _p1 = f.raw_property_by_pid(&root, 0x01);
-        let _p2 = f.raw_property_by_pid(&root, 0x02);
+        let _p1 = f.raw_property_by_pid(&root, 0x01).unwrap();
+        let _p2 = f.raw_property_by_pid(&root, 0x02).unwrap();
     }
 
     #[test]
@@ -502,7 +1078,7 @@ mod tests {
         let mut f = AAFFile::open(test_path).unwrap();
         let root = f.root_object();
 
-        let all = f.all_property_ids(&root);
+        let all = f.all_property_ids(&root).unwrap();
 
         assert_eq!(all.len(), 2, "Found property ids");
     }
@@ -510,20 +1086,804 @@ mod tests {
     #[test]
     fn test_get_header() {
         let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
-        let mut f = AAFFile::open(test_path).unwrap();
+        let f = AAFFile::open(test_path).unwrap();
 
-        let mut h = f.header();
+        let h = f.header().unwrap();
 
-        assert_eq!(h.byte_order(), 0x4949);
+        assert_eq!(h.byte_order().unwrap(), 0x4949);
 
         assert_eq!(
-            h.last_modified(),
+            h.last_modified().unwrap(),
             TimeStamp {
                 date: (2021, 11, 9),
                 time: (15, 28, 58, 0)
             }
         );
 
-        assert_eq!(h.version(), VersionType { major: 1, minor: 1 })
+        assert_eq!(h.version().unwrap(), VersionType { major: 1, minor: 1 })
+    }
+
+    #[test]
+    fn test_header_navigation() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let h = AAFFile::open(test_path).unwrap().header().unwrap();
+
+        let content = h.content().unwrap();
+        let dictionary = h.dictionary().unwrap();
+        let identifications = h.identification_list().unwrap();
+        assert!(!identifications.is_empty());
+
+        let mobs = content.mobs().unwrap();
+        assert!(!mobs.is_empty());
+        assert!(!dictionary.data_definitions().unwrap().is_empty());
+
+        let identification = h.last_identification().unwrap();
+        assert_eq!(
+            identification.descriptor(),
+            identifications.last().unwrap().descriptor()
+        );
+        assert!(!identification.company_name().unwrap().is_empty());
+        assert!(!identification.product_name().unwrap().is_empty());
+        identification.date().unwrap();
+        if let Some(version) = identification.product_version().unwrap() {
+            assert!(version.release_type <= 6);
+        }
+
+        // Handles opened from descriptors share the header's file.
+        let again: ContentStorage<File> = h.object(content.descriptor().clone()).unwrap();
+        assert!(Rc::ptr_eq(again.file(), h.file()));
+        assert_eq!(again.mobs().unwrap().len(), mobs.len());
+        assert_eq!(h.byte_order().unwrap(), 0x4949);
+    }
+
+    #[test]
+    fn test_mobs() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let content = AAFFile::open(test_path)
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+
+        let mobs = content.mobs().unwrap();
+        assert!(mobs.iter().all(|m| !matches!(m, Mob::Unknown(_))));
+        let composition_mobs = content.composition_mobs().unwrap();
+        assert_eq!(
+            composition_mobs.len()
+                + content.master_mobs().unwrap().len()
+                + content.source_mobs().unwrap().len(),
+            mobs.len()
+        );
+        assert!(!composition_mobs.is_empty());
+
+        let mut mob_ids = vec![];
+        for mob in &mobs {
+            let expected = match mob {
+                Mob::CompositionMob(_) => class_ids::COMPOSITION_MOB,
+                Mob::MasterMob(_) => class_ids::MASTER_MOB,
+                Mob::SourceMob(_) => class_ids::SOURCE_MOB,
+                Mob::Unknown(_) => unreachable!(),
+            };
+            assert_eq!(mob.descriptor().auid, expected);
+            mob_ids.push(mob.mob_id().unwrap());
+            mob.last_modified().unwrap();
+            mob.creation_time().unwrap();
+            mob.usage_code().unwrap();
+            for comment in mob.user_comments().unwrap() {
+                comment.name().unwrap();
+            }
+        }
+        mob_ids.sort_by_key(|id| id.0);
+        mob_ids.dedup();
+        assert_eq!(mob_ids.len(), mobs.len());
+
+        let composition = &composition_mobs[0];
+        assert!(!composition.name().unwrap().unwrap_or_default().is_empty());
+        assert!(!composition.slots().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_class() {
+        let bytes = std::fs::read("testmedia/AAF_Test_1/AAF_Test_1.aaf").unwrap();
+        let open = || {
+            let h = AAFFile::from_reader(Cursor::new(bytes.clone()))
+                .unwrap()
+                .header()
+                .unwrap();
+            let mut desc = h.content().unwrap().mobs().unwrap()[0].descriptor().clone();
+            desc.auid = Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+            (h, desc)
+        };
+
+        // A class in neither the file's dictionary nor the baseline
+        let (h, desc) = open();
+        let mob: Mob<_> = h.object(desc).unwrap();
+        assert!(matches!(mob, Mob::Unknown(_)));
+
+        // A dictionary that cannot be read is an error, not an unknown class
+        let (h, desc) = open();
+        {
+            let mut file = h.file().borrow_mut();
+            let root = file.root_object();
+            let md = file.get_object(&root, AAF_FILE_METADICTIONARY_PID).unwrap();
+            file.f.remove_storage_all(&md.path).unwrap();
+        }
+        assert!(h.object::<Mob<_>>(desc).is_err());
+    }
+
+    #[test]
+    fn test_segments() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let content = AAFFile::open(test_path)
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+
+        let mut source_clips = 0;
+        for mob in content.composition_mobs().unwrap() {
+            for slot in mob.slots().unwrap() {
+                let segment = match &slot {
+                    MobSlot::TimelineMobSlot(slot) => {
+                        slot.edit_rate().unwrap();
+                        slot.origin().unwrap();
+                        slot.segment().unwrap()
+                    }
+                    MobSlot::EventMobSlot(slot) => slot.segment().unwrap(),
+                    MobSlot::StaticMobSlot(slot) => slot.segment().unwrap(),
+                    MobSlot::Unknown(_) => panic!("slot of an unknown class"),
+                };
+                slot.slot_id().unwrap();
+                slot.physical_track_number().unwrap();
+
+                let components = match &segment {
+                    Segment::Sequence(sequence) => sequence.components().unwrap(),
+                    _ => vec![segment.clone().into()],
+                };
+                for component in components {
+                    component.data_definition().unwrap().name().unwrap();
+                    match Segment::try_from(component) {
+                        Ok(Segment::SourceClip(clip)) => {
+                            clip.source_id().unwrap();
+                            source_clips += 1;
+                        }
+                        Ok(Segment::Unknown(_)) => panic!("segment of an unknown class"),
+                        Ok(_) => {}
+                        Err(Component::Transition(transition)) => {
+                            transition.cut_point().unwrap();
+                        }
+                        Err(_) => panic!("component of an unknown class"),
+                    }
+                }
+            }
+        }
+        assert!(source_clips > 0);
+    }
+
+    #[test]
+    fn test_mob_by_id() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let content = AAFFile::open(test_path)
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+
+        for mob in content.mobs().unwrap() {
+            let id = mob.mob_id().unwrap();
+            assert_eq!(id.universal_label()[0..4], [0x06, 0x0a, 0x2b, 0x34]);
+            assert_eq!(id.length(), 0x13);
+
+            let urn = id.to_string();
+            assert!(urn.starts_with("urn:smpte:umid:060a2b34."));
+            assert_eq!(urn.parse::<MobID>().unwrap(), id);
+
+            let found = content.mob_by_id(&id).unwrap().unwrap();
+            assert_eq!(found.descriptor(), mob.descriptor());
+        }
+        assert!(content.mob_by_id(&MobID([0; 32])).unwrap().is_none());
+        // The Mobs set's index is read once, for the first lookup.
+        assert_eq!(content.file().borrow().set_members.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_mob_id() {
+        let urn = "urn:smpte:umid:060a2b34.01010105.01010f10.13000000.a5270c14.e8878000.9acc4f71.bfe13e00";
+        let id: MobID = urn.parse().unwrap();
+        assert_eq!(id.to_string(), urn);
+        assert_eq!(id.instance_number(), 0);
+        assert_eq!(
+            id.material_number().to_string(),
+            "a5270c14-e887-8000-9acc-4f71bfe13e00"
+        );
+        assert_eq!(id.0[16..20], [0x14, 0x0c, 0x27, 0xa5]);
+
+        let bare = urn.trim_start_matches("urn:smpte:umid:").replace('.', "");
+        assert_eq!(bare.parse::<MobID>().unwrap(), id);
+        assert!("urn:smpte:umid:060a2b34".parse::<MobID>().is_err());
+        assert!(urn.replace('a', "g").parse::<MobID>().is_err());
+    }
+
+    #[test]
+    fn test_open_stream_property() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1_Embedded.aaf";
+        let mut f = AAFFile::open(test_path).unwrap();
+        let essence_data = f
+            .objects()
+            .map(|entry| entry.unwrap().object)
+            .find(|object| object.auid == class_ids::ESSENCE_DATA)
+            .unwrap();
+
+        {
+            let mut stream = f
+                .open_stream_property(&essence_data, 0x2702)
+                .unwrap()
+                .unwrap();
+            assert_eq!(stream.path(), essence_data.path.join("Data-2702"));
+            assert_eq!(stream.byte_order(), StreamByteOrder::Unspecified);
+            assert_eq!(stream.len(), 6003);
+
+            let mut data = vec![];
+            assert_eq!(stream.read_to_end(&mut data).unwrap(), 6003);
+            let mut tail = vec![];
+            stream.seek(io::SeekFrom::End(-3)).unwrap();
+            stream.read_to_end(&mut tail).unwrap();
+            assert_eq!(tail, data[6000..]);
+            assert_eq!(stream.seek(io::SeekFrom::Start(7000)).unwrap(), 7000);
+            assert_eq!(stream.read(&mut [0; 16]).unwrap(), 0);
+            assert!(stream.seek(io::SeekFrom::Current(-8000)).is_err());
+        }
+
+        // The MobID is a data property, and Length is not present.
+        assert!(matches!(
+            f.open_stream_property(&essence_data, 0x2701),
+            Err(AAFError::UnexpectedValue { pid: 0x2701, .. })
+        ));
+        assert!(f
+            .open_stream_property(&essence_data, 0x2b01)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_get_typed_value() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let mut f = AAFFile::open(test_path).unwrap();
+        let root = f.root_object();
+        let header = f.get_object(&root, AAF_FILE_HEADER_PID).unwrap();
+
+        assert_eq!(
+            f.get_typed_value(&header, 0x3b01).unwrap(),
+            Some(AAFValue::Int16(0x4949))
+        );
+        assert_eq!(
+            f.get_typed_value(&header, 0x3b05).unwrap(),
+            Some(AAFValue::Record(vec![
+                ("major".to_string(), AAFValue::Int8(1)),
+                ("minor".to_string(), AAFValue::Int8(1)),
+            ]))
+        );
+        assert!(matches!(
+            f.get_typed_value(&header, 0x3b03).unwrap(),
+            Some(AAFValue::Object(_))
+        ));
+        assert!(f.get_typed_value(&header, 0x7fff).unwrap().is_none());
+
+        let idents = f.get_collection(&header, 0x3b06).unwrap();
+        match f.get_typed_value(&idents[0], 0x3c03).unwrap() {
+            Some(AAFValue::Record(members)) => {
+                assert_eq!(
+                    members.last().unwrap().1,
+                    AAFValue::Enumeration {
+                        name: Some("VersionReleased".to_string()),
+                        value: 1
+                    }
+                );
+            }
+            v => panic!("ProductVersion decoded as {:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_property_cache_limit() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let mut f = AAFFile::open(test_path).unwrap();
+        let count = f.walk_properties().count();
+        assert!(f.property_cache_size() > 0);
+
+        f.set_property_cache_limit(4096);
+        assert!(f.property_cache_size() <= 4096);
+        assert_eq!(f.walk_properties().count(), count);
+        assert!(f.property_cache_size() <= 4096);
+
+        f.set_property_cache_limit(0);
+        assert_eq!(f.property_cache_size(), 0);
+        assert_eq!(f.walk_properties().count(), count);
+        assert_eq!(f.property_cache_size(), 0);
+    }
+
+    #[test]
+    fn test_weak_references() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let mut f = AAFFile::open(test_path).unwrap();
+        let mut data_definitions = 0;
+        for entry in f.walk_properties() {
+            let entry = entry.unwrap();
+            // Component::DataDefinition
+            if entry.property_id() == 0x0201 {
+                match entry.value() {
+                    Some(PropertyValue::Reference(target)) => {
+                        assert_eq!(target.auid, class_ids::DATA_DEFINITION);
+                        data_definitions += 1;
+                    }
+                    v => panic!("DataDefinition is {:?}", v),
+                }
+            }
+        }
+        assert!(data_definitions > 0);
+    }
+
+    #[test]
+    fn test_objects() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let mut f = AAFFile::open(test_path).unwrap();
+
+        let owned: usize = f
+            .walk_properties()
+            .map(|e| match e.unwrap().value() {
+                Some(PropertyValue::Single(_)) => 1,
+                Some(PropertyValue::Vector(objs)) | Some(PropertyValue::Set(objs)) => objs.len(),
+                _ => 0,
+            })
+            .sum();
+
+        let depth_first: Vec<ObjectEntry> = f.objects().map(|e| e.unwrap()).collect();
+        assert_eq!(depth_first.len(), owned);
+        assert_eq!(depth_first[0].property_id, AAF_FILE_METADICTIONARY_PID);
+        assert_eq!(depth_first[0].depth, 1);
+        assert_eq!(depth_first[1].depth, 2);
+        assert_eq!(depth_first[1].parent, depth_first[0].object);
+
+        let breadth_first: Vec<ObjectEntry> =
+            f.objects_breadth_first().map(|e| e.unwrap()).collect();
+        assert_eq!(breadth_first.len(), owned);
+        assert_eq!(breadth_first[1].property_id, AAF_FILE_HEADER_PID);
+        assert_eq!(breadth_first[1].class_id(), class_ids::HEADER);
+        assert!(breadth_first.windows(2).all(|w| w[0].depth <= w[1].depth));
+    }
+
+    #[test]
+    fn test_missing_property() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let mut f = AAFFile::open(test_path).unwrap();
+        let root = f.root_object();
+
+        assert!(f.get_value(&root, 0x7fff).unwrap().is_none());
+        assert!(matches!(
+            f.get_object(&root, 0x7fff),
+            Err(AAFError::MissingProperty { pid: 0x7fff, .. })
+        ));
+    }
+
+    #[test]
+    fn test_other_stored_forms() {
+        // AAF_Test_1 with two opaque streams in its root storage.
+        let mut cfb = cfb::CompoundFile::open(Cursor::new(
+            std::fs::read("testmedia/AAF_Test_1/AAF_Test_1.aaf").unwrap(),
+        ))
+        .unwrap();
+        for path in ["/Opaque", "/Padded"] {
+            cfb.create_stream(path).unwrap();
+        }
+        cfb.flush().unwrap();
+        let mut f = AAFFile::from_reader(cfb.into_inner()).unwrap();
+        let root = f.root_object();
+        let raw = |stored_form, raw_value: Vec<u8>, endian| RawProperty {
+            pid: 0xffff,
+            stored_form,
+            raw_value,
+            endian,
+        };
+        let utf16 = |name: &str| -> Vec<u8> {
+            name.encode_utf16()
+                .flat_map(|unit| unit.to_le_bytes())
+                .collect()
+        };
+        let prefixed = |name: &str| [vec![0x55], utf16(name)].concat();
+        // A name following a byte order byte, with a trailing byte that
+        // makes the value an even number of bytes long.
+        let mut padded = prefixed("Padded\0");
+        padded.push(0);
+        for (value, expected) in [
+            (utf16("Opaque\0"), "/Opaque"),
+            (prefixed("Opaque\0"), "/Opaque"),
+            (padded, "/Padded"),
+            (utf16("Missing\0"), "/Missing"),
+        ] {
+            let property = raw(SF_OPAQUE_STREAM, value, Endian::Little);
+            match f.resolve_property_value(&root, &property).unwrap() {
+                PropertyValue::OpaqueStream(path) => assert_eq!(path, Path::new(expected)),
+                other => panic!("unexpected value {:?}", other),
+            }
+        }
+
+        let auid = Uuid::parse_str("01030202-0100-0000-060e-2b3404010101").unwrap();
+        let property = raw(SF_UNIQUE_OBJ_ID, auid.as_bytes().to_vec(), Endian::Little);
+        let value = f.resolve_property_value(&root, &property).unwrap();
+        assert!(matches!(&value, PropertyValue::UniqueObjectId(id) if id == auid.as_bytes()));
+        assert_eq!(
+            decode_data::<Uuid>(Some(value), Endian::Little, &root.path, 0xffff).unwrap(),
+            Some(Uuid::aaf_from(auid.as_bytes()).unwrap())
+        );
+
+        let mut big_endian_key = auid.as_bytes().to_vec();
+        swap_auid(&mut big_endian_key);
+        let property = raw(SF_WEAK_OBJECT_STORED_OBJ_ID, big_endian_key, Endian::Big);
+        let value = f.resolve_property_value(&root, &property).unwrap();
+        assert!(matches!(&value, PropertyValue::StoredObjectId(id) if id == auid.as_bytes()));
+
+        let property = raw(0x00ee, vec![1, 2, 3], Endian::Little);
+        match f.resolve_property_value(&root, &property).unwrap() {
+            PropertyValue::Unknown { stored_form, bytes } => {
+                assert_eq!((stored_form, bytes), (0x00ee, vec![1, 2, 3]))
+            }
+            other => panic!("unexpected value {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_open_error() {
+        let result = AAFFile::open("testmedia/does_not_exist.aaf");
+        assert!(matches!(result, Err(AAFError::Io { .. })));
+    }
+
+    /// The compound file at `path` rewritten with `version`'s sector size,
+    /// with `signature` in its header.
+    fn rewrite_compound_file(
+        path: &str,
+        version: cfb::Version,
+        signature: Option<AAFSignature>,
+    ) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut source = cfb::open(path).unwrap();
+        let entries: Vec<_> = source
+            .walk()
+            .map(|e| (e.path().to_path_buf(), e.is_stream(), *e.clsid()))
+            .collect();
+        let mut copy =
+            cfb::CompoundFile::create_with_version(version, Cursor::new(vec![])).unwrap();
+        for (path, is_stream, clsid) in entries {
+            if is_stream {
+                let mut data = vec![];
+                source
+                    .open_stream(&path)
+                    .unwrap()
+                    .read_to_end(&mut data)
+                    .unwrap();
+                copy.create_stream(&path).unwrap().write_all(&data).unwrap();
+            } else {
+                if path != Path::new("/") {
+                    copy.create_storage(&path).unwrap();
+                }
+                copy.set_storage_clsid(&path, clsid).unwrap();
+            }
+        }
+        copy.flush().unwrap();
+
+        let mut bytes = copy.into_inner().into_inner();
+        bytes[8..24].copy_from_slice(&signature.map(|s| s.to_bytes()).unwrap_or_default());
+        bytes
+    }
+
+    #[test]
+    fn test_file_format() {
+        let f = AAFFile::open("testmedia/AAF_Test_1/AAF_Test_1.aaf").unwrap();
+        assert_eq!(
+            f.format(),
+            FileFormat {
+                sector_size: 512,
+                signature: Some(AAFSignature::Aaf512Binary),
+            }
+        );
+    }
+
+    #[test]
+    fn test_4k_sectors() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1_Embedded.aaf";
+        let bytes =
+            rewrite_compound_file(test_path, cfb::Version::V4, Some(AAFSignature::Aaf4KBinary));
+        assert!(
+            cfb::CompoundFile::open(Cursor::new(&bytes))
+                .unwrap()
+                .version()
+                == cfb::Version::V4
+        );
+
+        let f = AAFFile::from_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(
+            f.format(),
+            FileFormat {
+                sector_size: 4096,
+                signature: Some(AAFSignature::Aaf4KBinary),
+            }
+        );
+        let content = f.header().unwrap().content().unwrap();
+        let original = std::fs::read(test_path).unwrap();
+        let original = AAFFile::from_reader(Cursor::new(original))
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+        let mob_ids = |mobs: Vec<Mob<_>>| -> Vec<MobID> {
+            mobs.iter().map(|m| m.mob_id().unwrap()).collect()
+        };
+        assert_eq!(
+            mob_ids(content.mobs().unwrap()),
+            mob_ids(original.mobs().unwrap())
+        );
+
+        let read_essence = |content: &ContentStorage<_>| {
+            let mut data = vec![];
+            for essence in content.essence_data().unwrap() {
+                essence.data().unwrap().read_to_end(&mut data).unwrap();
+            }
+            data
+        };
+        let essence = read_essence(&content);
+        assert_eq!(essence.len(), 6003);
+        assert_eq!(essence, read_essence(&original));
+    }
+
+    #[test]
+    fn test_not_aaf_file() {
+        let result = AAFFile::from_reader(Cursor::new(b"RIFF\x04\x00\x00\x00WAVE".to_vec()));
+        assert!(matches!(result, Err(AAFError::NotAAFFile { .. })));
+        let result = AAFFile::from_reader(Cursor::new(vec![]));
+        assert!(matches!(result, Err(AAFError::NotAAFFile { .. })));
+
+        let mut other = cfb::CompoundFile::create(Cursor::new(vec![])).unwrap();
+        other.create_stream("/WordDocument").unwrap();
+        other.flush().unwrap();
+        let result = AAFFile::from_reader(other.into_inner());
+        assert!(matches!(result, Err(AAFError::NotAAFFile { .. })));
+
+        // Without a signature, the root class identifies the file.
+        let bytes = rewrite_compound_file(
+            "testmedia/AAF_Test_1/AAF_Test_1.aaf",
+            cfb::Version::V4,
+            None,
+        );
+        let f = AAFFile::from_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(f.format().sector_size, 4096);
+        assert_eq!(f.format().signature, None);
+    }
+
+    #[test]
+    fn test_corrupt_properties_stream() {
+        let path = Path::new("/properties");
+
+        let truncated = [0x4c, 0x40, 0x01, 0x00, 0x01, 0x00];
+        assert!(matches!(
+            RawProperty::from_properties_istream(&truncated, path),
+            Err(AAFError::TruncatedStream { .. })
+        ));
+
+        let bad_bom = [0x00, 0x40, 0x00, 0x00];
+        assert!(matches!(
+            RawProperty::from_properties_istream(&bad_bom, path),
+            Err(AAFError::BadByteOrder {
+                byte_order: 0x00,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_corrupt_vector_index() {
+        // An entry count of 0xffffffff with only one key behind it
+        let mut index = vec![0xff, 0xff, 0xff, 0xff];
+        index.extend([0; 12]);
+        assert!(StrongVectorReferenceIndex::from_istream(&index[..], Endian::Little).is_err());
+    }
+
+    /// A big-endian `properties` stream holding `properties`.
+    fn big_endian_properties(properties: &[(OMPropertyId, OMStoredForm, Vec<u8>)]) -> Vec<u8> {
+        let mut data = vec![0x42, 0x20];
+        data.extend((properties.len() as u16).to_be_bytes());
+        for (pid, stored_form, value) in properties {
+            data.extend(pid.to_be_bytes());
+            data.extend(stored_form.to_be_bytes());
+            data.extend((value.len() as u16).to_be_bytes());
+        }
+        for (_, _, value) in properties {
+            data.extend(value);
+        }
+        data
+    }
+
+    /// `text` as a null-terminated UTF-16BE string.
+    fn utf16_be(text: &str) -> Vec<u8> {
+        text.encode_utf16()
+            .chain([0])
+            .flat_map(|unit| unit.to_be_bytes())
+            .collect()
+    }
+
+    fn be_u32s(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    /// A file written on a big-endian machine: a `Header` with an
+    /// `IdentificationList`, a `Dictionary` with one `DataDefinition` and a
+    /// weak reference, 0xffff, to that definition.
+    fn big_endian_file(picture: Uuid, product_id: Uuid) -> AAFFile<Cursor<Vec<u8>>> {
+        use std::io::Write;
+
+        let mut cfb = cfb::CompoundFile::create(Cursor::new(vec![])).unwrap();
+        let mut streams: Vec<(&str, Vec<u8>)> = vec![];
+
+        let mut referenced = vec![0x42];
+        referenced.extend(1u16.to_be_bytes());
+        referenced.extend(4u32.to_be_bytes());
+        for pid in [0x0002u16, 0x3b04, 0x2605, 0x0000] {
+            referenced.extend(pid.to_be_bytes());
+        }
+        streams.push(("/referenced properties", referenced));
+        streams.push((
+            "/properties",
+            big_endian_properties(&[(0x0002, SF_STRONG_OBJECT_REF, utf16_be("Header"))]),
+        ));
+
+        let mut weak_reference = vec![0x00, 0x00, 0x1b, 0x01, 16];
+        weak_reference.extend(picture.as_bytes());
+        let mut last_modified = 2001i16.to_be_bytes().to_vec();
+        last_modified.extend([7, 4, 12, 30, 0, 0]);
+        streams.push((
+            "/Header/properties",
+            big_endian_properties(&[
+                (0x3b01, SF_DATA, 0x4d4di16.to_be_bytes().to_vec()),
+                (0x3b02, SF_DATA, last_modified),
+                (0x3b04, SF_STRONG_OBJECT_REF, utf16_be("Dictionary")),
+                (0x3b05, SF_DATA, vec![1, 1]),
+                (
+                    0x3b06,
+                    SF_STRONG_OBJECT_REF_VECTOR,
+                    utf16_be("IdentificationList"),
+                ),
+                (0xffff, SF_WEAK_OBJECT_REF, weak_reference),
+            ]),
+        ));
+
+        streams.push(("/Header/IdentificationList index", be_u32s(&[1, 1, 1, 0])));
+        let mut product_version = vec![];
+        for part in [1u16, 2, 3, 4] {
+            product_version.extend(part.to_be_bytes());
+        }
+        product_version.push(5);
+        streams.push((
+            "/Header/IdentificationList{0}/properties",
+            big_endian_properties(&[
+                (0x3c01, SF_DATA, utf16_be("Big Endian Co.")),
+                (0x3c03, SF_DATA, product_version),
+                (0x3c05, SF_DATA, product_id.as_bytes().to_vec()),
+            ]),
+        ));
+
+        streams.push((
+            "/Header/Dictionary/properties",
+            big_endian_properties(&[(
+                0x2605,
+                SF_STRONG_OBJECT_REF_SET,
+                utf16_be("DataDefinitions"),
+            )]),
+        ));
+        let mut set_index = be_u32s(&[1, 1, 1]);
+        set_index.extend(0x1b01u16.to_be_bytes());
+        set_index.push(16);
+        set_index.extend(be_u32s(&[0, 1]));
+        set_index.extend(picture.as_bytes());
+        streams.push(("/Header/Dictionary/DataDefinitions index", set_index));
+        streams.push((
+            "/Header/Dictionary/DataDefinitions{0}/properties",
+            big_endian_properties(&[
+                (0x1b01, SF_DATA, picture.as_bytes().to_vec()),
+                (0x1b02, SF_DATA, utf16_be("Picture")),
+            ]),
+        ));
+
+        for (path, clsid) in [
+            ("/", AAF_ROOT_CLASS),
+            ("/Header", class_ids::HEADER),
+            ("/Header/IdentificationList{0}", class_ids::IDENTIFICATION),
+            ("/Header/Dictionary", class_ids::DICTIONARY),
+            (
+                "/Header/Dictionary/DataDefinitions{0}",
+                class_ids::DATA_DEFINITION,
+            ),
+        ] {
+            if path != "/" {
+                cfb.create_storage(path).unwrap();
+            }
+            cfb.set_storage_clsid(path, clsid).unwrap();
+        }
+        for (path, data) in streams {
+            cfb.create_stream(path).unwrap().write_all(&data).unwrap();
+        }
+        cfb.flush().unwrap();
+
+        let cursor = cfb.into_inner();
+        AAFFile::from_reader(cursor).unwrap()
+    }
+
+    #[test]
+    fn test_big_endian_properties() {
+        let path = Path::new("/properties");
+        let data = big_endian_properties(&[
+            (0x3b01, SF_DATA, vec![0x4d, 0x4d]),
+            (0x0002, SF_STRONG_OBJECT_REF, utf16_be("Header")),
+        ]);
+        let props = RawProperty::from_properties_istream(&data, path).unwrap();
+        assert_eq!(props.len(), 2);
+        assert_eq!((props[0].pid, props[0].stored_form), (0x3b01, SF_DATA));
+        assert_eq!(props[1].endian, Endian::Big);
+        assert_eq!(props[1].raw_string_value(), "Header");
+
+        let mut older = data.clone();
+        older[0] = 0x4d;
+        let props = RawProperty::from_properties_istream(&older, path).unwrap();
+        assert_eq!(props[1].raw_string_value(), "Header");
+    }
+
+    #[test]
+    fn test_big_endian_file() {
+        let picture = Uuid::parse_str("01030202-0100-0000-060e-2b3404010101").unwrap();
+        let product_id = Uuid::parse_str("b3b398a5-1c90-11d4-8053-080036210804").unwrap();
+        let file = big_endian_file(picture, product_id);
+        let header = file.header().unwrap();
+
+        assert_eq!(header.byte_order().unwrap(), 0x4d4d);
+        assert_eq!(
+            header.last_modified().unwrap(),
+            TimeStamp {
+                date: (2001, 7, 4),
+                time: (12, 30, 0, 0)
+            }
+        );
+        let identification = header.last_identification().unwrap();
+        assert_eq!(identification.company_name().unwrap(), "Big Endian Co.");
+        assert_eq!(identification.product_id().unwrap(), product_id);
+        assert_eq!(
+            identification.product_version().unwrap(),
+            Some(ProductVersion {
+                major: 1,
+                minor: 2,
+                tertiary: 3,
+                patch_level: 4,
+                release_type: 5,
+            })
+        );
+
+        let dictionary = header.dictionary().unwrap();
+        let definitions = dictionary.data_definitions().unwrap();
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0].identification().unwrap(), picture);
+        assert_eq!(definitions[0].name().unwrap(), "Picture");
+
+        // Set keys and weak references compare as they would in a
+        // little-endian file.
+        let mut file = header.file().borrow_mut();
+        let referenced = file.get_reference(header.descriptor(), 0xffff).unwrap();
+        assert_eq!(referenced.unwrap().path, definitions[0].descriptor().path);
+        let mut key = picture.as_bytes().to_vec();
+        swap_auid(&mut key);
+        let member = file
+            .get_set_member(dictionary.descriptor(), 0x2605, &key)
+            .unwrap();
+        assert_eq!(member.unwrap().path, definitions[0].descriptor().path);
     }
 }
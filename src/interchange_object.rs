@@ -7,7 +7,8 @@ pub trait InterchangeObject {
     fn path(&self) -> PathBuf;
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct InterchangeObjectDescriptor {
     pub auid: Uuid,
     pub path: PathBuf,
@@ -0,0 +1,289 @@
+/// json.rs
+///
+/// The object graph of a file as a tree of named, decoded values, which
+/// serializes as a nested JSON document.
+///
+/// Objects become maps of their property names to their values, with their
+/// class name under `"$class"`. The objects an object owns are nested in
+/// it, and weak references become `{"$ref": <key>}`, where the key is the
+/// AUID or MobID of the object referred to.
+use std::io::{Read, Seek};
+
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+
+use crate::aaf::meta_dictionary::MetaDictionary;
+use crate::aaf::values::AAFValue;
+use crate::error::{AAFError, Result};
+use crate::file::AAFFile;
+use crate::interchange_object::InterchangeObjectDescriptor;
+use crate::properties::PropertyValue;
+use crate::types::OMPropertyId;
+
+const ROOT_METADICTIONARY_PID: OMPropertyId = 0x0001;
+const ROOT_HEADER_PID: OMPropertyId = 0x0002;
+
+/// An object, with its properties named and decoded, and the objects it
+/// owns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectTree {
+    /// The name of the object's class.
+    pub class: String,
+    /// The object's properties by name, in the order they are stored.
+    pub properties: Vec<(String, TreeValue)>,
+}
+
+/// The value of a property of an `ObjectTree`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeValue {
+    /// A data or stream value.
+    Value(AAFValue),
+    /// The object a strong reference owns.
+    Object(ObjectTree),
+    /// The objects a strong reference vector or set owns.
+    Objects(Vec<ObjectTree>),
+    /// The key of the object a weak reference refers to.
+    Reference(AAFValue),
+    /// The keys of the objects a weak reference vector or set refers to.
+    References(Vec<AAFValue>),
+}
+
+/// A file's two trees: its `MetaDictionary` and its `Header`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileTree {
+    #[serde(rename = "MetaDictionary")]
+    pub meta_dictionary: ObjectTree,
+    #[serde(rename = "Header")]
+    pub header: ObjectTree,
+}
+
+impl Serialize for ObjectTree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.properties.len() + 1))?;
+        map.serialize_entry("$class", &self.class)?;
+        for (name, value) in &self.properties {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for TreeValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            TreeValue::Value(value) => value.serialize(serializer),
+            TreeValue::Object(object) => object.serialize(serializer),
+            TreeValue::Objects(objects) => objects.serialize(serializer),
+            TreeValue::Reference(key) => Reference(key).serialize(serializer),
+            TreeValue::References(keys) => {
+                let mut seq = serializer.serialize_seq(Some(keys.len()))?;
+                for key in keys {
+                    seq.serialize_element(&Reference(key))?;
+                }
+                seq.end()
+            }
+        }
+    }
+}
+
+/// A weak reference, which serializes as `{"$ref": <key>}`.
+struct Reference<'a>(&'a AAFValue);
+
+impl Serialize for Reference<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry("$ref", self.0)?;
+        map.end()
+    }
+}
+
+/// The tree of `object` and the objects it owns.
+pub fn object_tree<F: Read + Seek>(
+    file: &mut AAFFile<F>,
+    object: &InterchangeObjectDescriptor,
+) -> Result<ObjectTree> {
+    let md = file.meta_dictionary()?.clone();
+    read_tree(file, &md, object)
+}
+
+/// The trees of the file's `MetaDictionary` and `Header`.
+pub fn file_tree<F: Read + Seek>(file: &mut AAFFile<F>) -> Result<FileTree> {
+    let md = file.meta_dictionary()?.clone();
+    let root = file.root_object();
+    let mut tree = |pid| match file.get_reference(&root, pid)? {
+        Some(object) => read_tree(file, &md, &object),
+        None => Err(AAFError::MissingProperty {
+            path: root.path.clone(),
+            pid,
+        }),
+    };
+    Ok(FileTree {
+        meta_dictionary: tree(ROOT_METADICTIONARY_PID)?,
+        header: tree(ROOT_HEADER_PID)?,
+    })
+}
+
+/// The file's object graph as a pretty-printed JSON document.
+pub fn to_json<F: Read + Seek>(file: &mut AAFFile<F>) -> Result<String> {
+    let tree = file_tree(file)?;
+    Ok(serde_json::to_string_pretty(&tree).expect("an object tree serializes as JSON"))
+}
+
+fn read_tree<F: Read + Seek>(
+    file: &mut AAFFile<F>,
+    md: &MetaDictionary,
+    object: &InterchangeObjectDescriptor,
+) -> Result<ObjectTree> {
+    let class = match md.resolve_lineage(&object.auid).first() {
+        Some(class) => class.name.clone(),
+        None => {
+            return Err(AAFError::UndefinedClass {
+                path: object.path.clone(),
+                class_id: object.auid,
+            })
+        }
+    };
+
+    let mut properties = vec![];
+    for pid in file.all_property_ids(object)? {
+        let name = match md.resolve_property(&object.auid, pid) {
+            Some(property) => property.name.clone(),
+            None => {
+                return Err(AAFError::UndefinedProperty {
+                    path: object.path.clone(),
+                    pid,
+                })
+            }
+        };
+        let value = match file.get_value(object, pid)? {
+            Some(PropertyValue::Single(member)) => TreeValue::Object(read_tree(file, md, &member)?),
+            Some(PropertyValue::Vector(members)) | Some(PropertyValue::Set(members)) => {
+                TreeValue::Objects(
+                    members
+                        .iter()
+                        .map(|member| read_tree(file, md, member))
+                        .collect::<Result<_>>()?,
+                )
+            }
+            Some(PropertyValue::Reference(target)) => {
+                TreeValue::Reference(reference_key(file, md, &target)?)
+            }
+            Some(PropertyValue::ReferenceVector(targets))
+            | Some(PropertyValue::ReferenceSet(targets)) => TreeValue::References(
+                targets
+                    .iter()
+                    .map(|target| reference_key(file, md, target))
+                    .collect::<Result<_>>()?,
+            ),
+            Some(PropertyValue::StoredObjectId(_)) => match file.get_typed_value(object, pid)? {
+                Some(key) => TreeValue::Reference(key),
+                None => continue,
+            },
+            Some(_) => match file.get_typed_value(object, pid)? {
+                Some(value) => TreeValue::Value(value),
+                None => continue,
+            },
+            None => continue,
+        };
+        properties.push((name, value));
+    }
+
+    Ok(ObjectTree { class, properties })
+}
+
+/// The key of `target`: the value of its class's unique identifier.
+fn reference_key<F: Read + Seek>(
+    file: &mut AAFFile<F>,
+    md: &MetaDictionary,
+    target: &InterchangeObjectDescriptor,
+) -> Result<AAFValue> {
+    let pid = match md.resolve_unique_identifier(&target.auid) {
+        Some(key) => key.local_identification,
+        None => {
+            return Err(AAFError::UnexpectedValue {
+                path: target.path.clone(),
+                pid: 0,
+                expected: "an object with a unique identifier",
+            })
+        }
+    };
+    file.get_typed_value(target, pid)?
+        .ok_or_else(|| AAFError::MissingProperty {
+            path: target.path.clone(),
+            pid,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const TEST_PATH: &str = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+
+    #[test]
+    fn test_to_json() {
+        let mut file = AAFFile::open(TEST_PATH).unwrap();
+        let document: Value = serde_json::from_str(&to_json(&mut file).unwrap()).unwrap();
+
+        let header = &document["Header"];
+        assert_eq!(header["$class"], "Header");
+        assert_eq!(header["Content"]["$class"], "ContentStorage");
+        assert_eq!(document["MetaDictionary"]["$class"], "MetaDictionary");
+
+        let mobs = header["Content"]["Mobs"].as_array().unwrap();
+        let composition = mobs
+            .iter()
+            .find(|m| m["$class"] == "CompositionMob")
+            .unwrap();
+        assert!(composition["MobID"]
+            .as_str()
+            .unwrap()
+            .starts_with("urn:smpte:umid:"));
+        let slot = &composition["Slots"][0];
+        assert!(slot["EditRate"]["numerator"].is_number());
+
+        // Weak references are the keys of the objects they refer to.
+        let segment = &slot["Segment"];
+        let data_definition = segment["DataDefinition"]["$ref"].as_str().unwrap();
+        let definitions = header["Dictionary"]["DataDefinitions"].as_array().unwrap();
+        assert!(definitions
+            .iter()
+            .any(|d| d["Identification"] == data_definition));
+    }
+
+    #[test]
+    fn test_serialize_values() {
+        let value = AAFValue::Record(vec![
+            (
+                "Kind".to_string(),
+                AAFValue::Enumeration {
+                    name: Some("True".to_string()),
+                    value: 1,
+                },
+            ),
+            (
+                "Other".to_string(),
+                AAFValue::Enumeration {
+                    name: None,
+                    value: 7,
+                },
+            ),
+            (
+                "Samples".to_string(),
+                AAFValue::Array(vec![AAFValue::UInt8(1), AAFValue::UInt8(2)]),
+            ),
+        ]);
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!({"Kind": "True", "Other": 7, "Samples": [1, 2]})
+        );
+        assert_eq!(
+            serde_json::to_value(TreeValue::References(vec![AAFValue::String(
+                "a".to_string()
+            )]))
+            .unwrap(),
+            json!([{"$ref": "a"}])
+        );
+    }
+}
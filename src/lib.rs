@@ -0,0 +1,21 @@
+//! Rust implementation of the Advanced Authoring Format
+//!
+pub mod aaf;
+pub mod descriptors;
+pub mod error;
+pub mod essence;
+pub mod file;
+pub mod interchange_object;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod object_iterator;
+pub mod properties;
+pub mod property_cache;
+pub mod property_iterator;
+pub mod relink;
+pub mod session;
+pub mod source_chain;
+pub mod stream;
+pub mod timeline;
+pub mod types;
+pub mod xml;
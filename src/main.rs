@@ -1,36 +1,48 @@
-mod aaf;
-mod file;
-mod interchange_object;
-mod properties;
-mod property_iterator;
-mod object_iterator;
-mod session;
-mod types;
-
-use crate::file::AAFFile;
+use rust_aaf::error::Result;
+use rust_aaf::file::AAFFile;
 use std::io::{Read, Seek};
-use crate::object_iterator::InterchangeObjects;
 
-fn print_object<T>(file: &mut AAFFile<T>)
+/// Print the file's object graph as JSON.
+#[cfg(feature = "serde")]
+fn print_object<T>(file: &mut AAFFile<T>) -> Result<()>
+where
+    T: Read + Seek,
+{
+    println!("{}", rust_aaf::json::to_json(file)?);
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_object<T>(file: &mut AAFFile<T>) -> Result<()>
 where
     T: Read + Seek,
 {
     let i = file.walk_properties();
 
     for entry in i {
-        let indent = std::iter::repeat("  ")
-            .take(entry.depth())
-            .collect::<String>();
+        let entry = entry?;
+        let indent = "  ".repeat(entry.depth());
 
         println!("{}Parent: {:?}", indent, entry.parent().path);
         println!("{}Prop: {}", indent, entry.property_id());
     }
+    Ok(())
 }
 
 fn main() {
-    let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
-    let mut f = AAFFile::open(test_path).expect("error opening file");
-    
-    let root = f.root_object();
-    print_object(&mut f);
+    let test_path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "testmedia/AAF_Test_1/AAF_Test_1.aaf".to_string());
+    let mut f = match AAFFile::open(&test_path) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Error opening {}: {}", test_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = print_object(&mut f) {
+        eprintln!("Error reading {}: {}", test_path, e);
+        std::process::exit(1);
+    }
 }
@@ -1,44 +1,124 @@
-use crate::interchange_object::InterchangeObjectDescriptor;
+use crate::error::{AAFError, Result};
 use crate::file::AAFFile;
+use crate::interchange_object::InterchangeObjectDescriptor;
 use crate::types::OMPropertyId;
+use std::collections::VecDeque;
 use std::io::{Read, Seek};
-
+use uuid::Uuid;
 
 pub struct ObjectEntry {
     pub parent: InterchangeObjectDescriptor,
     pub property_id: OMPropertyId,
     pub object: InterchangeObjectDescriptor,
-    pub depth: usize
+    pub depth: usize,
+}
+
+impl ObjectEntry {
+    /// The AUID of the object's class
+    pub fn class_id(&self) -> Uuid {
+        self.object.auid
+    }
+}
+
+/// The order `InterchangeObjects` visits objects in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraversalOrder {
+    /// Each object is followed by the objects it owns.
+    DepthFirst,
+    /// Objects are visited by their depth in the tree.
+    BreadthFirst,
 }
 
-pub struct InterchangeObjects<'a,F> {
+/// Iterates over every object in the strong reference tree, not including
+/// the root object.
+///
+/// If an object's references can't be read the error is yielded once and
+/// iteration ends.
+pub struct InterchangeObjects<'a, F> {
     file: &'a mut AAFFile<F>,
-    stack : Vec<ObjectEntry>
+    order: TraversalOrder,
+    queue: VecDeque<ObjectEntry>,
+    error: Option<AAFError>,
 }
 
 impl<'a, F> InterchangeObjects<'a, F>
-where F: Read + Seek {
-    pub(crate) fn new(file: &'a mut AAFFile<F>, 
-        root_object: InterchangeObjectDescriptor) -> Self {
-        Self {
+where
+    F: Read + Seek,
+{
+    pub fn new(file: &'a mut AAFFile<F>, root_object: InterchangeObjectDescriptor) -> Self {
+        Self::with_order(file, root_object, TraversalOrder::DepthFirst)
+    }
+
+    pub fn with_order(
+        file: &'a mut AAFFile<F>,
+        root_object: InterchangeObjectDescriptor,
+        order: TraversalOrder,
+    ) -> Self {
+        let mut retval = Self {
             file,
-            stack: vec![]
+            order,
+            queue: VecDeque::new(),
+            error: None,
+        };
+
+        if let Err(e) = retval.fill_stack(&root_object, 0) {
+            retval.fail(e);
+        }
+        retval
+    }
+
+    /// Queue the objects `object` owns.
+    fn fill_stack(&mut self, object: &InterchangeObjectDescriptor, depth: usize) -> Result<()> {
+        let children = self
+            .file
+            .strong_references(object)?
+            .into_iter()
+            .flat_map(|(pid, objs)| objs.into_iter().map(move |o| (pid, o)))
+            .map(|(property_id, child)| ObjectEntry {
+                parent: object.clone(),
+                property_id,
+                object: child,
+                depth: depth + 1,
+            });
+
+        match self.order {
+            TraversalOrder::DepthFirst => {
+                let children: Vec<ObjectEntry> = children.collect();
+                for entry in children.into_iter().rev() {
+                    self.queue.push_back(entry);
+                }
+            }
+            TraversalOrder::BreadthFirst => self.queue.extend(children),
         }
+        Ok(())
     }
 
-    fn fill_stack(&mut self, object: &InterchangeObjectDescriptor) {
-        todo!()
+    fn fail(&mut self, error: AAFError) {
+        self.queue.clear();
+        self.error = Some(error);
     }
 }
 
-impl<'a, F> Iterator for InterchangeObjects<'a, F> {
-    type Item = ObjectEntry;
+impl<F> Iterator for InterchangeObjects<'_, F>
+where
+    F: Read + Seek,
+{
+    type Item = Result<ObjectEntry>;
 
-    fn next(&mut self) -> Option<ObjectEntry> {
-        if let Some(rval) = self.stack.pop() {
-            Some(rval)
-        } else {
-            None
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
         }
+
+        let entry = match self.order {
+            TraversalOrder::DepthFirst => self.queue.pop_back(),
+            TraversalOrder::BreadthFirst => self.queue.pop_front(),
+        };
+        entry.map(|e| {
+            if let Err(err) = self.fill_stack(&e.object, e.depth) {
+                self.fail(err);
+            }
+            Ok(e)
+        })
     }
 }
@@ -1,19 +1,19 @@
-use byteorder::{LittleEndian, ReadBytesExt};
-#[allow(dead_code)]
-#[allow(unused_imports)]
+use byteorder::ReadBytesExt;
 use std::fmt;
 
-use encoding::all::UTF_16LE;
-use encoding::{DecoderTrap, Encoding};
+use encoding::all::{UTF_16BE, UTF_16LE};
+use encoding::{DecoderTrap, EncodingRef};
 
+use crate::error::{AAFError, IoResultExt, Result};
 use crate::interchange_object::InterchangeObjectDescriptor;
 use crate::types::{
-    OMByteOrder, OMPropertyCount, OMPropertyId, OMPropertySize, OMStoredForm, OMVersion,
+    AAFFrom, Endian, OMByteOrder, OMPropertyCount, OMPropertyId, OMPropertySize, OMStoredForm,
+    OMVersion,
 };
 // use crate::file::AAFFile;
 
-use std::io::{Cursor, Read, Seek};
-use std::path::PathBuf;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
 
 pub const SF_DATA: OMStoredForm = 0x0082;
 pub const SF_DATA_STREAM: OMStoredForm = 0x0042;
@@ -23,14 +23,12 @@ pub const SF_STRONG_OBJECT_REF_SET: OMStoredForm = 0x003a;
 pub const SF_WEAK_OBJECT_REF: OMStoredForm = 0x0002;
 pub const SF_WEAK_OBJECT_REF_VECTOR: OMStoredForm = 0x0012;
 pub const SF_WEAK_OBJECT_REF_SET: OMStoredForm = 0x001a;
-
-// Not Yet Implemented
-// pub const SF_WEAK_OBJECT_STORED_OBJ_ID : OMStoredForm = 0x03;
-// pub const SF_UNIQUE_OBJ_ID : OMStoredForm = 0x86;
-// pub const SF_OPAQUE_STREAM : OMStoredForm = 0x40;
+pub const SF_WEAK_OBJECT_STORED_OBJ_ID: OMStoredForm = 0x0003;
+pub const SF_UNIQUE_OBJ_ID: OMStoredForm = 0x0086;
+pub const SF_OPAQUE_STREAM: OMStoredForm = 0x0040;
 
 pub enum PropertyValue {
-    Data(Box<Vec<u8>>),
+    Data(Vec<u8>),
     Stream(PathBuf),
     Single(InterchangeObjectDescriptor),
     Vector(Vec<InterchangeObjectDescriptor>),
@@ -38,39 +36,90 @@ pub enum PropertyValue {
     Reference(InterchangeObjectDescriptor),
     ReferenceVector(Vec<InterchangeObjectDescriptor>),
     ReferenceSet(Vec<InterchangeObjectDescriptor>),
+    /// The path of a stream whose contents have no known type or byte
+    /// order.
+    OpaqueStream(PathBuf),
+    /// The value of a property that uniquely identifies its object, such
+    /// as an AUID or MobID, which decodes as data.
+    UniqueObjectId(Vec<u8>),
+    /// A weak reference stored as the unique id of the object it refers
+    /// to, an AUID or MobID in the byte order of a little-endian file.
+    /// The reference is not resolved, since it does not say which set the
+    /// object is in.
+    StoredObjectId(Vec<u8>),
+    /// A property of a stored form this reader does not recognize, left
+    /// undecoded.
+    Unknown {
+        stored_form: OMStoredForm,
+        bytes: Vec<u8>,
+    },
 }
 
-impl PropertyValue {
-    pub fn unwrap_object(self) -> InterchangeObjectDescriptor {
-        if let Self::Single(o) = self {
-            o
-        } else {
-            panic!("Failed");
-        }
-    }
-
-    pub fn unwrap_vector(self) -> Vec<InterchangeObjectDescriptor> {
-        if let Self::Vector(o) = self {
-            o
-        } else {
-            panic!("Failed");
+/// Decode an optional `PropertyValue::Data`, stored in `endian` byte order,
+/// as a `T`.
+///
+/// `path` and `pid` identify the property being decoded in any error.
+pub(crate) fn decode_data<T: AAFFrom>(
+    value: Option<PropertyValue>,
+    endian: Endian,
+    path: &Path,
+    pid: OMPropertyId,
+) -> Result<Option<T>> {
+    match value {
+        Some(PropertyValue::Data(b)) | Some(PropertyValue::UniqueObjectId(b)) => {
+            match T::aaf_from_endian(&b, endian) {
+                Ok(v) => Ok(Some(v)),
+                Err(_) => Err(AAFError::InvalidProperty {
+                    path: path.into(),
+                    pid,
+                    type_name: std::any::type_name::<T>(),
+                }),
+            }
         }
+        Some(_) => Err(AAFError::UnexpectedValue {
+            path: path.into(),
+            pid,
+            expected: "a data value",
+        }),
+        None => Ok(None),
     }
+}
 
-    pub fn unwrap_set(self) -> Vec<InterchangeObjectDescriptor> {
-        if let Self::Set(o) = self {
-            o
-        } else {
-            panic!("Failed");
-        }
+/// Decode an optional strong or weak object reference.
+pub(crate) fn decode_reference(
+    value: Option<PropertyValue>,
+    path: &Path,
+    pid: OMPropertyId,
+) -> Result<Option<InterchangeObjectDescriptor>> {
+    match value {
+        Some(PropertyValue::Single(obj)) | Some(PropertyValue::Reference(obj)) => Ok(Some(obj)),
+        Some(_) => Err(AAFError::UnexpectedValue {
+            path: path.into(),
+            pid,
+            expected: "an object reference",
+        }),
+        None => Ok(None),
     }
+}
 
-    pub fn unwrap_reference(self) -> InterchangeObjectDescriptor {
-        if let Self::Reference(o) = self {
-            o
-        } else {
-            panic!("Failed");
-        }
+/// Decode an optional strong or weak reference vector or set, an absent
+/// property being an empty collection.
+pub(crate) fn decode_collection(
+    value: Option<PropertyValue>,
+    path: &Path,
+    pid: OMPropertyId,
+) -> Result<Vec<InterchangeObjectDescriptor>> {
+    match value {
+        Some(PropertyValue::Vector(objs))
+        | Some(PropertyValue::Set(objs))
+        | Some(PropertyValue::ReferenceVector(objs))
+        | Some(PropertyValue::ReferenceSet(objs)) => Ok(objs),
+        Some(_) => Err(AAFError::UnexpectedValue {
+            path: path.into(),
+            pid,
+            expected: "an object reference vector or set",
+        }),
+        None => Ok(vec![]),
     }
 }
 
@@ -109,14 +158,35 @@ impl fmt::Debug for PropertyValue {
                 .debug_struct("PropertyValue::ReferenceSet")
                 .field("referenced_objects", v)
                 .finish(),
+            Self::OpaqueStream(v) => f
+                .debug_struct("PropertyValue::OpaqueStream")
+                .field("path", v)
+                .finish(),
+            Self::UniqueObjectId(v) => f
+                .debug_struct("PropertyValue::UniqueObjectId")
+                .field("id", v)
+                .finish(),
+            Self::StoredObjectId(v) => f
+                .debug_struct("PropertyValue::StoredObjectId")
+                .field("id", v)
+                .finish(),
+            Self::Unknown { stored_form, bytes } => f
+                .debug_struct("PropertyValue::Unknown")
+                .field("stored_form", stored_form)
+                .field("bytes", bytes)
+                .finish(),
         }
     }
 }
 
+#[derive(Clone)]
 pub struct RawProperty {
     pub pid: OMPropertyId,
     pub stored_form: OMStoredForm,
-    pub raw_value: Box<Vec<u8>>,
+    pub raw_value: Vec<u8>,
+    /// The byte order of the `properties` stream the property was read
+    /// from.
+    pub endian: Endian,
 }
 
 impl fmt::Debug for RawProperty {
@@ -125,25 +195,31 @@ impl fmt::Debug for RawProperty {
             .field("pid", &self.pid)
             .field("stored_form", &self.stored_form)
             .field("len(raw_value)", &self.raw_value.len())
+            .field("endian", &self.endian)
             .finish()
     }
 }
 
 impl RawProperty {
-    pub fn from_properties_istream(data: &[u8]) -> Vec<RawProperty> {
+    /// Parse the contents of an object's `properties` stream, read from
+    /// `path`.
+    pub fn from_properties_istream(data: &[u8], path: &Path) -> Result<Vec<RawProperty>> {
         let mut stream = Cursor::new(data);
-        let bom = stream.read_u8().unwrap() as OMByteOrder;
-        assert_eq!(bom, 0x4c, "BOM is invalid");
+        let bom = stream.read_u8().at_path(path)? as OMByteOrder;
+        let endian = Endian::from_byte_order(bom).ok_or_else(|| AAFError::BadByteOrder {
+            path: path.into(),
+            byte_order: bom,
+        })?;
 
-        let _version = stream.read_u8().unwrap() as OMVersion;
-        let property_count = stream.read_u16::<LittleEndian>().unwrap() as OMPropertyCount;
+        let _version = stream.read_u8().at_path(path)? as OMVersion;
+        let property_count = endian.read_u16(&mut stream).at_path(path)? as OMPropertyCount;
 
         let mut prop_headers = Vec::with_capacity(property_count as usize);
 
         for _ in 0..property_count {
-            let pid = stream.read_u16::<LittleEndian>().unwrap() as OMPropertyId;
-            let stored_form = stream.read_u16::<LittleEndian>().unwrap() as OMStoredForm;
-            let size = stream.read_u16::<LittleEndian>().unwrap() as OMPropertySize;
+            let pid = endian.read_u16(&mut stream).at_path(path)? as OMPropertyId;
+            let stored_form = endian.read_u16(&mut stream).at_path(path)? as OMStoredForm;
+            let size = endian.read_u16(&mut stream).at_path(path)? as OMPropertySize;
             prop_headers.push((pid, stored_form, size));
         }
 
@@ -151,26 +227,51 @@ impl RawProperty {
 
         for (pid, stored_form, size) in prop_headers {
             let mut value = vec![0; size as usize];
-            stream.read_exact(&mut value).unwrap();
+            stream.read_exact(&mut value).at_path(path)?;
             let prop = RawProperty {
                 pid,
                 stored_form,
-                raw_value: Box::new(value),
+                raw_value: value,
+                endian,
             };
             retval.push(prop);
         }
 
-        retval
+        Ok(retval)
     }
 
     pub fn raw_string_value(&self) -> String {
-        let raw_name = &self.raw_value[0..self.raw_value.len() - 2];
-        UTF_16LE
+        let end = self.raw_value.len().saturating_sub(2);
+        self.decode_name(&self.raw_value[0..end])
+    }
+
+    /// The name of a data stream property's stream, which follows the
+    /// stream's byte order.
+    pub fn raw_stream_name(&self) -> String {
+        let end = self.raw_value.len().saturating_sub(2);
+        self.decode_name(self.raw_value.get(1..end).unwrap_or_default())
+    }
+
+    /// The possible names of an opaque stream property's stream: the name
+    /// as stored, then the name following a byte order byte as a data
+    /// stream property's name does. Opaque streams are written both ways,
+    /// so which one names the stream depends on which stream exists.
+    pub fn raw_opaque_stream_names(&self) -> [String; 2] {
+        [self.raw_string_value(), self.raw_stream_name()]
+    }
+
+    /// Decode a UTF-16 name stored in the property's byte order.
+    fn decode_name(&self, raw_name: &[u8]) -> String {
+        let encoding: EncodingRef = match self.endian {
+            Endian::Little => UTF_16LE,
+            Endian::Big => UTF_16BE,
+        };
+        encoding
             .decode(raw_name, DecoderTrap::Ignore)
-            .expect("Failed to decode object reference by name")
+            .unwrap_or_default()
     }
 
-    pub fn index_path(&self, obj_path: &PathBuf) -> PathBuf {
+    pub fn index_path(&self, obj_path: &Path) -> PathBuf {
         let decoded_name = self.raw_string_value();
         let index_name = format!("{} index", decoded_name);
         obj_path.join(index_name)
@@ -0,0 +1,119 @@
+/// property_cache.rs
+///
+/// Parsed `properties` streams, kept so each object's stream is only read
+/// once.
+use std::collections::{BTreeMap, HashMap};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::properties::RawProperty;
+
+/// The default memory bound of a file's property cache, in bytes.
+pub const DEFAULT_PROPERTY_CACHE_LIMIT: usize = 64 * 1024 * 1024;
+
+struct CacheEntry {
+    properties: Arc<Vec<RawProperty>>,
+    size: usize,
+    last_used: u64,
+}
+
+/// A least-recently-used cache of the properties of objects, keyed by
+/// object path.
+///
+/// The cache holds at most `limit` bytes of properties, by estimate. A
+/// limit of zero disables the cache.
+pub(crate) struct PropertyCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    by_use: BTreeMap<u64, PathBuf>,
+    clock: u64,
+    size: usize,
+    limit: usize,
+}
+
+impl PropertyCache {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            by_use: BTreeMap::new(),
+            clock: 0,
+            size: 0,
+            limit,
+        }
+    }
+
+    /// The properties of the object at `path`, if they are cached.
+    pub(crate) fn get(&mut self, path: &Path) -> Option<Arc<Vec<RawProperty>>> {
+        let entry = self.entries.get_mut(path)?;
+        self.clock += 1;
+        self.by_use.remove(&entry.last_used);
+        self.by_use.insert(self.clock, path.to_path_buf());
+        entry.last_used = self.clock;
+        Some(entry.properties.clone())
+    }
+
+    /// Cache the properties of the object at `path`, evicting the least
+    /// recently used objects to stay within the limit.
+    pub(crate) fn insert(&mut self, path: &Path, properties: Arc<Vec<RawProperty>>) {
+        let size = Self::estimate_size(path, &properties);
+        if size > self.limit {
+            return;
+        }
+        self.remove(path);
+        self.shrink_to(self.limit - size);
+
+        self.clock += 1;
+        self.by_use.insert(self.clock, path.to_path_buf());
+        self.entries.insert(
+            path.to_path_buf(),
+            CacheEntry {
+                properties,
+                size,
+                last_used: self.clock,
+            },
+        );
+        self.size += size;
+    }
+
+    /// Change the memory bound, evicting objects if the cache is now over
+    /// it.
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.shrink_to(limit);
+    }
+
+    /// The estimated size of the cached properties, in bytes.
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Evict the least recently used objects until the cache holds at most
+    /// `size` bytes.
+    fn shrink_to(&mut self, size: usize) {
+        while self.size > size {
+            match self.by_use.pop_first() {
+                Some((_, evicted)) => {
+                    if let Some(entry) = self.entries.remove(&evicted) {
+                        self.size -= entry.size;
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.entries.remove(path) {
+            self.by_use.remove(&entry.last_used);
+            self.size -= entry.size;
+        }
+    }
+
+    fn estimate_size(path: &Path, properties: &[RawProperty]) -> usize {
+        let values: usize = properties
+            .iter()
+            .map(|p| size_of::<RawProperty>() + p.raw_value.len())
+            .sum();
+        size_of::<CacheEntry>() + path.as_os_str().len() * 2 + values
+    }
+}
@@ -1,7 +1,8 @@
+use crate::error::{AAFError, Result};
+use crate::file::AAFFile;
 use crate::interchange_object::InterchangeObjectDescriptor;
-use crate::types::OMPropertyId;
 use crate::properties::PropertyValue;
-use crate::file::AAFFile;
+use crate::types::OMPropertyId;
 
 use std::io::{Read, Seek};
 
@@ -18,7 +19,7 @@ impl AAFEntry {
     }
 
     pub fn property_id(&self) -> OMPropertyId {
-        self.property.clone()
+        self.property
     }
 
     pub fn value(&self) -> &Option<PropertyValue> {
@@ -26,13 +27,19 @@ impl AAFEntry {
     }
 
     pub fn depth(&self) -> usize {
-        self.depth.clone()
+        self.depth
     }
 }
 
+/// Iterates over every property of every object in the strong reference
+/// tree.
+///
+/// If a property can't be read the error is yielded once and iteration
+/// ends.
 pub struct AAFPropertyIterator<'a, F> {
     file: &'a mut AAFFile<F>,
     stack: Vec<AAFEntry>,
+    error: Option<AAFError>,
 }
 
 impl<'a, F> AAFPropertyIterator<'a, F>
@@ -43,15 +50,18 @@ where
         let mut retval = AAFPropertyIterator {
             file,
             stack: vec![],
+            error: None,
         };
 
-        retval.fill_stack(&root_object, 0);
+        if let Err(e) = retval.fill_stack(&root_object, 0) {
+            retval.fail(e);
+        }
         retval
     }
 
-    fn fill_stack(&mut self, parent: &InterchangeObjectDescriptor, depth: usize) {
-        for pid in self.file.all_property_ids(parent) {
-            let pv = self.file.get_value(&parent, pid);
+    fn fill_stack(&mut self, parent: &InterchangeObjectDescriptor, depth: usize) -> Result<()> {
+        for pid in self.file.all_property_ids(parent)? {
+            let pv = self.file.get_value(parent, pid)?;
             self.stack.push(AAFEntry {
                 parent: parent.clone(),
                 property: pid,
@@ -59,36 +69,46 @@ where
                 depth: depth + 1,
             })
         }
+        Ok(())
+    }
+
+    fn fill_children(&mut self, entry: &AAFEntry) -> Result<()> {
+        match &entry.value {
+            Some(PropertyValue::Single(obj)) => {
+                self.fill_stack(obj, entry.depth())?;
+            }
+            Some(PropertyValue::Vector(list)) | Some(PropertyValue::Set(list)) => {
+                for obj in list.iter().rev() {
+                    self.fill_stack(obj, entry.depth())?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, error: AAFError) {
+        self.stack.clear();
+        self.error = Some(error);
     }
 }
 
-impl<'a, F> Iterator for AAFPropertyIterator<'_, F>
+impl<F> Iterator for AAFPropertyIterator<'_, F>
 where
     F: Read + Seek,
 {
-    type Item = AAFEntry;
+    type Item = Result<AAFEntry>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.stack.pop().map(move |e| {
-            match &e.value {
-                Some(PropertyValue::Single(obj)) => {
-                    self.fill_stack(&obj, e.depth());
-                }
-                Some(PropertyValue::Vector(list)) => {
-                    for obj in list.into_iter().rev() {
-                        self.fill_stack(&obj, e.depth());
-                    }
-                }
-                Some(PropertyValue::Set(list)) => {
-                    for obj in list.into_iter().rev() {
-                        self.fill_stack(&obj, e.depth());
-                    }
-                }
-                _ => {}
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+
+        self.stack.pop().map(|e| {
+            if let Err(err) = self.fill_children(&e) {
+                self.fail(err);
             }
-            e
+            Ok(e)
         })
     }
 }
-
-
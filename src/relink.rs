@@ -0,0 +1,550 @@
+/// relink.rs
+///
+/// The media files of `SourceMob`s, found on disk from their locators.
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::aaf::classes::*;
+use crate::descriptors::{AifcSummary, WaveSummary};
+use crate::error::Result;
+use crate::types::*;
+
+/// How much of a media file is read to find its format and length.
+const HEADER_LEN: u64 = 65536;
+/// The file name extensions tried with a mob's name when it has no
+/// locators.
+const MEDIA_EXTENSIONS: [&str; 4] = ["wav", "aif", "aiff", "mxf"];
+
+impl<F: Read + Seek> Locator<F> {
+    /// The URL of a `NetworkLocator` or the text of a `TextLocator`.
+    pub fn location(&self) -> Result<Option<String>> {
+        match self {
+            Self::NetworkLocator(l) => l.url_string().map(Some),
+            Self::TextLocator(l) => l.name().map(Some),
+            Self::Unknown(_) => Ok(None),
+        }
+    }
+
+    /// The local path the locator gives, if it gives one.
+    pub fn path(&self) -> Result<Option<PathBuf>> {
+        Ok(self.location()?.as_deref().and_then(location_path))
+    }
+}
+
+impl<F: Read + Seek> NetworkLocator<F> {
+    /// `URLString` as a local path, if it is a `file:` URL.
+    pub fn path(&self) -> Result<Option<PathBuf>> {
+        Ok(url_to_path(&self.url_string()?))
+    }
+}
+
+/// The path of a `file:` URL, percent-decoded.
+///
+/// URLs of other schemes, and `file:` URLs of hosts other than `localhost`,
+/// give `None`.
+pub fn url_to_path(url: &str) -> Option<PathBuf> {
+    let scheme_len = url.find(':')?;
+    if !url[..scheme_len].eq_ignore_ascii_case("file") {
+        return None;
+    }
+    let rest = &url[scheme_len + 1..];
+    let path = match rest.strip_prefix("//") {
+        Some(authority) => {
+            let host_len = authority.find('/').unwrap_or(authority.len());
+            match &authority[..host_len] {
+                "" | "localhost" => &authority[host_len..],
+                _ => return None,
+            }
+        }
+        None => rest,
+    };
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    percent_decode(path).map(PathBuf::from)
+}
+
+/// `text` with its `%XX` escapes decoded, if it is valid UTF-8 after
+/// decoding.
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 3).filter(|_| bytes[i] == b'%');
+        match escape.and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()) {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// The local path a locator's text gives: the path of a `file:` URL, or
+/// the text itself if it is not a URL.
+fn location_path(location: &str) -> Option<PathBuf> {
+    if location.is_empty() {
+        return None;
+    }
+    match location.contains("://") || location.starts_with("file:") {
+        true => url_to_path(location),
+        false => Some(PathBuf::from(location)),
+    }
+}
+
+/// How a `Relinker` found a media file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelinkMethod {
+    /// At the path a locator gives.
+    Locator,
+    /// At the path a locator gives, rewritten by one of the relinker's
+    /// path rewrites.
+    Rewrite,
+    /// In the directory of the AAF file or one of its subdirectories.
+    Sibling,
+    /// Under one of the relinker's search paths.
+    SearchPath,
+}
+
+/// A media file found for a `SourceMob`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relink {
+    pub mob_id: MobID,
+    pub path: PathBuf,
+    pub method: RelinkMethod,
+}
+
+/// What a media file is expected to hold, from its essence descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ExpectedMedia {
+    channels: Option<u16>,
+    /// The length, in `sample_rate` units.
+    length: LengthType,
+    sample_rate: Rational,
+}
+
+/// The format and length of an audio file, from its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MediaHeader {
+    channels: u16,
+    sample_rate: u32,
+    frames: u64,
+}
+
+/// Finds the media files of `SourceMob`s that are described by
+/// `FileDescriptor`s.
+///
+/// For each mob the relinker tries, in order:
+///
+/// * the paths its locators give,
+/// * those paths rewritten by the relinker's path rewrites,
+/// * the directory of the AAF file and that directory's subdirectories,
+/// * the relinker's search paths and all of their subdirectories.
+///
+/// The last two look for a file with the file name of one of the locator
+/// paths, ignoring ASCII case, or with the mob's name and a media file
+/// extension if it has no locators.
+#[derive(Debug, Clone, Default)]
+pub struct Relinker {
+    rewrites: Vec<(PathBuf, PathBuf)>,
+    aaf_dir: Option<PathBuf>,
+    search_paths: Vec<PathBuf>,
+    verify: bool,
+}
+
+impl Relinker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A relinker that also looks next to the AAF file at `aaf_path`.
+    pub fn for_aaf<P: AsRef<Path>>(aaf_path: P) -> Self {
+        Self {
+            aaf_dir: aaf_path.as_ref().parent().map(Path::to_path_buf),
+            ..Self::default()
+        }
+    }
+
+    /// Replace the prefix `from` of locator paths with `to`, for instance
+    /// `/Volumes/Media` with `/mnt/media`.
+    pub fn add_rewrite<P: Into<PathBuf>, Q: Into<PathBuf>>(&mut self, from: P, to: Q) -> &mut Self {
+        self.rewrites.push((from.into(), to.into()));
+        self
+    }
+
+    /// Look for media files under `dir`, after the other places.
+    pub fn add_search_path<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.search_paths.push(dir.into());
+        self
+    }
+
+    /// Whether a WAVE or AIFF file must also match the channel count and
+    /// duration of the mob's essence descriptor. Files in other formats
+    /// are matched by name alone.
+    pub fn set_verify(&mut self, verify: bool) -> &mut Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Find the media file of `mob`.
+    ///
+    /// Returns `Ok(None)` if the mob is not described by a
+    /// `FileDescriptor` or no file is found. Directories that cannot be
+    /// read are skipped.
+    pub fn relink<F: Read + Seek>(&self, mob: &SourceMob<F>) -> Result<Option<Relink>> {
+        let descriptor = match FileDescriptor::try_from(mob.essence_description()?) {
+            Ok(descriptor) => descriptor,
+            Err(_) => return Ok(None),
+        };
+        let expected = match self.verify {
+            true => Some(expected_media(&descriptor)?),
+            false => None,
+        };
+        let matches = |path: &Path| path.is_file() && verify_media(path, expected.as_ref());
+        let relink = |path: PathBuf, method| {
+            Ok(Some(Relink {
+                mob_id: mob.mob_id()?,
+                path,
+                method,
+            }))
+        };
+
+        let mut paths = vec![];
+        for locator in descriptor.locator()? {
+            if let Some(path) = locator.path()? {
+                paths.push(path);
+            }
+        }
+        if let Some(path) = paths.iter().find(|p| matches(p)) {
+            return relink(path.clone(), RelinkMethod::Locator);
+        }
+        for path in &paths {
+            for (from, to) in &self.rewrites {
+                if let Ok(rest) = path.strip_prefix(from) {
+                    let rewritten = to.join(rest);
+                    if matches(&rewritten) {
+                        return relink(rewritten, RelinkMethod::Rewrite);
+                    }
+                }
+            }
+        }
+
+        let mut names: Vec<String> = paths
+            .iter()
+            .filter_map(|p| Some(p.file_name()?.to_str()?.to_owned()))
+            .collect();
+        if names.is_empty() {
+            if let Some(name) = mob.name()? {
+                names.extend(
+                    MEDIA_EXTENSIONS
+                        .iter()
+                        .map(|ext| format!("{}.{}", name, ext)),
+                );
+            }
+        }
+        if let Some(dir) = &self.aaf_dir {
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            if let Some(path) = find_file(dir, &names, 1, &matches, &mut HashSet::new()) {
+                return relink(path, RelinkMethod::Sibling);
+            }
+        }
+        for dir in &self.search_paths {
+            let found = find_file(dir, &names, usize::MAX, &matches, &mut HashSet::new());
+            if let Some(path) = found {
+                return relink(path, RelinkMethod::SearchPath);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Find the media files of the `SourceMob`s in `content` that are
+    /// described by `FileDescriptor`s, with `None` for those not found.
+    pub fn relink_content<F: Read + Seek>(
+        &self,
+        content: &ContentStorage<F>,
+    ) -> Result<Vec<(SourceMob<F>, Option<Relink>)>> {
+        let mut relinks = vec![];
+        for mob in content.mobs()? {
+            if let Mob::SourceMob(mob) = mob {
+                if FileDescriptor::try_from(mob.essence_description()?).is_ok() {
+                    let relink = self.relink(&mob)?;
+                    relinks.push((mob, relink));
+                }
+            }
+        }
+        Ok(relinks)
+    }
+}
+
+/// A file in `dir`, or in its subdirectories down to `depth` levels,
+/// named one of `names` ignoring ASCII case, that `matches`.
+///
+/// Symbolic links to directories are followed, but no directory is
+/// searched twice, so links that loop back are not followed around the
+/// loop. `visited` are the directories already searched.
+fn find_file(
+    dir: &Path,
+    names: &[String],
+    depth: usize,
+    matches: &dyn Fn(&Path) -> bool,
+    visited: &mut HashSet<PathBuf>,
+) -> Option<PathBuf> {
+    if !visited.insert(fs::canonicalize(dir).ok()?) {
+        return None;
+    }
+    let mut entries: Vec<_> = fs::read_dir(dir).ok()?.filter_map(|e| e.ok()).collect();
+    entries.sort_by_key(|e| e.file_name());
+    let mut subdirs = vec![];
+    for entry in entries {
+        let path = entry.path();
+        if path.is_dir() {
+            subdirs.push(path);
+            continue;
+        }
+        let name = entry.file_name();
+        let is_named = name
+            .to_str()
+            .is_some_and(|name| names.iter().any(|n| n.eq_ignore_ascii_case(name)));
+        if is_named && matches(&path) {
+            return Some(path);
+        }
+    }
+    if depth == 0 {
+        return None;
+    }
+    subdirs
+        .iter()
+        .find_map(|subdir| find_file(subdir, names, depth - 1, matches, visited))
+}
+
+/// The channel count and length of `descriptor`'s essence.
+fn expected_media<F: Read + Seek>(descriptor: &FileDescriptor<F>) -> Result<ExpectedMedia> {
+    let channels = match descriptor {
+        FileDescriptor::SoundDescriptor(d) => Some(d.channels()?),
+        FileDescriptor::PCMDescriptor(d) => Some(d.channels()?),
+        FileDescriptor::AES3PCMDescriptor(d) => Some(d.channels()?),
+        FileDescriptor::WAVEDescriptor(d) => Some(d.wave_summary()?.channels as AAFUInt32),
+        FileDescriptor::AIFCDescriptor(d) => Some(d.aifc_summary()?.channels as AAFUInt32),
+        _ => None,
+    };
+    Ok(ExpectedMedia {
+        channels: channels.and_then(|c| u16::try_from(c).ok()),
+        length: descriptor.length()?,
+        sample_rate: descriptor.sample_rate()?,
+    })
+}
+
+/// The format and length of the WAVE or AIFF file at `path`.
+fn read_media_header(path: &Path) -> Option<MediaHeader> {
+    let mut header = vec![];
+    File::open(path)
+        .ok()?
+        .take(HEADER_LEN)
+        .read_to_end(&mut header)
+        .ok()?;
+    if let Some(summary) = WaveSummary::parse(&header) {
+        let block_align = summary.block_align.max(1) as u64;
+        return Some(MediaHeader {
+            channels: summary.channels,
+            sample_rate: summary.sample_rate,
+            frames: summary.data_len? as u64 / block_align,
+        });
+    }
+    let summary = AifcSummary::parse(&header)?;
+    Some(MediaHeader {
+        channels: summary.channels,
+        sample_rate: summary.sample_rate,
+        frames: summary.frames as u64,
+    })
+}
+
+/// Whether the file at `path` holds `expected`, to within one edit unit of
+/// its length. Files that are not WAVE or AIFF files match anything.
+fn verify_media(path: &Path, expected: Option<&ExpectedMedia>) -> bool {
+    let expected = match expected {
+        Some(expected) => expected,
+        None => return true,
+    };
+    let header = match read_media_header(path) {
+        Some(header) => header,
+        None => return true,
+    };
+    if expected.channels.is_some_and(|c| c != header.channels) {
+        return false;
+    }
+    let rate = expected.sample_rate;
+    if rate.numerator <= 0 || rate.denominator <= 0 || header.sample_rate == 0 {
+        return true;
+    }
+    let length = header.frames as i128 * rate.numerator as i128
+        / (header.sample_rate as i128 * rate.denominator as i128);
+    (length - expected.length as i128).abs() <= 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::AAFFile;
+
+    const TEST_PATH: &str = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+    const MEDIA_DIR: &str = "testmedia/AAF_Test_1/AAF_Test_1 Audio Files";
+    const MEDIA_NAME: &str = "AAF_Test_1vqzsXQBCsfggBVXX.wav";
+
+    fn content() -> ContentStorage<File> {
+        AAFFile::open(TEST_PATH)
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_url_to_path() {
+        assert_eq!(
+            url_to_path("file:///Volumes/Media%20Drive/a%2Fb.wav"),
+            Some(PathBuf::from("/Volumes/Media Drive/a/b.wav"))
+        );
+        assert_eq!(
+            url_to_path("file://localhost/tmp/x.wav"),
+            Some(PathBuf::from("/tmp/x.wav"))
+        );
+        assert_eq!(
+            url_to_path("FILE:/tmp/%E2%82%AC.wav"),
+            Some(PathBuf::from("/tmp/€.wav"))
+        );
+        assert_eq!(url_to_path("file://server/share/x.wav"), None);
+        assert_eq!(url_to_path("http://example.com/x.wav"), None);
+        assert_eq!(
+            location_path("/tmp/x.wav"),
+            Some(PathBuf::from("/tmp/x.wav"))
+        );
+    }
+
+    #[test]
+    fn test_locator_paths() {
+        let mut paths = vec![];
+        for mob in content().mobs().unwrap() {
+            if let Mob::SourceMob(mob) = mob {
+                for locator in mob.essence_description().unwrap().locator().unwrap() {
+                    paths.push(locator.path().unwrap().unwrap());
+                }
+            }
+        }
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].starts_with("/Macintosh HD/Users"));
+        assert!(paths[0].ends_with(Path::new("AAF_Test_1 Audio Files").join(MEDIA_NAME)));
+    }
+
+    #[test]
+    fn test_relink() {
+        let content = content();
+        let media = Path::new(MEDIA_DIR).join(MEDIA_NAME);
+
+        let mut relinker = Relinker::for_aaf(TEST_PATH);
+        relinker.set_verify(true);
+        let relinks = relinker.relink_content(&content).unwrap();
+        assert_eq!(relinks.len(), 1);
+        let (mob, relink) = &relinks[0];
+        let relink = relink.as_ref().unwrap();
+        assert_eq!(relink.mob_id, mob.mob_id().unwrap());
+        assert_eq!(relink.path, media);
+        assert_eq!(relink.method, RelinkMethod::Sibling);
+
+        let mut relinker = Relinker::new();
+        relinker.add_rewrite("/Macintosh HD/Users/jamiehardt/Desktop", "testmedia");
+        let relink = relinker.relink(mob).unwrap().unwrap();
+        assert_eq!(relink.method, RelinkMethod::Rewrite);
+        assert_eq!(relink.path, media);
+
+        let mut relinker = Relinker::new();
+        relinker.add_search_path("testmedia").set_verify(true);
+        let relink = relinker.relink(mob).unwrap().unwrap();
+        assert_eq!(relink.method, RelinkMethod::SearchPath);
+        assert_eq!(relink.path, media);
+
+        assert_eq!(Relinker::new().relink(mob).unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_search_symlink_loops() {
+        use std::os::unix::fs::symlink;
+
+        // Each directory links back to the root and to the other, and the
+        // media is only in the last directory searched.
+        let root = std::env::temp_dir().join(format!("rust-aaf-relink-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["a", "b", "c"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for (dir, target) in [("a", "b"), ("b", "a")] {
+            symlink(&root, root.join(dir).join("root")).unwrap();
+            symlink(root.join(target), root.join(dir).join("other")).unwrap();
+        }
+        fs::copy(
+            Path::new(MEDIA_DIR).join(MEDIA_NAME),
+            root.join("c").join(MEDIA_NAME),
+        )
+        .unwrap();
+
+        let mob = content()
+            .source_mobs()
+            .unwrap()
+            .into_iter()
+            .find(|m| FileDescriptor::try_from(m.essence_description().unwrap()).is_ok())
+            .unwrap();
+        let mut relinker = Relinker::new();
+        relinker.add_search_path(&root).set_verify(true);
+        let relink = relinker.relink(&mob).unwrap().unwrap();
+        assert_eq!(relink.method, RelinkMethod::SearchPath);
+        assert_eq!(relink.path, root.join("c").join(MEDIA_NAME));
+
+        let mut visited = HashSet::new();
+        let names = ["missing.wav".to_string()];
+        assert_eq!(
+            find_file(&root, &names, usize::MAX, &|_| true, &mut visited),
+            None
+        );
+        assert_eq!(visited.len(), 4);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_verify_media() {
+        let media = Path::new(MEDIA_DIR).join(MEDIA_NAME);
+        let header = read_media_header(&media).unwrap();
+        assert_eq!((header.channels, header.sample_rate), (1, 48000));
+        assert_eq!(header.frames, 2001);
+
+        let expected = ExpectedMedia {
+            channels: Some(1),
+            length: 2001,
+            sample_rate: Rational {
+                numerator: 48000,
+                denominator: 1,
+            },
+        };
+        assert!(verify_media(&media, Some(&expected)));
+        let stereo = ExpectedMedia {
+            channels: Some(2),
+            ..expected
+        };
+        assert!(!verify_media(&media, Some(&stereo)));
+        let longer = ExpectedMedia {
+            length: 4000,
+            ..expected
+        };
+        assert!(!verify_media(&media, Some(&longer)));
+    }
+}
@@ -1,9 +1,408 @@
-// use anymap::AnyMap;
+/// session.rs
+///
+/// The objects of an AAF file, materialised once and addressed by id.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anymap::AnyMap;
+use uuid::Uuid;
+
+use crate::aaf::classes::{AAFObject, Header};
+use crate::aaf::values::AAFValue;
+use crate::error::{AAFError, Result};
+use crate::file::{AAFFile, SharedAAFFile};
+use crate::interchange_object::InterchangeObjectDescriptor;
+use crate::types::*;
+
+const AAF_FILE_HEADER_PID: OMPropertyId = 0x0002;
+
+/// The id of an object in a `Session`.
+///
+/// Ids are stable for the life of the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(usize);
+
+/// An object that refers to another through a weak reference property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeakReferrer {
+    pub object: ObjectId,
+    pub property_id: OMPropertyId,
+}
+
+/// A weak reference property whose references could not be resolved, and
+/// why.
+#[derive(Debug)]
+pub struct UnresolvedReference {
+    pub referrer: WeakReferrer,
+    pub error: AAFError,
+}
+
+/// Every weak reference in the file, by the object it refers to.
+#[derive(Default)]
+struct WeakReferenceIndex {
+    referrers: HashMap<ObjectId, Vec<WeakReferrer>>,
+    unresolved: Vec<UnresolvedReference>,
+}
+
+struct SessionObject {
+    descriptor: InterchangeObjectDescriptor,
+    owner: Option<(ObjectId, OMPropertyId)>,
+    children: Vec<ObjectId>,
+}
 
 /// A Session maintains the AAF object graph
-pub struct Session {
+///
+/// Opening a session reads the tree of strong references once, giving every
+/// `InterchangeObject` in the file an `ObjectId`. Objects decoded from the
+/// file can be cached in the session by type.
+pub struct Session<F> {
+    file: SharedAAFFile<F>,
+    objects: Vec<SessionObject>,
+    by_path: HashMap<PathBuf, ObjectId>,
+    decoded: AnyMap,
+    weak_references: Option<WeakReferenceIndex>,
+}
+
+impl Session<File> {
+    /// Open the AAF file at `path` in a new session.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Session<File>> {
+        Self::new(AAFFile::open(path)?)
+    }
 }
 
-impl Session {
+impl<F: Read + Seek> Session<F> {
+    /// A new session reading `file`.
+    pub fn new(mut file: AAFFile<F>) -> Result<Self> {
+        let root = file.root_object();
+        let mut objects = vec![SessionObject {
+            descriptor: root.clone(),
+            owner: None,
+            children: vec![],
+        }];
+        let mut by_path = HashMap::from([(root.path, ObjectId(0))]);
+
+        for entry in file.objects() {
+            let entry = entry?;
+            let id = ObjectId(objects.len());
+            let parent = by_path[&entry.parent.path];
+            objects[parent.0].children.push(id);
+            by_path.insert(entry.object.path.clone(), id);
+            objects.push(SessionObject {
+                descriptor: entry.object,
+                owner: Some((parent, entry.property_id)),
+                children: vec![],
+            });
+        }
+
+        Ok(Self {
+            file: Rc::new(RefCell::new(file)),
+            objects,
+            by_path,
+            decoded: AnyMap::new(),
+            weak_references: None,
+        })
+    }
+
+    /// The file the session reads.
+    pub fn file(&self) -> &SharedAAFFile<F> {
+        &self.file
+    }
+
+    /// The number of objects in the session, including the root.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// The ids of every object, parents before their children.
+    pub fn object_ids(&self) -> impl Iterator<Item = ObjectId> {
+        (0..self.objects.len()).map(ObjectId)
+    }
+
+    /// The root object, which owns the `MetaDictionary` and the `Header`.
+    pub fn root(&self) -> ObjectId {
+        ObjectId(0)
+    }
+
+    /// The id of the `Header`.
+    pub fn header_id(&self) -> Result<ObjectId> {
+        self.children(self.root())
+            .iter()
+            .copied()
+            .find(|id| self.owning_property(*id) == Some(AAF_FILE_HEADER_PID))
+            .ok_or_else(|| AAFError::MissingProperty {
+                path: self.descriptor(self.root()).path.clone(),
+                pid: AAF_FILE_HEADER_PID,
+            })
+    }
+
+    /// A handle on the `Header`.
+    pub fn header(&self) -> Result<Header<F>> {
+        self.handle(self.header_id()?)
+    }
+
+    /// A typed handle on an object.
+    pub fn handle<T: AAFObject<F>>(&self, id: ObjectId) -> Result<T> {
+        T::make(self.file.clone(), self.descriptor(id).clone())
+    }
+
+    pub fn descriptor(&self, id: ObjectId) -> &InterchangeObjectDescriptor {
+        &self.objects[id.0].descriptor
+    }
+
+    /// The AUID of an object's class.
+    pub fn class_id(&self, id: ObjectId) -> Uuid {
+        self.objects[id.0].descriptor.auid
+    }
+
+    /// The id of the object at `desc`'s path, if it is in the session.
+    pub fn id(&self, desc: &InterchangeObjectDescriptor) -> Option<ObjectId> {
+        self.by_path.get(&desc.path).copied()
+    }
+
+    /// The object that owns `id` through a strong reference, or `None` for
+    /// the root.
+    pub fn parent(&self, id: ObjectId) -> Option<ObjectId> {
+        self.objects[id.0].owner.map(|(parent, _)| parent)
+    }
+
+    /// The property of its parent that owns `id`, or `None` for the root.
+    pub fn owning_property(&self, id: ObjectId) -> Option<OMPropertyId> {
+        self.objects[id.0].owner.map(|(_, pid)| pid)
+    }
+
+    /// The objects `id` owns through its strong references, in property
+    /// order.
+    pub fn children(&self, id: ObjectId) -> &[ObjectId] {
+        &self.objects[id.0].children
+    }
+
+    /// The ids of the objects whose class is `class_id` or derives from it.
+    pub fn instances_of(&self, class_id: &Uuid) -> Result<Vec<ObjectId>> {
+        let mut file = self.file.borrow_mut();
+        let md = file.meta_dictionary()?;
+        let mut is_kind_of = HashMap::new();
+        Ok(self
+            .object_ids()
+            .filter(|id| {
+                let class = self.class_id(*id);
+                *is_kind_of.entry(class).or_insert_with(|| {
+                    md.resolve_lineage(&class)
+                        .iter()
+                        .any(|c| c.identification == *class_id)
+                })
+            })
+            .collect())
+    }
+
+    /// The value of a property of an object, decoded according to its
+    /// type definition.
+    ///
+    /// Returns `Ok(None)` if the object does not have the property.
+    pub fn value(&self, id: ObjectId, pid: OMPropertyId) -> Result<Option<AAFValue>> {
+        self.file
+            .borrow_mut()
+            .get_typed_value(self.descriptor(id), pid)
+    }
+
+    /// The objects that refer to `id` through a weak reference, and the
+    /// properties they refer to it through.
+    ///
+    /// Every weak reference in the file is resolved the first time this is
+    /// asked. References that cannot be resolved are left out, and are
+    /// listed by `unresolved_weak_references`.
+    pub fn weak_referrers(&mut self, id: ObjectId) -> Result<&[WeakReferrer]> {
+        Ok(self
+            .weak_reference_index()?
+            .referrers
+            .get(&id)
+            .map_or(&[], |referrers| referrers.as_slice()))
+    }
+
+    /// The weak reference properties whose references could not be
+    /// resolved, such as those that refer to an object the file does not
+    /// have.
+    pub fn unresolved_weak_references(&mut self) -> Result<&[UnresolvedReference]> {
+        Ok(&self.weak_reference_index()?.unresolved)
+    }
+
+    fn weak_reference_index(&mut self) -> Result<&WeakReferenceIndex> {
+        if self.weak_references.is_none() {
+            let mut index = WeakReferenceIndex::default();
+            let mut file = self.file.borrow_mut();
+            for object in self.object_ids() {
+                for (property_id, targets) in file.weak_references(self.descriptor(object))? {
+                    let referrer = WeakReferrer {
+                        object,
+                        property_id,
+                    };
+                    let targets = match targets {
+                        Ok(targets) => targets,
+                        Err(error) => {
+                            index
+                                .unresolved
+                                .push(UnresolvedReference { referrer, error });
+                            continue;
+                        }
+                    };
+                    for target in targets.iter().filter_map(|t| self.id(t)) {
+                        index.referrers.entry(target).or_default().push(referrer);
+                    }
+                }
+            }
+            drop(file);
+            self.weak_references = Some(index);
+        }
+
+        Ok(self
+            .weak_references
+            .as_ref()
+            .expect("the index was just built"))
+    }
+
+    /// A `T` decoded from the object `id`, cached in the session.
+    ///
+    /// `decode` is called the first time a `T` is asked for an object.
+    pub fn decoded<T: 'static>(
+        &mut self,
+        id: ObjectId,
+        decode: impl FnOnce(&Self, ObjectId) -> Result<T>,
+    ) -> Result<&T> {
+        let cached = self
+            .decoded
+            .get::<HashMap<ObjectId, T>>()
+            .is_some_and(|objects| objects.contains_key(&id));
+        if !cached {
+            let value = decode(self, id)?;
+            self.decoded
+                .entry::<HashMap<ObjectId, T>>()
+                .or_insert_with(HashMap::new)
+                .insert(id, value);
+        }
+        Ok(&self.decoded.get::<HashMap<ObjectId, T>>().unwrap()[&id])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aaf::class_ids;
+    use crate::aaf::classes::ContentStorage;
+
+    #[test]
+    fn test_session() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let session = Session::open(test_path).unwrap();
+
+        let count = AAFFile::open(test_path).unwrap().objects().count();
+        assert_eq!(session.len(), count + 1);
+
+        let header = session.header_id().unwrap();
+        assert_eq!(session.parent(header), Some(session.root()));
+        assert_eq!(session.class_id(header), class_ids::HEADER);
+        assert_eq!(session.header().unwrap().byte_order().unwrap(), 0x4949);
+
+        for id in session.object_ids().skip(1) {
+            let parent = session.parent(id).unwrap();
+            assert!(parent < id);
+            assert!(session.children(parent).contains(&id));
+            assert_eq!(session.id(session.descriptor(id)), Some(id));
+        }
+
+        let storage = session.instances_of(&class_ids::CONTENT_STORAGE).unwrap();
+        assert_eq!(storage.len(), 1);
+        let content: ContentStorage<File> = session.handle(storage[0]).unwrap();
+        let mobs = session.instances_of(&class_ids::MOB).unwrap();
+        assert_eq!(content.mobs().unwrap().len(), mobs.len());
+    }
+
+    #[test]
+    fn test_weak_referrers() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let mut session = Session::open(test_path).unwrap();
+
+        let data_definitions = session.instances_of(&class_ids::DATA_DEFINITION).unwrap();
+        let referrers: usize = data_definitions
+            .iter()
+            .map(|id| session.weak_referrers(*id).unwrap().len())
+            .sum();
+        assert!(referrers > 0);
+
+        let components = session.instances_of(&class_ids::COMPONENT).unwrap();
+        for component in components {
+            let data_definition = match session.value(component, 0x0201).unwrap() {
+                Some(AAFValue::Object(obj)) => session.id(&obj).unwrap(),
+                _ => panic!("component without a data definition"),
+            };
+            assert!(session
+                .weak_referrers(data_definition)
+                .unwrap()
+                .contains(&WeakReferrer {
+                    object: component,
+                    property_id: 0x0201,
+                }));
+        }
+    }
+
+    #[test]
+    fn test_unresolved_weak_references() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        // One component refers to a data definition the file does not have.
+        let xml = AAFFile::open(test_path)
+            .unwrap()
+            .to_xml_string()
+            .unwrap()
+            .replacen(
+                "<DataDefinition>DataDef_LegacySound</DataDefinition>",
+                "<DataDefinition>{01020304-0506-0708-090a-0b0c0d0e0f10}</DataDefinition>",
+                1,
+            );
+        let file = AAFFile::from_xml(&xml, Path::new(".")).unwrap();
+        let mut session = Session::new(file).unwrap();
+
+        let unresolved = session.unresolved_weak_references().unwrap();
+        assert_eq!(unresolved.len(), 1);
+        let referrer = unresolved[0].referrer;
+        assert_eq!(referrer.property_id, 0x0201);
+        assert!(session
+            .instances_of(&class_ids::COMPONENT)
+            .unwrap()
+            .contains(&referrer.object));
+
+        // The other references are indexed.
+        let data_definitions = session.instances_of(&class_ids::DATA_DEFINITION).unwrap();
+        let referrers: Vec<WeakReferrer> = data_definitions
+            .iter()
+            .flat_map(|id| session.weak_referrers(*id).unwrap().to_vec())
+            .collect();
+        assert!(!referrers.is_empty());
+        assert!(!referrers.contains(&referrer));
+    }
+
+    #[test]
+    fn test_decoded() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+        let mut session = Session::open(test_path).unwrap();
+        let header = session.header_id().unwrap();
+        let expected = session.children(header).len();
 
+        let mut calls = 0;
+        for _ in 0..2 {
+            let children = session
+                .decoded(header, |s, id| {
+                    calls += 1;
+                    Ok(s.children(id).len())
+                })
+                .unwrap();
+            assert_eq!(*children, expected);
+        }
+        assert_eq!(calls, 1);
+    }
 }
@@ -0,0 +1,514 @@
+/// source_chain.rs
+///
+/// Source references followed from mob to mob down to the original
+/// sources.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{Read, Seek};
+
+use uuid::Uuid;
+
+use crate::aaf::classes::*;
+use crate::error::{AAFError, Result};
+use crate::timeline::{flatten_slot, TimelineEvent, TimelineEventKind};
+use crate::types::*;
+
+/// A position in a mob slot that a source reference chain passes through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLink {
+    pub mob_id: MobID,
+    /// The class of the mob.
+    pub class_id: Uuid,
+    pub slot_id: AAFUInt32,
+    pub edit_rate: Rational,
+    /// The position in the slot's segment, in the slot's edit units.
+    pub position: PositionType,
+}
+
+/// A timecode on a tape or other physical source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TapeTimecode {
+    /// The frame count since midnight.
+    pub frame: PositionType,
+    pub fps: AAFUInt16,
+    pub drop: bool,
+}
+
+impl fmt::Display for TapeTimecode {
+    /// `HH:MM:SS:FF`, or `HH:MM:SS;FF` for drop-frame timecode.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fps = self.fps.max(1) as i64;
+        let mut frame = self.frame;
+        if self.drop && self.fps % 30 == 0 {
+            // Two frame numbers (four at 60fps) are skipped at the start of
+            // every minute except every tenth.
+            let dropped = 2 * fps / 30;
+            let per_minute = fps * 60 - dropped;
+            let per_ten_minutes = fps * 600 - dropped * 9;
+            let tens = frame.div_euclid(per_ten_minutes);
+            let rest = frame.rem_euclid(per_ten_minutes);
+            frame += dropped * 9 * tens;
+            if rest > dropped {
+                frame += dropped * ((rest - dropped) / per_minute);
+            }
+        }
+        let (ff, seconds) = (frame.rem_euclid(fps), frame.div_euclid(fps));
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            seconds / 3600 % 24,
+            seconds / 60 % 60,
+            seconds % 60,
+            if self.drop { ';' } else { ':' },
+            ff
+        )
+    }
+}
+
+/// Where a source reference leads.
+pub struct SourceChain<F> {
+    /// The slots the reference passes through, in the order they were
+    /// followed.
+    pub links: Vec<SourceLink>,
+    /// The first `SourceMob` described by a `FileDescriptor`.
+    pub file_mob: Option<SourceMob<F>>,
+    /// The essence descriptor of `file_mob`.
+    pub essence_descriptor: Option<EssenceDescriptor<F>>,
+    /// The locators of `essence_descriptor`.
+    pub locators: Vec<Locator<F>>,
+    /// The timecode at the referenced position of the first `SourceMob`
+    /// describing a tape or other physical source that has a timecode
+    /// slot.
+    pub tape_timecode: Option<TapeTimecode>,
+    /// The mob the chain ends at a reference to, if it is not in the file.
+    pub unresolved: Option<MobID>,
+}
+
+/// Follows source references between the mobs of a `ContentStorage`.
+pub struct SourceResolver<F> {
+    mobs: HashMap<MobID, Mob<F>>,
+}
+
+impl<F: Read + Seek> SourceResolver<F> {
+    pub fn new(content: &ContentStorage<F>) -> Result<Self> {
+        let mut mobs = HashMap::new();
+        for mob in content.mobs()? {
+            mobs.insert(mob.mob_id()?, mob);
+        }
+        Ok(Self { mobs })
+    }
+
+    /// The mob with MobID `id`.
+    pub fn mob(&self, id: &MobID) -> Option<&Mob<F>> {
+        self.mobs.get(id)
+    }
+
+    /// Follow the reference of `clip`, `offset` edit units into the clip.
+    ///
+    /// `edit_rate` is the edit rate of the slot the clip is in.
+    pub fn resolve_clip(
+        &self,
+        clip: &SourceClip<F>,
+        offset: LengthType,
+        edit_rate: Rational,
+    ) -> Result<SourceChain<F>> {
+        let position = add(clip.start_time()?.unwrap_or(0), offset)?;
+        self.resolve(
+            clip.source_id()?,
+            clip.source_mob_slot_id()?,
+            position,
+            edit_rate,
+        )
+    }
+
+    /// Follow a reference to `position` in slot `slot_id` of mob
+    /// `source_id`, where `position` is in `edit_rate` units.
+    ///
+    /// A `source_id` of `None` or zero refers to no further source.
+    pub fn resolve(
+        &self,
+        source_id: Option<MobID>,
+        slot_id: AAFUInt32,
+        position: PositionType,
+        edit_rate: Rational,
+    ) -> Result<SourceChain<F>> {
+        let mut chain = SourceChain {
+            links: vec![],
+            file_mob: None,
+            essence_descriptor: None,
+            locators: vec![],
+            tape_timecode: None,
+            unresolved: None,
+        };
+        let mut visited = HashSet::new();
+        let mut next = source_id.map(|id| (id, slot_id, position, edit_rate));
+
+        while let Some((mob_id, slot_id, position, edit_rate)) = next.take() {
+            if mob_id == MobID([0; 32]) || !visited.insert((mob_id, slot_id)) {
+                break;
+            }
+            let mob = match self.mobs.get(&mob_id) {
+                Some(mob) => mob,
+                None => {
+                    chain.unresolved = Some(mob_id);
+                    break;
+                }
+            };
+            let slot = match find_slot(mob, slot_id)? {
+                Some(slot) => slot,
+                None => break,
+            };
+            let timeline = flatten_slot(&slot)?;
+            let slot_rate = timeline.edit_rate.unwrap_or(edit_rate);
+            let position = add(convert(position, edit_rate, slot_rate)?, timeline.origin)?;
+            chain.links.push(SourceLink {
+                mob_id,
+                class_id: mob.descriptor().auid,
+                slot_id,
+                edit_rate: slot_rate,
+                position,
+            });
+
+            if let Mob::SourceMob(source) = mob {
+                self.describe(&mut chain, source, position, slot_rate)?;
+            }
+
+            let event = timeline
+                .events
+                .iter()
+                .find(|e| within(position, e.record_in, e.length));
+            if let Some(TimelineEvent {
+                record_in,
+                kind:
+                    TimelineEventKind::SourceClip {
+                        source_id: Some(id),
+                        source_mob_slot_id,
+                        start_time,
+                    },
+                ..
+            }) = event
+            {
+                let offset = sub(position, *record_in)?;
+                next = Some((
+                    *id,
+                    *source_mob_slot_id,
+                    add(*start_time, offset)?,
+                    slot_rate,
+                ));
+            }
+        }
+        Ok(chain)
+    }
+
+    /// Record what `source` describes in `chain`.
+    fn describe(
+        &self,
+        chain: &mut SourceChain<F>,
+        source: &SourceMob<F>,
+        position: PositionType,
+        edit_rate: Rational,
+    ) -> Result<()> {
+        let descriptor = source.essence_description()?;
+        match FileDescriptor::try_from(descriptor) {
+            Ok(file) => {
+                if chain.file_mob.is_none() {
+                    let descriptor = EssenceDescriptor::from(file);
+                    chain.locators = descriptor.locator()?;
+                    chain.essence_descriptor = Some(descriptor);
+                    chain.file_mob = Some(source.clone());
+                }
+            }
+            Err(_) => {
+                if chain.tape_timecode.is_none() {
+                    chain.tape_timecode = timecode_at(source, position, edit_rate)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The slot of `mob` with SlotID `slot_id`.
+fn find_slot<F: Read + Seek>(mob: &Mob<F>, slot_id: AAFUInt32) -> Result<Option<MobSlot<F>>> {
+    for slot in mob.slots()? {
+        if slot.slot_id()? == slot_id {
+            return Ok(Some(slot));
+        }
+    }
+    Ok(None)
+}
+
+/// `position` in `from` edit units, in `to` edit units, rounded down.
+fn convert(position: PositionType, from: Rational, to: Rational) -> Result<PositionType> {
+    if from == to || from.numerator == 0 || to.denominator == 0 {
+        return Ok(position);
+    }
+    let numerator = position as i128 * to.numerator as i128 * from.denominator as i128;
+    let denominator = from.numerator as i128 * to.denominator as i128;
+    PositionType::try_from(numerator.div_euclid(denominator)).map_err(|_| out_of_range())
+}
+
+/// Whether `position` is in the `length` edit units from `start`.
+fn within(position: PositionType, start: PositionType, length: LengthType) -> bool {
+    start <= position
+        && position
+            .checked_sub(start)
+            .is_some_and(|offset| offset < length)
+}
+
+fn add(a: PositionType, b: PositionType) -> Result<PositionType> {
+    a.checked_add(b).ok_or_else(out_of_range)
+}
+
+fn sub(a: PositionType, b: PositionType) -> Result<PositionType> {
+    a.checked_sub(b).ok_or_else(out_of_range)
+}
+
+/// A position or length, from a corrupt file, that overflows a
+/// `PositionType` when followed.
+fn out_of_range() -> AAFError {
+    AAFError::InvalidValue {
+        type_name: "PositionType",
+        len: std::mem::size_of::<PositionType>(),
+    }
+}
+
+/// The timecode at `position` of the first `Timecode` segment in a slot of
+/// `mob`, where `position` is in `edit_rate` units.
+fn timecode_at<F: Read + Seek>(
+    mob: &SourceMob<F>,
+    position: PositionType,
+    edit_rate: Rational,
+) -> Result<Option<TapeTimecode>> {
+    for slot in mob.slots()? {
+        let (slot_rate, origin) = match &slot {
+            MobSlot::TimelineMobSlot(s) => (s.edit_rate()?, s.origin()?),
+            _ => continue,
+        };
+        let position = add(convert(position, edit_rate, slot_rate)?, origin)?;
+        let segments = match slot.segment()? {
+            Segment::Sequence(sequence) => sequence.components()?,
+            segment => vec![segment.into()],
+        };
+        let mut start = 0;
+        for component in segments {
+            let length = component.length()?.unwrap_or(0);
+            if let Component::Timecode(timecode) = component {
+                if within(position, start, length) {
+                    return Ok(Some(TapeTimecode {
+                        frame: add(timecode.start()?, sub(position, start)?)?,
+                        fps: timecode.fps()?,
+                        drop: timecode.drop()?,
+                    }));
+                }
+            }
+            start = add(start, length)?;
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aaf::class_ids;
+    use crate::file::AAFFile;
+    use crate::timeline::flatten_mob;
+    use std::path::Path;
+
+    #[test]
+    fn test_timecode_display() {
+        let tc = |frame, fps, drop| TapeTimecode { frame, fps, drop }.to_string();
+        assert_eq!(tc(0, 25, false), "00:00:00:00");
+        assert_eq!(tc(90_000, 25, false), "01:00:00:00");
+        assert_eq!(tc(1799, 30, true), "00:00:59;29");
+        assert_eq!(tc(1800, 30, true), "00:01:00;02");
+        assert_eq!(tc(17982, 30, true), "00:10:00;00");
+        assert_eq!(tc(107_892, 30, true), "01:00:00;00");
+    }
+
+    const TEST_PATH: &str = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+    const MASTER_MOB_ID: &str =
+        "urn:smpte:umid:060a2b34.01010105.01010f10.13000000.140c27a5.87e80080.9acc4f71.bfe13e00";
+    const FILE_MOB_ID: &str =
+        "urn:smpte:umid:060a2b34.01010101.01010f00.13000000.ddb0443a.af8b107d.060e2b34.7f7f2a80";
+    const TAPE_MOB_ID: &str =
+        "urn:smpte:umid:060a2b34.01010105.01010f10.13000000.200f27a5.87e80080.ee3d4f71.bfe13e00";
+
+    fn link(mob_id: &str, class_id: Uuid, edit_rate: i32, position: PositionType) -> SourceLink {
+        SourceLink {
+            mob_id: mob_id.parse().unwrap(),
+            class_id,
+            slot_id: 3,
+            edit_rate: Rational {
+                numerator: edit_rate,
+                denominator: 1,
+            },
+            position,
+        }
+    }
+
+    /// Apply `edits` in order to the slots of the mob `mob_id` in `xml`.
+    fn edit_slots(xml: &mut String, mob_id: &str, edits: &[(&str, &str)]) {
+        let end = xml.find(&format!("<MobID>{}</MobID>", mob_id)).unwrap();
+        let start = xml[..end].rfind("<Slots>").unwrap();
+        let mut slots = xml[start..end].to_string();
+        for (from, to) in edits {
+            assert!(slots.contains(from), "{} not in {}", from, mob_id);
+            slots = slots.replacen(from, to, 1);
+        }
+        xml.replace_range(start..end, &slots);
+    }
+
+    #[test]
+    fn test_resolve_source_clips() {
+        let content = AAFFile::open(TEST_PATH)
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+        let resolver = SourceResolver::new(&content).unwrap();
+
+        let mobs = content.composition_mobs().unwrap();
+        let timelines = flatten_mob(&mobs[0]).unwrap();
+        let clip = &timelines[0].events[0];
+        let (source_id, slot_id, start_time) = match clip.kind {
+            TimelineEventKind::SourceClip {
+                source_id,
+                source_mob_slot_id,
+                start_time,
+            } => (source_id, source_mob_slot_id, start_time),
+            _ => panic!("{:?} is not a source clip", clip),
+        };
+        let chain = resolver
+            .resolve(
+                source_id,
+                slot_id,
+                start_time,
+                timelines[0].edit_rate.unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            chain.links,
+            vec![
+                link(MASTER_MOB_ID, class_ids::MASTER_MOB, 24, 0),
+                link(FILE_MOB_ID, class_ids::SOURCE_MOB, 24, 0),
+                link(TAPE_MOB_ID, class_ids::SOURCE_MOB, 24, 0),
+            ]
+        );
+        assert_eq!(
+            chain.file_mob.unwrap().mob_id().unwrap(),
+            FILE_MOB_ID.parse().unwrap()
+        );
+        let descriptor = chain.essence_descriptor.unwrap();
+        assert_eq!(descriptor.descriptor().auid, class_ids::WAVE_DESCRIPTOR);
+        assert_eq!(chain.locators.len(), 1);
+        assert_eq!(
+            chain.tape_timecode,
+            Some(TapeTimecode {
+                frame: 0,
+                fps: 24,
+                drop: false,
+            })
+        );
+        assert_eq!(chain.unresolved, None);
+    }
+
+    #[test]
+    fn test_resolve_across_edit_rates() {
+        // AAF_Test_1, edited so that each mob in the chain starts its clip
+        // part way into the next, the file and tape have their own edit
+        // rates, and the tape's timecode starts at 01:00:00:00.
+        let mut xml = AAFFile::open(TEST_PATH).unwrap().to_xml_string().unwrap();
+        edit_slots(
+            &mut xml,
+            MASTER_MOB_ID,
+            &[
+                ("<StartTime>0</StartTime>", "<StartTime>100</StartTime>"),
+                ("<Length>1</Length>", "<Length>48</Length>"),
+            ],
+        );
+        edit_slots(
+            &mut xml,
+            FILE_MOB_ID,
+            &[
+                ("<EditRate>24/1</EditRate>", "<EditRate>48000/1</EditRate>"),
+                ("<StartTime>0</StartTime>", "<StartTime>480000</StartTime>"),
+                ("<Length>1</Length>", "<Length>2400000</Length>"),
+            ],
+        );
+        edit_slots(
+            &mut xml,
+            TAPE_MOB_ID,
+            &[
+                ("<Origin>0</Origin>", "<Origin>10</Origin>"),
+                ("<EditRate>24/1</EditRate>", "<EditRate>25/1</EditRate>"),
+                ("<EditRate>24/1</EditRate>", "<EditRate>25/1</EditRate>"),
+                ("<FPS>24</FPS>", "<FPS>25</FPS>"),
+                ("<Start>0</Start>", "<Start>90000</Start>"),
+            ],
+        );
+        let content = AAFFile::from_xml(&xml, Path::new("."))
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+        let resolver = SourceResolver::new(&content).unwrap();
+
+        let rate = Rational {
+            numerator: 24,
+            denominator: 1,
+        };
+        let chain = resolver
+            .resolve(Some(MASTER_MOB_ID.parse().unwrap()), 3, 12, rate)
+            .unwrap();
+
+        // 112 frames at 24fps is 224000 samples at 48kHz, and 224000 +
+        // 480000 samples is 366 frames at 25fps, rounded down.
+        assert_eq!(
+            chain.links,
+            vec![
+                link(MASTER_MOB_ID, class_ids::MASTER_MOB, 24, 12),
+                link(FILE_MOB_ID, class_ids::SOURCE_MOB, 48000, 224000),
+                link(TAPE_MOB_ID, class_ids::SOURCE_MOB, 25, 376),
+            ]
+        );
+        let timecode = chain.tape_timecode.unwrap();
+        assert_eq!(
+            timecode,
+            TapeTimecode {
+                frame: 90376,
+                fps: 25,
+                drop: false,
+            }
+        );
+        assert_eq!(timecode.to_string(), "01:00:15:01");
+    }
+
+    #[test]
+    fn test_resolve_overflow() {
+        let mut xml = AAFFile::open(TEST_PATH).unwrap().to_xml_string().unwrap();
+        edit_slots(
+            &mut xml,
+            MASTER_MOB_ID,
+            &[("<Origin>0</Origin>", "<Origin>9223372036854775807</Origin>")],
+        );
+        let content = AAFFile::from_xml(&xml, Path::new("."))
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+        let resolver = SourceResolver::new(&content).unwrap();
+
+        let rate = Rational {
+            numerator: 24,
+            denominator: 1,
+        };
+        let result = resolver.resolve(Some(MASTER_MOB_ID.parse().unwrap()), 3, 1, rate);
+        assert!(matches!(result, Err(AAFError::InvalidValue { .. })));
+    }
+}
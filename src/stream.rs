@@ -0,0 +1,278 @@
+/// stream.rs
+///
+/// The streams of stream properties, read without copying them into memory.
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::file::SharedAAFFile;
+use crate::interchange_object::InterchangeObjectDescriptor;
+use crate::types::*;
+
+/// How much of a `PropertyStream` is read from the file at once.
+const READ_AHEAD: usize = 64 * 1024;
+
+/// The byte order of the data in a stream property's stream, from the byte
+/// that prefixes the stream's name in the property value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamByteOrder {
+    Little,
+    Big,
+    Unspecified,
+}
+
+impl StreamByteOrder {
+    /// The byte order a stream property value's prefix byte stands for.
+    pub(crate) fn from_prefix(byte_order: OMByteOrder) -> Option<Self> {
+        match byte_order {
+            0x4c => Some(Self::Little),
+            0x42 => Some(Self::Big),
+            0x55 => Some(Self::Unspecified),
+            _ => None,
+        }
+    }
+}
+
+/// The new position of a seek from `position` in a stream of length `len`.
+fn seek_position(pos: SeekFrom, position: u64, len: u64) -> io::Result<u64> {
+    let position = match pos {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::End(offset) => len.checked_add_signed(offset),
+        SeekFrom::Current(offset) => position.checked_add_signed(offset),
+    };
+    position
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position"))
+}
+
+/// A stream property's stream, read through a shared file.
+///
+/// The stream is read from the file as it is needed, through a
+/// `PropertyStreamReader`, so it may be kept alongside the handles of other
+/// objects in the file. Small reads are served from a buffer read ahead of
+/// them, so that the stream is not looked up in the file for each.
+pub struct PropertyStream<F> {
+    file: SharedAAFFile<F>,
+    path: PathBuf,
+    byte_order: StreamByteOrder,
+    len: u64,
+    position: u64,
+    /// Bytes of the stream starting `buffer_start` bytes in.
+    buffer: Vec<u8>,
+    buffer_start: u64,
+}
+
+impl<F: Read + Seek> PropertyStream<F> {
+    /// The stream of property `pid` of `object`.
+    ///
+    /// Returns `Ok(None)` if the object does not have the property.
+    pub(crate) fn open(
+        file: SharedAAFFile<F>,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+    ) -> Result<Option<Self>> {
+        let (path, byte_order) = match file.borrow_mut().stream_property(object, pid)? {
+            Some(stream) => stream,
+            None => return Ok(None),
+        };
+        let len = file
+            .borrow_mut()
+            .open_stream(path.clone(), byte_order)?
+            .len();
+        Ok(Some(Self {
+            file,
+            path,
+            byte_order,
+            len,
+            position: 0,
+            buffer: vec![],
+            buffer_start: 0,
+        }))
+    }
+
+    /// The stream's path in the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The byte order of the data in the stream.
+    pub fn byte_order(&self) -> StreamByteOrder {
+        self.byte_order
+    }
+
+    /// The length of the stream, in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Read from the file into `buf`, from the position on.
+    fn read_file(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut file = self.file.borrow_mut();
+        let mut reader = file
+            .open_stream(self.path.clone(), self.byte_order)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
+        reader.seek(SeekFrom::Start(self.position))?;
+        reader.read(buf)
+    }
+
+    /// The buffered bytes from the position on.
+    fn buffered(&self) -> &[u8] {
+        match self.position.checked_sub(self.buffer_start) {
+            Some(offset) if offset < self.buffer.len() as u64 => &self.buffer[offset as usize..],
+            _ => &[],
+        }
+    }
+}
+
+impl<F: Read + Seek> Read for PropertyStream<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.buffered().is_empty() {
+            if buf.len() >= READ_AHEAD {
+                let count = self.read_file(buf)?;
+                self.position += count as u64;
+                return Ok(count);
+            }
+            let mut buffer = vec![0; READ_AHEAD];
+            let count = self.read_file(&mut buffer)?;
+            buffer.truncate(count);
+            self.buffer = buffer;
+            self.buffer_start = self.position;
+        }
+        let buffered = self.buffered();
+        let count = buffered.len().min(buf.len());
+        buf[..count].copy_from_slice(&buffered[..count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<F: Read + Seek> Seek for PropertyStream<F> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(pos, self.position, self.len)?;
+        Ok(self.position)
+    }
+}
+
+/// A stream property's stream, borrowed from an `AAFFile`.
+///
+/// Reads end at the end of the stream. Seeking past the end is allowed, as
+/// it is for a `File`, and reads from there return no bytes.
+pub struct PropertyStreamReader<'a, F> {
+    stream: cfb::Stream<'a, F>,
+    path: PathBuf,
+    byte_order: StreamByteOrder,
+    position: u64,
+}
+
+impl<'a, F: Read + Seek> PropertyStreamReader<'a, F> {
+    pub(crate) fn new(
+        stream: cfb::Stream<'a, F>,
+        path: PathBuf,
+        byte_order: StreamByteOrder,
+    ) -> Self {
+        Self {
+            stream,
+            path,
+            byte_order,
+            position: 0,
+        }
+    }
+
+    /// The stream's path in the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The byte order of the data in the stream.
+    pub fn byte_order(&self) -> StreamByteOrder {
+        self.byte_order
+    }
+
+    /// The length of the stream, in bytes.
+    pub fn len(&self) -> u64 {
+        self.stream.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stream.len() == 0
+    }
+}
+
+impl<F: Read + Seek> Read for PropertyStreamReader<'_, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.stream.len() {
+            return Ok(0);
+        }
+        self.stream.seek(SeekFrom::Start(self.position))?;
+        let count = self.stream.read(buf)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<F: Read + Seek> Seek for PropertyStreamReader<'_, F> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(pos, self.position, self.stream.len())?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aaf::classes::AAFObject;
+    use crate::file::AAFFile;
+
+    #[test]
+    fn test_property_stream() {
+        let test_path = "testmedia/AAF_Test_1/AAF_Test_1_Embedded.aaf";
+        let content = AAFFile::open(test_path)
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+        let essence = content.essence_data().unwrap().remove(0);
+        let expected = {
+            let mut file = essence.file().borrow_mut();
+            let mut stream = file
+                .open_stream_property(essence.descriptor(), 0x2702)
+                .unwrap()
+                .unwrap();
+            let mut data = vec![];
+            stream.read_to_end(&mut data).unwrap();
+            data
+        };
+
+        let mut stream = essence.data().unwrap();
+        assert_eq!(stream.len(), expected.len() as u64);
+        assert_eq!(stream.byte_order(), StreamByteOrder::Unspecified);
+
+        // Small reads, from the buffer and across its end.
+        let mut data = vec![];
+        let mut chunk = [0; 7];
+        loop {
+            let count = stream.read(&mut chunk).unwrap();
+            if count == 0 {
+                break;
+            }
+            data.extend_from_slice(&chunk[..count]);
+        }
+        assert_eq!(data, expected);
+
+        // Seeks back into the buffer, and reads as large as the buffer.
+        stream.seek(SeekFrom::Start(100)).unwrap();
+        let mut head = [0; 4];
+        stream.read_exact(&mut head).unwrap();
+        assert_eq!(head, expected[100..104]);
+        stream.seek(SeekFrom::Current(-2)).unwrap();
+        let mut rest = vec![0; READ_AHEAD];
+        let count = stream.read(&mut rest).unwrap();
+        assert_eq!(rest[..count], expected[102..102 + count]);
+        assert_eq!(stream.seek(SeekFrom::End(0)).unwrap(), stream.len());
+        assert_eq!(stream.read(&mut chunk).unwrap(), 0);
+    }
+}
@@ -0,0 +1,373 @@
+/// timeline.rs
+///
+/// Mob slots flattened into lists of timed events.
+use std::io::{Read, Seek};
+
+use uuid::Uuid;
+
+use crate::aaf::classes::*;
+use crate::error::{AAFError, Result};
+use crate::types::*;
+
+/// What plays during a `TimelineEvent`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimelineEventKind {
+    /// Material from a slot of another mob, starting at `start_time` in that
+    /// slot's edit units.
+    SourceClip {
+        source_id: Option<MobID>,
+        source_mob_slot_id: AAFUInt32,
+        start_time: PositionType,
+    },
+    Filler,
+    /// A transition between the events before and after it, which it
+    /// overlaps.
+    Transition {
+        cut_point: PositionType,
+        operation: Uuid,
+    },
+    /// A segment this module does not flatten, such as a `Timecode`, by its
+    /// class.
+    Other {
+        class_id: Uuid,
+    },
+}
+
+/// An event on a flattened slot, in the slot's edit units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineEvent {
+    pub record_in: PositionType,
+    pub length: LengthType,
+    pub kind: TimelineEventKind,
+    /// The `OperationDefinition`s of the `OperationGroup`s the event is an
+    /// input of, outermost first.
+    pub effects: Vec<Uuid>,
+}
+
+impl TimelineEvent {
+    /// The position the event ends at, exclusive, or the greatest position
+    /// if a corrupt length puts it beyond that.
+    pub fn record_out(&self) -> PositionType {
+        self.record_in.saturating_add(self.length)
+    }
+}
+
+/// A mob slot flattened into a list of events.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline {
+    pub slot_id: AAFUInt32,
+    pub slot_name: Option<String>,
+    /// The slot's edit rate, or `None` for a `StaticMobSlot`.
+    pub edit_rate: Option<Rational>,
+    pub origin: PositionType,
+    pub events: Vec<TimelineEvent>,
+}
+
+/// Flatten each of the slots of a `CompositionMob`.
+pub fn flatten_mob<F: Read + Seek>(mob: &CompositionMob<F>) -> Result<Vec<Timeline>> {
+    mob.slots()?.iter().map(flatten_slot).collect()
+}
+
+/// Flatten a mob slot's segment into a list of events.
+pub fn flatten_slot<F: Read + Seek>(slot: &MobSlot<F>) -> Result<Timeline> {
+    let (edit_rate, origin) = match slot {
+        MobSlot::TimelineMobSlot(s) => (Some(s.edit_rate()?), s.origin()?),
+        MobSlot::EventMobSlot(s) => (Some(s.edit_rate()?), s.event_slot_origin()?.unwrap_or(0)),
+        MobSlot::StaticMobSlot(_) | MobSlot::Unknown(_) => (None, 0),
+    };
+    let mut events = vec![];
+    flatten_segment(&slot.segment()?, 0, &mut vec![], &mut events)?;
+    Ok(Timeline {
+        slot_id: slot.slot_id()?,
+        slot_name: slot.slot_name()?,
+        edit_rate,
+        origin,
+        events,
+    })
+}
+
+/// Append the events of `segment`, starting at `record_in`, to `events`.
+///
+/// `effects` are the operations `segment` is an input of.
+fn flatten_segment<F: Read + Seek>(
+    segment: &Segment<F>,
+    record_in: PositionType,
+    effects: &mut Vec<Uuid>,
+    events: &mut Vec<TimelineEvent>,
+) -> Result<()> {
+    let length = segment.length()?.unwrap_or(0);
+    let kind = match segment {
+        Segment::Sequence(sequence) => {
+            let mut position = record_in;
+            for component in sequence.components()? {
+                match Segment::try_from(component) {
+                    Ok(segment) => {
+                        flatten_segment(&segment, position, effects, events)?;
+                        position = position
+                            .checked_add(segment.length()?.unwrap_or(0))
+                            .ok_or_else(out_of_range)?;
+                    }
+                    Err(Component::Transition(transition)) => {
+                        // A transition overlaps the segments either side of
+                        // it, so the next segment starts at its start.
+                        let length = transition.length()?.unwrap_or(0);
+                        position = position.checked_sub(length).ok_or_else(out_of_range)?;
+                        let operation = transition.operation_group()?.operation()?;
+                        events.push(TimelineEvent {
+                            record_in: position,
+                            length,
+                            kind: TimelineEventKind::Transition {
+                                cut_point: transition.cut_point()?,
+                                operation: operation.identification()?,
+                            },
+                            effects: effects.clone(),
+                        });
+                    }
+                    Err(other) => events.push(TimelineEvent {
+                        record_in: position,
+                        length: other.length()?.unwrap_or(0),
+                        kind: TimelineEventKind::Other {
+                            class_id: other.descriptor().auid,
+                        },
+                        effects: effects.clone(),
+                    }),
+                }
+            }
+            return Ok(());
+        }
+        Segment::NestedScope(scope) => {
+            // The last slot of a scope is its output.
+            if let Some(output) = scope.slots()?.last() {
+                return flatten_segment(output, record_in, effects, events);
+            }
+            TimelineEventKind::Other {
+                class_id: segment.descriptor().auid,
+            }
+        }
+        Segment::OperationGroup(group) => {
+            // An effect's first input is the material it applies to.
+            if let Some(input) = group.input_segments()?.first() {
+                effects.push(group.operation()?.identification()?);
+                let result = flatten_segment(input, record_in, effects, events);
+                effects.pop();
+                return result;
+            }
+            TimelineEventKind::Other {
+                class_id: segment.descriptor().auid,
+            }
+        }
+        Segment::Selector(selector) => {
+            return flatten_segment(&selector.selected()?, record_in, effects, events);
+        }
+        Segment::SourceClip(clip) => TimelineEventKind::SourceClip {
+            source_id: clip.source_id()?,
+            source_mob_slot_id: clip.source_mob_slot_id()?,
+            start_time: clip.start_time()?.unwrap_or(0),
+        },
+        Segment::Filler(_) => TimelineEventKind::Filler,
+        _ => TimelineEventKind::Other {
+            class_id: segment.descriptor().auid,
+        },
+    };
+    events.push(TimelineEvent {
+        record_in,
+        length,
+        kind,
+        effects: effects.clone(),
+    });
+    Ok(())
+}
+
+/// A length, from a corrupt file, that puts a position beyond the range of
+/// `PositionType`.
+fn out_of_range() -> AAFError {
+    AAFError::InvalidValue {
+        type_name: "PositionType",
+        len: std::mem::size_of::<PositionType>(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aaf::class_ids;
+    use crate::file::AAFFile;
+    use std::path::Path;
+
+    const TEST_PATH: &str = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+    const MASTER_MOB_ID: &str =
+        "urn:smpte:umid:060a2b34.01010105.01010f10.13000000.140c27a5.87e80080.9acc4f71.bfe13e00";
+
+    fn clip(
+        record_in: PositionType,
+        length: LengthType,
+        start_time: PositionType,
+    ) -> TimelineEvent {
+        TimelineEvent {
+            record_in,
+            length,
+            kind: TimelineEventKind::SourceClip {
+                source_id: Some(MASTER_MOB_ID.parse().unwrap()),
+                source_mob_slot_id: 3,
+                start_time,
+            },
+            effects: vec![],
+        }
+    }
+
+    #[test]
+    fn test_flatten_mob() {
+        let content = AAFFile::open(TEST_PATH)
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+
+        let mobs = content.composition_mobs().unwrap();
+        assert_eq!(mobs.len(), 1);
+        let timelines = flatten_mob(&mobs[0]).unwrap();
+        assert_eq!(
+            timelines,
+            vec![
+                Timeline {
+                    slot_id: 3,
+                    slot_name: Some("Audio 1".to_string()),
+                    edit_rate: Some(Rational {
+                        numerator: 24,
+                        denominator: 1,
+                    }),
+                    origin: 0,
+                    events: vec![clip(0, 1, 0)],
+                },
+                Timeline {
+                    slot_id: 1002,
+                    slot_name: Some("Timecode".to_string()),
+                    edit_rate: Some(Rational {
+                        numerator: 24,
+                        denominator: 1,
+                    }),
+                    origin: 0,
+                    events: vec![TimelineEvent {
+                        record_in: 0,
+                        length: 2073600,
+                        kind: TimelineEventKind::Other {
+                            class_id: class_ids::TIMECODE,
+                        },
+                        effects: vec![],
+                    }],
+                },
+            ]
+        );
+    }
+
+    /// Replace the `Sequence` of the composition in `xml`, a document of
+    /// AAF_Test_1, with `sequence`.
+    fn replace_sequence(xml: &mut String, sequence: &str) {
+        let composition = xml.find("<UsageCode>Usage_TopLevel</UsageCode>").unwrap();
+        let start = composition + xml[composition..].find("<Sequence>").unwrap();
+        let end = start + xml[start..].find("</Sequence>").unwrap() + "</Sequence>".len();
+        xml.replace_range(start..end, sequence);
+    }
+
+    #[test]
+    fn test_flatten_fillers_and_transitions() {
+        // AAF_Test_1's composition, edited to be a filler and then two clips
+        // with a dissolve between them.
+        let dissolve = "0c3bea40-fc05-11d2-8a29-0050040ef7d2";
+        let operation_definitions = format!(
+            "
+        <OperationDefinitions>
+          <OperationDefinition>
+            <Identification>{{{dissolve}}}</Identification>
+            <Name>Mono Audio Dissolve</Name>
+            <DataDefinition>DataDef_LegacySound</DataDefinition>
+            <NumberInputs>2</NumberInputs>
+          </OperationDefinition>
+        </OperationDefinitions>
+        <DataDefinitions>"
+        );
+        let sound = "<DataDefinition>DataDef_LegacySound</DataDefinition>";
+        let source_clip = |start_time: i64, length: i64| {
+            format!(
+                "<SourceClip><StartTime>{start_time}</StartTime><SourceMobSlotID>3</SourceMobSlotID>\
+                 <SourceID>{MASTER_MOB_ID}</SourceID><Length>{length}</Length>{sound}</SourceClip>"
+            )
+        };
+        let sequence = format!(
+            "<Sequence><Components>\
+             <Filler><Length>10</Length>{sound}</Filler>\
+             {}\
+             <Transition><OperationGroup><OperationGroup>\
+             <Operation>{{{dissolve}}}</Operation><Length>6</Length>{sound}\
+             </OperationGroup></OperationGroup><CutPoint>3</CutPoint><Length>6</Length>{sound}\
+             </Transition>\
+             {}\
+             </Components><Length>48</Length>{sound}</Sequence>",
+            source_clip(5, 24),
+            source_clip(100, 20)
+        );
+
+        let mut xml = AAFFile::open(TEST_PATH)
+            .unwrap()
+            .to_xml_string()
+            .unwrap()
+            .replacen("\n        <DataDefinitions>", &operation_definitions, 1);
+        replace_sequence(&mut xml, &sequence);
+
+        let content = AAFFile::from_xml(&xml, Path::new("."))
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+        let mobs = content.composition_mobs().unwrap();
+        let timelines = flatten_mob(&mobs[0]).unwrap();
+        assert_eq!(
+            timelines[0].events,
+            vec![
+                TimelineEvent {
+                    record_in: 0,
+                    length: 10,
+                    kind: TimelineEventKind::Filler,
+                    effects: vec![],
+                },
+                clip(10, 24, 5),
+                TimelineEvent {
+                    record_in: 28,
+                    length: 6,
+                    kind: TimelineEventKind::Transition {
+                        cut_point: 3,
+                        operation: Uuid::parse_str(dissolve).unwrap(),
+                    },
+                    effects: vec![],
+                },
+                clip(28, 20, 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flatten_overflow() {
+        let sound = "<DataDefinition>DataDef_LegacySound</DataDefinition>";
+        let filler = format!("<Filler><Length>{}</Length>{sound}</Filler>", i64::MAX);
+        let sequence = format!(
+            "<Sequence><Components>{filler}{filler}</Components>\
+             <Length>48</Length>{sound}</Sequence>"
+        );
+        let mut xml = AAFFile::open(TEST_PATH).unwrap().to_xml_string().unwrap();
+        replace_sequence(&mut xml, &sequence);
+
+        let content = AAFFile::from_xml(&xml, Path::new("."))
+            .unwrap()
+            .header()
+            .unwrap()
+            .content()
+            .unwrap();
+        let mobs = content.composition_mobs().unwrap();
+        assert!(matches!(
+            flatten_mob(&mobs[0]),
+            Err(AAFError::InvalidValue { .. })
+        ));
+    }
+}
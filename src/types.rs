@@ -1,9 +1,14 @@
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::Cursor;
-#[allow(dead_code)]
-#[allow(unused_imports)]
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use encoding::all::{UTF_16BE, UTF_16LE};
+use encoding::{DecoderTrap, EncodingRef};
+use std::fmt;
+use std::io::{self, Read};
+use std::str::FromStr;
 use uuid::Uuid;
 
+use crate::aaf::enums::RGBAComponentKind;
+use crate::error::{AAFError, Result};
+
 pub type OMByteOrder = u8;
 pub type OMVersion = u8;
 pub type OMPropertyCount = u16;
@@ -23,6 +28,56 @@ pub type AAFInt16 = i16;
 pub type AAFInt32 = i32;
 pub type AAFInt64 = i64;
 
+/// The byte order an object's properties are stored in, which its index
+/// streams and data values share.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endian {
+    /// The byte order an `OMByteOrder` byte stands for: `'L'` for
+    /// little-endian, and `'B'` or, from some older writers, `'M'` for
+    /// big-endian.
+    pub fn from_byte_order(byte_order: OMByteOrder) -> Option<Self> {
+        match byte_order {
+            0x4c => Some(Self::Little),
+            0x42 | 0x4d => Some(Self::Big),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn read_u16<R: Read>(self, stream: &mut R) -> io::Result<u16> {
+        match self {
+            Self::Little => stream.read_u16::<LittleEndian>(),
+            Self::Big => stream.read_u16::<BigEndian>(),
+        }
+    }
+
+    pub(crate) fn read_u32<R: Read>(self, stream: &mut R) -> io::Result<u32> {
+        match self {
+            Self::Little => stream.read_u32::<LittleEndian>(),
+            Self::Big => stream.read_u32::<BigEndian>(),
+        }
+    }
+
+    pub(crate) fn read_u64<R: Read>(self, stream: &mut R) -> io::Result<u64> {
+        match self {
+            Self::Little => stream.read_u64::<LittleEndian>(),
+            Self::Big => stream.read_u64::<BigEndian>(),
+        }
+    }
+}
+
+/// Swap the first three fields of a stored AUID between byte orders.
+pub(crate) fn swap_auid(auid: &mut [u8]) {
+    auid[0..4].reverse();
+    auid[4..6].reverse();
+    auid[6..8].reverse();
+}
+
 pub type PositionType = AAFInt64;
 pub type LengthType = AAFInt64;
 pub type JPEGTableIDType = AAFInt32;
@@ -40,96 +95,446 @@ pub struct VersionType {
     pub minor: u8,
 }
 
-pub trait AAFFrom {
-    fn aaf_from(item: &[u8]) -> Self;
+/// The version of the application or toolkit that wrote a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProductVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub tertiary: u16,
+    pub patch_level: u16,
+    /// A `ProductReleaseType`: unknown, released, debug, patched, beta or
+    /// private build.
+    pub release_type: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Rational {
+    pub numerator: i32,
+    pub denominator: i32,
+}
+
+/// A component of the pixels of an `RGBADescriptor`, and its size in bits.
+///
+/// An `RGBALayout` lists the components in the order they are stored, and
+/// ends with `CompNull` components if it has fewer than eight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RGBAComponent {
+    pub code: RGBAComponentKind,
+    pub size: AAFUInt8,
+}
+
+/// A SMPTE 330M basic UMID identifying a `Mob`, as it is stored.
+///
+/// The 32 bytes are a 12 byte universal label, a length byte, a 3 byte
+/// instance number and a 16 byte material number, which AAF stores as an
+/// AUID.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MobID(pub [u8; 32]);
+
+impl MobID {
+    /// The SMPTE universal label that begins the UMID.
+    pub fn universal_label(&self) -> [u8; 12] {
+        let mut label = [0u8; 12];
+        label.copy_from_slice(&self.0[0..12]);
+        label
+    }
+
+    /// The length of the rest of the UMID, 0x13 for a basic UMID.
+    pub fn length(&self) -> u8 {
+        self.0[12]
+    }
+
+    pub fn instance_number(&self) -> u32 {
+        u32::from_be_bytes([0, self.0[13], self.0[14], self.0[15]])
+    }
+
+    pub fn material_number(&self) -> Uuid {
+        // A stored AUID is always 16 bytes
+        self.0[16..32].aaf_into().unwrap()
+    }
+
+    /// The UMID as it is written in `urn:smpte:umid:` form and SMPTE 330M,
+    /// with the material number in AUID byte order.
+    pub(crate) fn umid_bytes(&self) -> [u8; 32] {
+        let mut bytes = self.0;
+        bytes[16..32].copy_from_slice(self.material_number().as_bytes());
+        bytes
+    }
+
+    /// The MobID of a UMID in the byte order of `umid_bytes`.
+    pub(crate) fn from_umid_bytes(umid: [u8; 32]) -> Self {
+        let mut stored = umid;
+        let material = Uuid::from_bytes(umid[16..32].try_into().unwrap());
+        let (d1, d2, d3, d4) = material.as_fields();
+        stored[16..20].copy_from_slice(&d1.to_le_bytes());
+        stored[20..22].copy_from_slice(&d2.to_le_bytes());
+        stored[22..24].copy_from_slice(&d3.to_le_bytes());
+        stored[24..32].copy_from_slice(d4);
+        MobID(stored)
+    }
+}
+
+impl fmt::Display for MobID {
+    /// The UMID as a URN, in eight dot-separated groups of four bytes:
+    /// `urn:smpte:umid:060a2b34.01010105.01010f10.13000000.…`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "urn:smpte:umid:")?;
+        for (i, group) in self.umid_bytes().chunks(4).enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            for b in group {
+                write!(f, "{:02x}", b)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A MobID serializes as its URN.
+#[cfg(feature = "serde")]
+impl serde::Serialize for MobID {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl fmt::Debug for MobID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MobID({})", self)
+    }
+}
+
+impl FromStr for MobID {
+    type Err = AAFError;
+
+    /// Parse a UMID in `urn:smpte:umid:` form, or as 64 hex digits, which
+    /// may be separated by dots.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || AAFError::InvalidMobID {
+            text: s.to_string(),
+        };
+        let trimmed = s.trim();
+        let hex: Vec<u8> = trimmed
+            .strip_prefix("urn:smpte:umid:")
+            .unwrap_or(trimmed)
+            .bytes()
+            .filter(|b| *b != b'.')
+            .collect();
+        if hex.len() != 64 {
+            return Err(invalid());
+        }
+
+        let mut umid = [0u8; 32];
+        for (byte, digits) in umid.iter_mut().zip(hex.chunks(2)) {
+            let digits = std::str::from_utf8(digits).map_err(|_| invalid())?;
+            *byte = u8::from_str_radix(digits, 16).map_err(|_| invalid())?;
+        }
+
+        Ok(MobID::from_umid_bytes(umid))
+    }
+}
+
+/// A type that can be decoded from a stored data value.
+pub trait AAFFrom: Sized {
+    /// Decode a value stored in `endian` byte order.
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self>;
+
+    /// Decode a value stored in little-endian byte order.
+    fn aaf_from(item: &[u8]) -> Result<Self> {
+        Self::aaf_from_endian(item, Endian::Little)
+    }
 }
 
 pub trait AAFInto<F> {
-    fn aaf_into(self: Self) -> F;
+    fn aaf_into(self) -> Result<F>;
+    fn aaf_into_endian(self, endian: Endian) -> Result<F>;
 }
 
 impl<T> AAFInto<T> for &[u8]
 where
     T: AAFFrom,
 {
-    fn aaf_into(self: Self) -> T {
+    fn aaf_into(self) -> Result<T> {
         T::aaf_from(self)
     }
+
+    fn aaf_into_endian(self, endian: Endian) -> Result<T> {
+        T::aaf_from_endian(self, endian)
+    }
+}
+
+fn invalid<T>(type_name: &'static str, item: &[u8]) -> Result<T> {
+    Err(AAFError::InvalidValue {
+        type_name,
+        len: item.len(),
+    })
+}
+
+impl AAFFrom for bool {
+    fn aaf_from_endian(item: &[u8], _endian: Endian) -> Result<Self> {
+        match item.first() {
+            Some(b) => Ok(*b != 0),
+            None => invalid("Boolean", item),
+        }
+    }
+}
+
+impl AAFFrom for AAFUInt8 {
+    fn aaf_from_endian(item: &[u8], _endian: Endian) -> Result<Self> {
+        match item.first() {
+            Some(b) => Ok(*b),
+            None => invalid("AAFUInt8", item),
+        }
+    }
+}
+
+impl AAFFrom for AAFInt8 {
+    fn aaf_from_endian(item: &[u8], _endian: Endian) -> Result<Self> {
+        match item.first() {
+            Some(b) => Ok(*b as i8),
+            None => invalid("AAFInt8", item),
+        }
+    }
 }
 
 impl AAFFrom for AAFUInt16 {
-    fn aaf_from(item: &[u8]) -> Self {
-        Cursor::new(item)
-            .read_u16::<LittleEndian>()
-            .expect("Error reading AAFUInt16")
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        endian
+            .read_u16(&mut &item[..])
+            .or_else(|_| invalid("AAFUInt16", item))
     }
 }
 
 impl AAFFrom for u32 {
-    fn aaf_from(item: &[u8]) -> Self {
-        Cursor::new(item)
-            .read_u32::<LittleEndian>()
-            .expect("Failed to decode u32")
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        endian
+            .read_u32(&mut &item[..])
+            .or_else(|_| invalid("AAFUInt32", item))
     }
 }
 
 impl AAFFrom for AAFUInt64 {
-    fn aaf_from(item: &[u8]) -> Self {
-        Cursor::new(item)
-            .read_u64::<LittleEndian>()
-            .expect("Failed to decode AAFUInt64")
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        endian
+            .read_u64(&mut &item[..])
+            .or_else(|_| invalid("AAFUInt64", item))
     }
 }
 
 impl AAFFrom for AAFInt16 {
-    fn aaf_from(item: &[u8]) -> Self {
-        Cursor::new(item)
-            .read_i16::<LittleEndian>()
-            .expect("Error  reading AAFInt16")
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        endian
+            .read_u16(&mut &item[..])
+            .map(|value| value as AAFInt16)
+            .or_else(|_| invalid("AAFInt16", item))
+    }
+}
+
+impl AAFFrom for AAFInt32 {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        endian
+            .read_u32(&mut &item[..])
+            .map(|value| value as AAFInt32)
+            .or_else(|_| invalid("AAFInt32", item))
+    }
+}
+
+impl AAFFrom for AAFInt64 {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        endian
+            .read_u64(&mut &item[..])
+            .map(|value| value as AAFInt64)
+            .or_else(|_| invalid("AAFInt64", item))
+    }
+}
+
+/// A UTF-16 string, with or without its terminating null.
+impl AAFFrom for String {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        if item.len() % 2 != 0 {
+            return invalid("String", item);
+        }
+        let mut end = item.len();
+        while end >= 2 && item[end - 2] == 0 && item[end - 1] == 0 {
+            end -= 2;
+        }
+        let encoding: EncodingRef = match endian {
+            Endian::Little => UTF_16LE,
+            Endian::Big => UTF_16BE,
+        };
+        encoding
+            .decode(&item[0..end], DecoderTrap::Replace)
+            .or_else(|_| invalid("String", item))
+    }
+}
+
+/// A `StringArray`: a run of null-terminated UTF-16 strings.
+impl AAFFrom for Vec<String> {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        if item.len() % 2 != 0 {
+            return invalid("StringArray", item);
+        }
+        let mut retval = vec![];
+        let mut start = 0;
+        for i in (0..item.len()).step_by(2) {
+            if item[i] == 0 && item[i + 1] == 0 {
+                retval.push(item[start..i].aaf_into_endian(endian)?);
+                start = i + 2;
+            }
+        }
+        if start < item.len() {
+            retval.push(item[start..].aaf_into_endian(endian)?);
+        }
+        Ok(retval)
+    }
+}
+
+/// The stored bytes of a value, undecoded.
+impl AAFFrom for Vec<u8> {
+    fn aaf_from_endian(item: &[u8], _endian: Endian) -> Result<Self> {
+        Ok(item.to_vec())
+    }
+}
+
+impl AAFFrom for Vec<AAFInt64> {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        if item.len() % 8 != 0 {
+            return invalid("Int64Array", item);
+        }
+        item.chunks(8).map(|c| c.aaf_into_endian(endian)).collect()
+    }
+}
+
+impl AAFFrom for Vec<AAFInt32> {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        if item.len() % 4 != 0 {
+            return invalid("Int32Array", item);
+        }
+        item.chunks(4).map(|c| c.aaf_into_endian(endian)).collect()
+    }
+}
+
+impl AAFFrom for Vec<AAFUInt32> {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        if item.len() % 4 != 0 {
+            return invalid("UInt32Array", item);
+        }
+        item.chunks(4).map(|c| c.aaf_into_endian(endian)).collect()
+    }
+}
+
+impl AAFFrom for Vec<RGBAComponent> {
+    fn aaf_from_endian(item: &[u8], _endian: Endian) -> Result<Self> {
+        if item.len() % 2 != 0 {
+            return invalid("RGBALayout", item);
+        }
+        Ok(item
+            .chunks(2)
+            .map(|c| RGBAComponent {
+                code: RGBAComponentKind::from(c[0]),
+                size: c[1],
+            })
+            .collect())
+    }
+}
+
+impl AAFFrom for Vec<Uuid> {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        if item.len() % 16 != 0 {
+            return invalid("AUIDArray", item);
+        }
+        item.chunks(16).map(|c| c.aaf_into_endian(endian)).collect()
     }
 }
 
 impl AAFFrom for TimeStamp {
-    fn aaf_from(item: &[u8]) -> Self {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
         if item.len() < 8 {
-            panic!("TimeStamp record insufficient length")
+            invalid("TimeStamp", item)
         } else {
-            TimeStamp {
-                date: (item[0..2].aaf_into(), item[2].into(), item[3].into()),
-                time: (
-                    item[4].into(),
-                    item[5].into(),
-                    item[6].into(),
-                    item[7].into(),
-                ),
+            Ok(TimeStamp {
+                date: (item[0..2].aaf_into_endian(endian)?, item[2], item[3]),
+                time: (item[4], item[5], item[6], item[7]),
+            })
+        }
+    }
+}
+
+impl AAFFrom for Rational {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        if item.len() < 8 {
+            invalid("Rational", item)
+        } else {
+            Ok(Rational {
+                numerator: item[0..4].aaf_into_endian(endian)?,
+                denominator: item[4..8].aaf_into_endian(endian)?,
+            })
+        }
+    }
+}
+
+impl AAFFrom for MobID {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        match item.get(0..32) {
+            Some(b) => {
+                let mut bytes = [0u8; 32];
+                bytes.copy_from_slice(b);
+                // The material number is an AUID, kept in the byte order
+                // of a little-endian file.
+                if endian == Endian::Big {
+                    swap_auid(&mut bytes[16..32]);
+                }
+                Ok(MobID(bytes))
             }
+            None => invalid("MobIDType", item),
         }
     }
 }
 
 impl AAFFrom for VersionType {
-    fn aaf_from(item: &[u8]) -> Self {
+    fn aaf_from_endian(item: &[u8], _endian: Endian) -> Result<Self> {
         if item.len() < 2 {
-            panic!("VersionType record insufficient length")
+            invalid("VersionType", item)
         } else {
-            VersionType {
-                major: item[0].into(),
-                minor: item[1].into(),
-            }
+            Ok(VersionType {
+                major: item[0],
+                minor: item[1],
+            })
+        }
+    }
+}
+
+impl AAFFrom for ProductVersion {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
+        if item.len() < 9 {
+            invalid("ProductVersion", item)
+        } else {
+            Ok(ProductVersion {
+                major: item[0..2].aaf_into_endian(endian)?,
+                minor: item[2..4].aaf_into_endian(endian)?,
+                tertiary: item[4..6].aaf_into_endian(endian)?,
+                patch_level: item[6..8].aaf_into_endian(endian)?,
+                release_type: item[8],
+            })
         }
     }
 }
 
 impl AAFFrom for Uuid {
-    fn aaf_from(item: &[u8]) -> Self {
+    fn aaf_from_endian(item: &[u8], endian: Endian) -> Result<Self> {
         if item.len() < 16 {
-            panic!("Uuid record insufficient length")
+            invalid("Uuid", item)
         } else {
-            let d1: AAFUInt32 = item[0..3].aaf_into();
-            let d2: AAFUInt16 = item[3..5].aaf_into();
-            let d3: AAFUInt16 = item[5..7].aaf_into();
-            let d4: &[u8] = &item[7..15];
-            Uuid::from_fields(d1, d2, d3, d4).unwrap()
+            let d1: AAFUInt32 = item[0..4].aaf_into_endian(endian)?;
+            let d2: AAFUInt16 = item[4..6].aaf_into_endian(endian)?;
+            let d3: AAFUInt16 = item[6..8].aaf_into_endian(endian)?;
+            let d4: &[u8] = &item[8..16];
+            Uuid::from_fields(d1, d2, d3, d4).or_else(|_| invalid("Uuid", item))
         }
     }
 }
@@ -0,0 +1,225 @@
+/// xml/mod.rs
+///
+/// AAF-XML, the SMPTE ST 2001-1 encoding of the AAF object model.
+///
+/// Objects are elements named for their class, and properties are child
+/// elements named for the property. A strong reference contains the
+/// element of the object it refers to, and a weak reference contains the
+/// key of its target, or the symbol of a well-known definition such as
+/// `DataDef_Sound`.
+use uuid::Uuid;
+
+use crate::aaf::class_ids;
+
+pub mod reader;
+pub mod writer;
+
+/// The first four bytes of a SMPTE universal label, which an AUID that
+/// holds a label stores in its last eight bytes.
+const SMPTE_LABEL_PREFIX: [u8; 4] = [0x06, 0x0e, 0x2b, 0x34];
+
+/// An AUID as AAF-XML writes it: `urn:smpte:ul:` and the label, in four
+/// dot-separated groups of four bytes, for AUIDs that hold a SMPTE
+/// universal label, and `urn:uuid:` otherwise.
+pub fn auid_to_urn(auid: &Uuid) -> String {
+    let bytes = auid.as_bytes();
+    if bytes[8..12] != SMPTE_LABEL_PREFIX {
+        return auid.to_urn().to_string();
+    }
+    let mut label = bytes[8..16].to_vec();
+    label.extend(&bytes[0..8]);
+    let groups: Vec<String> = label
+        .chunks(4)
+        .map(|group| group.iter().map(|b| format!("{:02x}", b)).collect())
+        .collect();
+    format!("urn:smpte:ul:{}", groups.join("."))
+}
+
+/// Parse an AUID in `urn:smpte:ul:` or `urn:uuid:` form, or as a UUID
+/// with or without braces.
+pub fn auid_from_urn(text: &str) -> Option<Uuid> {
+    let text = text.trim();
+    if let Some(label) = text.strip_prefix("urn:smpte:ul:") {
+        let hex: Vec<u8> = label.bytes().filter(|b| *b != b'.').collect();
+        if hex.len() != 32 {
+            return None;
+        }
+        let mut bytes = [0u8; 16];
+        for (i, digits) in hex.chunks(2).enumerate() {
+            let byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+            bytes[(i + 8) % 16] = byte;
+        }
+        return Some(Uuid::from_bytes(bytes));
+    }
+    let text = text
+        .strip_prefix('{')
+        .and_then(|t| t.strip_suffix('}'))
+        .unwrap_or(text);
+    Uuid::parse_str(text).ok()
+}
+
+/// The bytes of an AUID as a little-endian file stores it.
+pub(crate) fn auid_bytes(auid: &Uuid) -> [u8; 16] {
+    let (d1, d2, d3, d4) = auid.as_fields();
+    let mut bytes = [0u8; 16];
+    bytes[0..4].copy_from_slice(&d1.to_le_bytes());
+    bytes[4..6].copy_from_slice(&d2.to_le_bytes());
+    bytes[6..8].copy_from_slice(&d3.to_le_bytes());
+    bytes[8..16].copy_from_slice(d4);
+    bytes
+}
+
+/// A definition the AAF SDK registers in every file, which AAF-XML may
+/// refer to by its symbol without defining it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownDefinition {
+    pub symbol: &'static str,
+    pub name: &'static str,
+    /// The AUID of the definition's class.
+    pub class: Uuid,
+    pub identification: Uuid,
+}
+
+const fn data_def(symbol: &'static str, name: &'static str, id: u128) -> KnownDefinition {
+    KnownDefinition {
+        symbol,
+        name,
+        class: class_ids::DATA_DEFINITION,
+        identification: Uuid::from_u128(id),
+    }
+}
+
+const fn container_def(symbol: &'static str, name: &'static str, id: u128) -> KnownDefinition {
+    KnownDefinition {
+        symbol,
+        name,
+        class: class_ids::CONTAINER_DEFINITION,
+        identification: Uuid::from_u128(id),
+    }
+}
+
+/// The data and container definitions of the baseline.
+pub const KNOWN_DEFINITIONS: [KnownDefinition; 17] = [
+    data_def(
+        "DataDef_Picture",
+        "Picture",
+        0x01030202_0100_0000_060e_2b3404010101,
+    ),
+    data_def(
+        "DataDef_LegacyPicture",
+        "Picture",
+        0x6f3c8ce1_6cef_11d2_807d_006008143e6f,
+    ),
+    data_def(
+        "DataDef_Matte",
+        "Matte",
+        0x05cba731_1daa_11d3_80ad_006008143e6f,
+    ),
+    data_def(
+        "DataDef_PictureWithMatte",
+        "PictureWithMatte",
+        0x05cba732_1daa_11d3_80ad_006008143e6f,
+    ),
+    data_def(
+        "DataDef_Sound",
+        "Sound",
+        0x01030202_0200_0000_060e_2b3404010101,
+    ),
+    data_def(
+        "DataDef_LegacySound",
+        "Sound",
+        0x78e1ebe1_6cef_11d2_807d_006008143e6f,
+    ),
+    data_def(
+        "DataDef_Timecode",
+        "Timecode",
+        0x01030201_0100_0000_060e_2b3404010101,
+    ),
+    data_def(
+        "DataDef_LegacyTimecode",
+        "Timecode",
+        0x7f275e81_77e5_11d2_807f_006008143e6f,
+    ),
+    data_def(
+        "DataDef_Edgecode",
+        "Edgecode",
+        0xd2bb2af0_d234_11d2_89ee_006097116212,
+    ),
+    data_def(
+        "DataDef_DescriptiveMetadata",
+        "Descriptive Metadata",
+        0x01030201_1000_0000_060e_2b3404010101,
+    ),
+    data_def(
+        "DataDef_Auxiliary",
+        "Auxiliary",
+        0x01030203_0100_0000_060e_2b3404010105,
+    ),
+    container_def(
+        "ContainerDef_External",
+        "External",
+        0x4313b572_d8ba_11d2_809b_006008143e6f,
+    ),
+    container_def(
+        "ContainerDef_OMF",
+        "OMF",
+        0x4b1c1a46_03f2_11d4_80fb_006008143e6f,
+    ),
+    container_def(
+        "ContainerDef_AAF",
+        "AAF",
+        0x4313b571_d8ba_11d2_809b_006008143e6f,
+    ),
+    container_def(
+        "ContainerDef_RIFFWAVE",
+        "ContainerDef_RIFFWAVE",
+        0x0d011301_0101_0100_060e_2b3404010106,
+    ),
+    container_def(
+        "ContainerDef_JFIF",
+        "ContainerDef_JFIF",
+        0x0d011301_0102_0200_060e_2b3404010107,
+    ),
+    container_def(
+        "ContainerDef_AIFFAIFC",
+        "ContainerDef_AIFFAIFC",
+        0x0d011301_0104_0100_060e_2b3404010106,
+    ),
+];
+
+/// The well-known definition with the symbol `symbol`.
+pub fn known_definition(symbol: &str) -> Option<&'static KnownDefinition> {
+    KNOWN_DEFINITIONS.iter().find(|d| d.symbol == symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::AAFFrom;
+
+    #[test]
+    fn test_auid_urns() {
+        let picture = Uuid::parse_str("01030202-0100-0000-060e-2b3404010101").unwrap();
+        let urn = auid_to_urn(&picture);
+        assert_eq!(urn, "urn:smpte:ul:060e2b34.04010101.01030202.01000000");
+        assert_eq!(auid_from_urn(&urn), Some(picture));
+
+        let legacy = Uuid::parse_str("6f3c8ce1-6cef-11d2-807d-006008143e6f").unwrap();
+        let urn = auid_to_urn(&legacy);
+        assert_eq!(urn, "urn:uuid:6f3c8ce1-6cef-11d2-807d-006008143e6f");
+        assert_eq!(auid_from_urn(&urn), Some(legacy));
+        assert_eq!(
+            auid_from_urn("{6f3c8ce1-6cef-11d2-807d-006008143e6f}"),
+            Some(legacy)
+        );
+        assert_eq!(auid_from_urn("DataDef_Picture"), None);
+
+        // Stored bytes decode to the same AUID
+        let stored = auid_bytes(&picture);
+        assert_eq!(Uuid::aaf_from(&stored).unwrap(), picture);
+        assert_eq!(
+            known_definition("DataDef_Picture").unwrap().identification,
+            picture
+        );
+    }
+}
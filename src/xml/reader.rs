@@ -0,0 +1,1955 @@
+/// xml/reader.rs
+///
+/// Reading AAF-XML into the same object graph as a structured storage file.
+///
+/// The document's objects are stored into a compound file held in memory,
+/// with the pids, stored forms, indexes and weak reference tags a file
+/// written by the AAF SDK would have, so that an `AAFFile` read from XML
+/// behaves exactly as one read from disk.
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use base64::Engine;
+use roxmltree::{Document, Node, ParsingOptions};
+use uuid::Uuid;
+
+use crate::aaf::class_ids;
+use crate::aaf::meta_dictionary::{
+    ClassDefinition, MetaDictionary, PropertyDefinition, TypeDefinition, TypeDefinitionKind,
+};
+use crate::aaf::type_ids;
+use crate::error::{AAFError, IoResultExt, Result};
+use crate::file::{AAFFile, AAFSignature, AAF_ROOT_CLASS};
+use crate::properties::*;
+use crate::relink::url_to_path;
+use crate::types::*;
+use crate::xml::{auid_bytes, auid_from_urn, known_definition};
+
+const ROOT_METADICTIONARY_PID: OMPropertyId = 0x0001;
+const ROOT_HEADER_PID: OMPropertyId = 0x0002;
+
+/// The AUIDs of the root object's properties, which begin the target sets
+/// of weak references.
+const ROOT_METADICTIONARY_ID: Uuid = Uuid::from_u128(0x0d010301_0101_0100_060e_2b3401010102);
+const ROOT_HEADER_ID: Uuid = Uuid::from_u128(0x0d010301_0102_0100_060e_2b3401010102);
+
+const DEFINITION_IDENTIFICATION_PID: OMPropertyId = 0x1b01;
+const DEFINITION_NAME_PID: OMPropertyId = 0x1b02;
+const META_DEFINITION_IDENTIFICATION_PID: OMPropertyId = 0x0005;
+const META_DEFINITION_NAME_PID: OMPropertyId = 0x0006;
+
+/// The byte order byte of little-endian properties and values.
+const LITTLE_ENDIAN: OMByteOrder = 0x4c;
+/// The version byte of a `properties` stream.
+const PROPERTIES_VERSION: OMVersion = 0x20;
+/// The byte order byte of a stream whose contents have no byte order.
+const STREAM_UNSPECIFIED_BYTE_ORDER: OMByteOrder = 0x55;
+
+/// The lowest pid given to a property the document gives no pid.
+const MIN_DYNAMIC_PID: OMPropertyId = 0x8000;
+
+/// The longest name a compound file storage or stream may have.
+const MAX_STORAGE_NAME_LEN: usize = 31;
+
+impl AAFFile<Cursor<Vec<u8>>> {
+    /// Open the AAF-XML document at `path`.
+    ///
+    /// Streams the document refers to in other files are read relative to
+    /// the document's directory, and only from within it.
+    pub fn open_xml<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).at_path(path)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Self::from_xml(&text, base_dir)
+    }
+
+    /// Read an AAF-XML document from `text`.
+    ///
+    /// Streams the document refers to in other files are read relative to
+    /// `base_dir`, and only from within it: a document that refers to a file
+    /// elsewhere, by an absolute path, a `file:` URL or `..`, is
+    /// `InvalidXml`.
+    ///
+    /// The file's `MetaDictionary` holds the definitions of the document's
+    /// extensions, and its format has the `AafXml` signature.
+    pub fn from_xml(text: &str, base_dir: &Path) -> Result<Self> {
+        let options = ParsingOptions {
+            allow_dtd: true,
+            ..ParsingOptions::default()
+        };
+        let doc =
+            Document::parse_with_options(text, options).map_err(|e| AAFError::InvalidXml {
+                line: e.pos().row,
+                reason: e.to_string(),
+            })?;
+
+        let mut reader = XmlReader::new(&doc, base_dir, unparsed_entities(text));
+        let aaf = doc.root_element();
+        if aaf.tag_name().name() != "AAF" {
+            return reader.invalid(aaf, "the document element is not AAF".to_string());
+        }
+        if let Some(extensions) = child_element(aaf, "Extensions") {
+            reader.read_extensions(extensions)?;
+        }
+        let header_node = match child_element(aaf, "Header") {
+            Some(node) => node,
+            None => return reader.invalid(aaf, "there is no Header".to_string()),
+        };
+        reader.index_definitions(header_node);
+
+        let header = reader.read_object(header_node)?;
+        let mut root = Object {
+            class: AAF_ROOT_CLASS,
+            properties: vec![
+                Property {
+                    pid: ROOT_METADICTIONARY_PID,
+                    name: "MetaDictionary".to_string(),
+                    value: Value::Strong(Box::new(Object {
+                        class: class_ids::META_DICTIONARY,
+                        properties: vec![],
+                    })),
+                },
+                Property {
+                    pid: ROOT_HEADER_PID,
+                    name: "Header".to_string(),
+                    value: Value::Strong(Box::new(header)),
+                },
+            ],
+        };
+        reader.add_placeholders(header_node, &mut root)?;
+
+        let mut storage = Storage {
+            cfb: cfb::CompoundFile::create(Cursor::new(vec![])).at_path(Path::new("/"))?,
+            tags: vec![],
+        };
+        storage.store_object(Path::new("/"), &root)?;
+        let mut cfb = storage.finish()?;
+        cfb.flush().at_path(Path::new("/"))?;
+
+        let mut file = AAFFile::with_cfb(cfb, Some(AAFSignature::AafXml))?;
+        file.set_meta_dictionary(reader.md);
+        Ok(file)
+    }
+}
+
+/// An object read from the document.
+struct Object {
+    class: Uuid,
+    properties: Vec<Property>,
+}
+
+struct Property {
+    pid: OMPropertyId,
+    name: String,
+    value: Value,
+}
+
+enum Value {
+    Data(Vec<u8>),
+    Stream(Vec<u8>),
+    Strong(Box<Object>),
+    StrongVector(Vec<Object>),
+    /// The members of a strong reference set and the pid of the property
+    /// they are keyed by, or 0 if their class has no unique identifier.
+    StrongSet(OMPropertyId, Vec<Object>),
+    Weak(WeakReference),
+    WeakVector(WeakReference),
+    WeakSet(WeakReference),
+}
+
+/// A definition a weak reference refers to that the document does not
+/// define: a well-known definition, or a class or type of the
+/// `MetaDictionary`, which AAF-XML does not write.
+struct Placeholder {
+    /// The pids of the properties from the root object to the set the
+    /// definition belongs in.
+    target: Vec<OMPropertyId>,
+    class: Uuid,
+    identification: Uuid,
+    name: String,
+}
+
+/// The targets of a weak reference or weak reference collection.
+struct WeakReference {
+    /// The pids of the properties from the root object to the strong
+    /// reference set the targets are members of.
+    target: Vec<OMPropertyId>,
+    key_pid: OMPropertyId,
+    /// The keys of the targets, as a little-endian file stores them.
+    keys: Vec<Vec<u8>>,
+}
+
+struct XmlReader<'a, 'input> {
+    doc: &'a Document<'input>,
+    base_dir: PathBuf,
+    /// The file names of the unparsed entities the document declares.
+    entities: HashMap<String, String>,
+    /// The definitions of the document's extensions.
+    md: MetaDictionary,
+    /// The AUIDs of the extensions' definitions, by symbol.
+    symbols: HashMap<String, Uuid>,
+    /// The AUIDs of the definition objects in the document, by name.
+    names: HashMap<String, Uuid>,
+    /// The AUIDs of the definition objects in the document.
+    defined: HashSet<Uuid>,
+    /// Definitions that weak references refer to but the document does not
+    /// define.
+    placeholders: RefCell<Vec<Placeholder>>,
+}
+
+impl<'a, 'input> XmlReader<'a, 'input> {
+    fn new(doc: &'a Document<'input>, base_dir: &Path, entities: HashMap<String, String>) -> Self {
+        Self {
+            doc,
+            base_dir: base_dir.to_path_buf(),
+            entities,
+            md: MetaDictionary::default(),
+            symbols: HashMap::new(),
+            names: HashMap::new(),
+            defined: HashSet::new(),
+            placeholders: RefCell::new(vec![]),
+        }
+    }
+
+    fn invalid<T>(&self, node: Node, reason: String) -> Result<T> {
+        Err(AAFError::InvalidXml {
+            line: self.doc.text_pos_at(node.range().start).row,
+            reason,
+        })
+    }
+
+    /// The class named `name`, from the extensions or the baseline.
+    fn class_by_name(&self, name: &str) -> Option<&ClassDefinition> {
+        self.symbols
+            .get(name)
+            .and_then(|id| self.md.class(id))
+            .or_else(|| self.md.class_by_name(name))
+            .or_else(|| MetaDictionary::baseline().class_by_name(name))
+    }
+
+    fn class(&self, id: &Uuid) -> Option<&ClassDefinition> {
+        self.md
+            .class(id)
+            .or_else(|| MetaDictionary::baseline().class(id))
+    }
+
+    fn resolve_type(&self, id: &Uuid) -> Option<&TypeDefinition> {
+        self.md.resolve_type(id)
+    }
+
+    /// The properties of class `id` and its ancestors.
+    fn all_properties(&self, id: &Uuid) -> Vec<&PropertyDefinition> {
+        let baseline = MetaDictionary::baseline();
+        self.md
+            .resolve_lineage(id)
+            .into_iter()
+            .flat_map(|c| {
+                let defined = [
+                    self.md.class(&c.identification),
+                    baseline.class(&c.identification),
+                ];
+                defined
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|c| c.properties.iter())
+            })
+            .collect()
+    }
+
+    fn property_by_name(&self, class: &Uuid, name: &str) -> Option<&PropertyDefinition> {
+        self.all_properties(class)
+            .into_iter()
+            .find(|p| p.name == name || self.symbols.get(name) == Some(&p.identification))
+    }
+
+    fn property_by_pid(&self, class: &Uuid, pid: OMPropertyId) -> Option<&PropertyDefinition> {
+        self.all_properties(class)
+            .into_iter()
+            .find(|p| p.local_identification == pid)
+    }
+
+    /// The property with AUID `id`, of any class.
+    fn property_by_id(&self, id: &Uuid) -> Option<&PropertyDefinition> {
+        self.md
+            .classes()
+            .chain(MetaDictionary::baseline().classes())
+            .flat_map(|c| c.properties.iter())
+            .find(|p| p.identification == *id)
+    }
+
+    /// The property members of class `id` are identified by, if it has one.
+    fn unique_identifier(&self, id: &Uuid) -> Option<&PropertyDefinition> {
+        self.all_properties(id)
+            .into_iter()
+            .find(|p| p.is_unique_identifier)
+    }
+
+    fn is_kind_of(&self, id: &Uuid, ancestor: &Uuid) -> bool {
+        self.md
+            .resolve_lineage(id)
+            .iter()
+            .any(|c| c.identification == *ancestor)
+    }
+
+    /// The pids from the root object to the set named by a weak reference
+    /// type's target set of property AUIDs.
+    fn target_path(&self, target_set: &[Uuid]) -> Option<Vec<OMPropertyId>> {
+        target_set
+            .iter()
+            .map(|id| match *id {
+                ROOT_METADICTIONARY_ID => Some(ROOT_METADICTIONARY_PID),
+                ROOT_HEADER_ID => Some(ROOT_HEADER_PID),
+                _ => self.property_by_id(id).map(|p| p.local_identification),
+            })
+            .collect()
+    }
+
+    /// Record the AUID and name of each definition object in the document,
+    /// so that weak references may refer to them by name.
+    fn index_definitions(&mut self, header: Node) {
+        for node in header.descendants().filter(Node::is_element) {
+            let class = match self.class_by_name(node.tag_name().name()) {
+                Some(class) => class.identification,
+                None => continue,
+            };
+            if !self.is_kind_of(&class, &class_ids::DEFINITION_OBJECT) {
+                continue;
+            }
+            let id = match child_text(node, "Identification").and_then(|t| auid_from_urn(&t)) {
+                Some(id) => id,
+                None => continue,
+            };
+            self.defined.insert(id);
+            if let Some(name) = child_text(node, "Name") {
+                self.names.insert(name, id);
+            }
+        }
+    }
+
+    fn read_object(&self, node: Node) -> Result<Object> {
+        let class_name = node.tag_name().name();
+        let class = match self.class_by_name(class_name) {
+            Some(class) => class,
+            None => return self.invalid(node, format!("{} is not a class", class_name)),
+        };
+
+        let mut properties = vec![];
+        for child in node.children().filter(Node::is_element) {
+            let name = child.tag_name().name();
+            let property = match self.property_by_name(&class.identification, name) {
+                Some(property) => property,
+                None => {
+                    return self.invalid(
+                        child,
+                        format!("{} is not a property of {}", name, class.name),
+                    )
+                }
+            };
+            let type_def = match self.resolve_type(&property.type_id) {
+                Some(type_def) => type_def,
+                None => return self.invalid(child, format!("{} has an undefined type", name)),
+            };
+            properties.push(Property {
+                pid: property.local_identification,
+                name: property.name.clone(),
+                value: self.read_value(child, type_def)?,
+            });
+        }
+
+        Ok(Object {
+            class: class.identification,
+            properties,
+        })
+    }
+
+    fn read_value(&self, node: Node, type_def: &TypeDefinition) -> Result<Value> {
+        match &type_def.kind {
+            TypeDefinitionKind::Stream => self.read_stream(node).map(Value::Stream),
+            TypeDefinitionKind::StrongObjectReference { .. } => {
+                match node.children().find(Node::is_element) {
+                    Some(object) => Ok(Value::Strong(Box::new(self.read_object(object)?))),
+                    None => self.invalid(node, "a strong reference has no object".to_string()),
+                }
+            }
+            TypeDefinitionKind::WeakObjectReference { .. } => self
+                .read_weak_reference(node, type_def, &[node])
+                .map(Value::Weak),
+            TypeDefinitionKind::FixedArray { element_type, .. }
+            | TypeDefinitionKind::VariableArray { element_type }
+            | TypeDefinitionKind::Set { element_type } => {
+                let element_type = match self.resolve_type(element_type) {
+                    Some(t) => t,
+                    None => return self.invalid(node, "an undefined element type".to_string()),
+                };
+                let is_set = matches!(type_def.kind, TypeDefinitionKind::Set { .. });
+                let members: Vec<Node> = node.children().filter(Node::is_element).collect();
+                match &element_type.kind {
+                    TypeDefinitionKind::StrongObjectReference { referenced_class } => {
+                        let objects = members
+                            .into_iter()
+                            .map(|member| self.read_object(member))
+                            .collect::<Result<Vec<_>>>()?;
+                        if is_set {
+                            let key_pid = self
+                                .unique_identifier(referenced_class)
+                                .map_or(0, |p| p.local_identification);
+                            Ok(Value::StrongSet(key_pid, objects))
+                        } else {
+                            Ok(Value::StrongVector(objects))
+                        }
+                    }
+                    TypeDefinitionKind::WeakObjectReference { .. } => {
+                        let weak = self.read_weak_reference(node, element_type, &members)?;
+                        if is_set {
+                            Ok(Value::WeakSet(weak))
+                        } else {
+                            Ok(Value::WeakVector(weak))
+                        }
+                    }
+                    _ => self.encode(node, type_def).map(Value::Data),
+                }
+            }
+            _ => self.encode(node, type_def).map(Value::Data),
+        }
+    }
+
+    /// The contents of a stream property: the file in `base_dir` named by its
+    /// `stream` attribute, an unparsed entity or a path, or else its text in
+    /// base64.
+    fn read_stream(&self, node: Node) -> Result<Vec<u8>> {
+        let reference = node
+            .attributes()
+            .find(|a| a.name() == "stream")
+            .map(|a| a.value());
+        match reference {
+            Some(reference) => {
+                let location = self
+                    .entities
+                    .get(reference)
+                    .map_or(reference, |s| s.as_str());
+                let path = url_to_path(location).unwrap_or_else(|| self.base_dir.join(location));
+                // Following links and `..`, so that the document cannot
+                // read files from elsewhere
+                let resolved = path.canonicalize().at_path(&path)?;
+                let base_dir = self.base_dir.canonicalize().at_path(&self.base_dir)?;
+                if !resolved.starts_with(&base_dir) {
+                    return self.invalid(
+                        node,
+                        format!("stream {} is outside {:?}", location, self.base_dir),
+                    );
+                }
+                std::fs::read(&resolved).at_path(&path)
+            }
+            None => {
+                let text: String = node_text(node)
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect();
+                match base64::engine::general_purpose::STANDARD.decode(text) {
+                    Ok(bytes) => Ok(bytes),
+                    Err(e) => self.invalid(node, format!("a stream is not base64: {}", e)),
+                }
+            }
+        }
+    }
+
+    /// The targets of a weak reference of `weak_type`, one for each of
+    /// `members`, whose text is a key or the name of a definition.
+    fn read_weak_reference(
+        &self,
+        node: Node,
+        weak_type: &TypeDefinition,
+        members: &[Node],
+    ) -> Result<WeakReference> {
+        let (referenced_class, target_set) = match &weak_type.kind {
+            TypeDefinitionKind::WeakObjectReference {
+                referenced_class,
+                target_set,
+            } => (referenced_class, target_set),
+            _ => return self.invalid(node, "not a weak reference".to_string()),
+        };
+        let target = match self.target_path(target_set) {
+            Some(target) if !target.is_empty() => target,
+            _ => {
+                return self.invalid(
+                    node,
+                    format!("the target set of {} is undefined", weak_type.name),
+                )
+            }
+        };
+        let key = match self.unique_identifier(referenced_class) {
+            Some(key) => key,
+            None => {
+                return self.invalid(
+                    node,
+                    format!(
+                        "the targets of {} have no unique identifier",
+                        weak_type.name
+                    ),
+                )
+            }
+        };
+
+        let mut keys = vec![];
+        for member in members {
+            let text = node_text(*member);
+            let text = text.trim();
+            let stored = if key.type_id == type_ids::MOB_ID_TYPE {
+                MobID::from_str(text).ok().map(|id| id.0.to_vec())
+            } else {
+                self.definition_id(text, &target, referenced_class)
+                    .map(|id| auid_bytes(&id).to_vec())
+            };
+            match stored {
+                Some(stored) => keys.push(stored),
+                None => {
+                    return self.invalid(*member, format!("{:?} does not name an object", text))
+                }
+            }
+        }
+
+        Ok(WeakReference {
+            target,
+            key_pid: key.local_identification,
+            keys,
+        })
+    }
+
+    /// The AUID of the definition of `class` that `text` refers to: an
+    /// AUID, the symbol of a well-known definition, or the name of a
+    /// definition in the document, or of a class or type if the definition
+    /// belongs in the `MetaDictionary`.
+    fn definition_id(&self, text: &str, target: &[OMPropertyId], class: &Uuid) -> Option<Uuid> {
+        let placeholder = |identification: Uuid, name: &str| {
+            self.placeholders.borrow_mut().push(Placeholder {
+                target: target.to_vec(),
+                class: *class,
+                identification,
+                name: name.to_string(),
+            });
+            Some(identification)
+        };
+
+        if target.first() == Some(&ROOT_METADICTIONARY_PID) {
+            let id = auid_from_urn(text)
+                .or_else(|| self.class_by_reference(text))
+                .or_else(|| self.type_by_reference(text).map(|t| t.identification))?;
+            let name = self
+                .class(&id)
+                .map(|c| c.name.as_str())
+                .or_else(|| self.resolve_type(&id).map(|t| t.name.as_str()))
+                .unwrap_or(text);
+            return placeholder(id, name);
+        }
+        if let Some(id) = auid_from_urn(text) {
+            return Some(id);
+        }
+        if let Some(known) = known_definition(text) {
+            if self.defined.contains(&known.identification) {
+                return Some(known.identification);
+            }
+            return placeholder(known.identification, known.name);
+        }
+        self.names.get(text).copied()
+    }
+
+    /// Add the definitions the document refers to but does not define to
+    /// the sets weak references to them look in, reporting errors against
+    /// `node`.
+    fn add_placeholders(&self, node: Node, root: &mut Object) -> Result<()> {
+        let mut added = HashSet::new();
+        for placeholder in self.placeholders.borrow().iter() {
+            if !added.insert((placeholder.target.clone(), placeholder.identification)) {
+                continue;
+            }
+            let definition = self.placeholder_object(placeholder)?;
+            self.add_to_set(node, root, &placeholder.target, definition)?;
+        }
+        Ok(())
+    }
+
+    fn placeholder_object(&self, placeholder: &Placeholder) -> Result<Object> {
+        let (identification_pid, name_pid) =
+            if self.is_kind_of(&placeholder.class, &class_ids::META_DEFINITION) {
+                (META_DEFINITION_IDENTIFICATION_PID, META_DEFINITION_NAME_PID)
+            } else {
+                (DEFINITION_IDENTIFICATION_PID, DEFINITION_NAME_PID)
+            };
+        let mut properties = vec![];
+        let values = [
+            (
+                identification_pid,
+                auid_bytes(&placeholder.identification).to_vec(),
+            ),
+            (name_pid, utf16_string(&placeholder.name)),
+        ];
+        for (pid, value) in values {
+            let property = self.property_by_pid(&placeholder.class, pid).ok_or(
+                AAFError::UndefinedProperty {
+                    path: PathBuf::from(&placeholder.name),
+                    pid,
+                },
+            )?;
+            properties.push(Property {
+                pid,
+                name: property.name.clone(),
+                value: Value::Data(value),
+            });
+        }
+        Ok(Object {
+            class: placeholder.class,
+            properties,
+        })
+    }
+
+    /// Add `member` to the strong reference set at the end of the pid path
+    /// `target`, creating the set and the objects on the way to it if the
+    /// document left them out.
+    fn add_to_set(
+        &self,
+        node: Node,
+        object: &mut Object,
+        target: &[OMPropertyId],
+        member: Object,
+    ) -> Result<()> {
+        let (pid, rest) = match target.split_first() {
+            Some(split) => split,
+            None => return Ok(()),
+        };
+        if !object.properties.iter().any(|p| p.pid == *pid) {
+            let property =
+                self.property_by_pid(&object.class, *pid)
+                    .ok_or(AAFError::UndefinedProperty {
+                        path: PathBuf::from("/"),
+                        pid: *pid,
+                    })?;
+            let referenced_class = match self.resolve_type(&property.type_id) {
+                Some(t) => self.referenced_class(node, t)?,
+                None => None,
+            };
+            let value = match (referenced_class, rest.is_empty()) {
+                (Some(class), true) => {
+                    let key_pid = self
+                        .unique_identifier(&class)
+                        .map_or(0, |p| p.local_identification);
+                    Value::StrongSet(key_pid, vec![])
+                }
+                (Some(class), false) => Value::Strong(Box::new(Object {
+                    class,
+                    properties: vec![],
+                })),
+                (None, _) => {
+                    return Err(AAFError::UnexpectedValue {
+                        path: PathBuf::from("/"),
+                        pid: *pid,
+                        expected: "a strong reference",
+                    })
+                }
+            };
+            object.properties.push(Property {
+                pid: *pid,
+                name: property.name.clone(),
+                value,
+            });
+        }
+
+        let property = object
+            .properties
+            .iter_mut()
+            .find(|p| p.pid == *pid)
+            .expect("the property was added");
+        match (&mut property.value, rest.is_empty()) {
+            (Value::StrongSet(_, members), true) => {
+                members.push(member);
+                Ok(())
+            }
+            (Value::Strong(next), false) => self.add_to_set(node, next, rest, member),
+            _ => Err(AAFError::UnexpectedValue {
+                path: PathBuf::from("/"),
+                pid: *pid,
+                expected: "a strong reference",
+            }),
+        }
+    }
+
+    /// The class a strong reference type, or a collection of them, refers
+    /// to.
+    ///
+    /// A collection of itself, which only a corrupt document defines, is
+    /// `InvalidXml` at `node`.
+    fn referenced_class(&self, node: Node, type_def: &TypeDefinition) -> Result<Option<Uuid>> {
+        let mut enclosing = vec![];
+        let mut type_def = type_def;
+        loop {
+            if enclosing.contains(&type_def.identification) {
+                return self.invalid(node, self_referential(type_def));
+            }
+            enclosing.push(type_def.identification);
+            type_def = match &type_def.kind {
+                TypeDefinitionKind::StrongObjectReference { referenced_class } => {
+                    return Ok(Some(*referenced_class))
+                }
+                TypeDefinitionKind::Set { element_type }
+                | TypeDefinitionKind::VariableArray { element_type } => {
+                    match self.resolve_type(element_type) {
+                        Some(t) => t,
+                        None => return Ok(None),
+                    }
+                }
+                _ => return Ok(None),
+            };
+        }
+    }
+
+    /// Encode the text or child elements of `node` as a data value of
+    /// `type_def`, as a little-endian file stores it.
+    ///
+    /// A type that is defined in terms of itself, which only a corrupt
+    /// document has, is `InvalidXml`.
+    fn encode(&self, node: Node, type_def: &TypeDefinition) -> Result<Vec<u8>> {
+        self.encode_within(node, type_def, &mut vec![])
+    }
+
+    /// Encode as `encode` does a value that is part of values of the types
+    /// `enclosing`, which it cannot itself be of.
+    fn encode_within(
+        &self,
+        node: Node,
+        type_def: &TypeDefinition,
+        enclosing: &mut Vec<Uuid>,
+    ) -> Result<Vec<u8>> {
+        if enclosing.contains(&type_def.identification) {
+            return self.invalid(node, self_referential(type_def));
+        }
+        enclosing.push(type_def.identification);
+        let retval = self.encode_type(node, type_def, enclosing);
+        enclosing.pop();
+        retval
+    }
+
+    fn encode_type(
+        &self,
+        node: Node,
+        type_def: &TypeDefinition,
+        enclosing: &mut Vec<Uuid>,
+    ) -> Result<Vec<u8>> {
+        let text = node_text(node);
+        let trimmed = text.trim();
+        let has_elements = node.children().any(|n| n.is_element());
+        let bad = |what: &str| self.invalid(node, format!("{:?} is not {}", trimmed, what));
+
+        if !has_elements {
+            let special = match type_def.identification {
+                type_ids::AUID => Some(auid_from_urn(trimmed).map(|id| auid_bytes(&id).to_vec())),
+                type_ids::MOB_ID_TYPE => {
+                    Some(MobID::from_str(trimmed).ok().map(|id| id.0.to_vec()))
+                }
+                type_ids::RATIONAL => Some(parse_rational(trimmed)),
+                type_ids::TIME_STAMP => Some(parse_timestamp(trimmed)),
+                type_ids::DATE_STRUCT => Some(parse_date(trimmed)),
+                type_ids::TIME_STRUCT => Some(parse_time(trimmed)),
+                type_ids::VERSION_TYPE => Some(parse_version(trimmed)),
+                _ => None,
+            };
+            if let Some(value) = special {
+                return value.map_or_else(|| bad(&type_def.name), Ok);
+            }
+        }
+
+        match &type_def.kind {
+            TypeDefinitionKind::Integer { size, is_signed } => parse_integer(trimmed)
+                .and_then(|value| encode_integer(value, *size, *is_signed))
+                .map_or_else(|| bad(&type_def.name), Ok),
+            TypeDefinitionKind::Enumeration {
+                element_type,
+                elements,
+            } => {
+                let value = elements
+                    .iter()
+                    .find(|(name, _)| name == trimmed)
+                    .or_else(|| {
+                        elements
+                            .iter()
+                            .find(|(name, _)| name.eq_ignore_ascii_case(trimmed))
+                    })
+                    .map(|(_, value)| *value as i128)
+                    .or_else(|| parse_integer(trimmed));
+                let encoded = match (value, self.resolve_type(element_type).map(|t| &t.kind)) {
+                    (Some(value), Some(TypeDefinitionKind::Integer { size, is_signed })) => {
+                        encode_integer(value, *size, *is_signed)
+                    }
+                    _ => None,
+                };
+                encoded.map_or_else(|| bad(&type_def.name), Ok)
+            }
+            TypeDefinitionKind::ExtendibleEnumeration { elements } => elements
+                .iter()
+                .find(|(name, _)| name == trimmed)
+                .map(|(_, value)| *value)
+                .or_else(|| auid_from_urn(trimmed))
+                .map(|id| auid_bytes(&id).to_vec())
+                .map_or_else(|| bad(&type_def.name), Ok),
+            TypeDefinitionKind::Record { members } => {
+                let mut retval = vec![];
+                for (name, member_type) in members {
+                    let member = match child_element(node, name) {
+                        Some(member) => member,
+                        None => return bad(&format!("a {} with a {}", type_def.name, name)),
+                    };
+                    let member_type = match self.resolve_type(member_type) {
+                        Some(t) => t,
+                        None => {
+                            return bad(&format!("a {} with a defined {}", type_def.name, name))
+                        }
+                    };
+                    retval.extend(self.encode_within(member, member_type, enclosing)?);
+                }
+                Ok(retval)
+            }
+            TypeDefinitionKind::FixedArray { element_type, .. }
+            | TypeDefinitionKind::VariableArray { element_type }
+            | TypeDefinitionKind::Set { element_type }
+            | TypeDefinitionKind::String { element_type } => {
+                let element_type = match self.resolve_type(element_type) {
+                    Some(t) => t,
+                    None => return bad(&type_def.name),
+                };
+                match (&element_type.kind, has_elements) {
+                    (TypeDefinitionKind::Character, _) => Ok(utf16_string(&text)),
+                    (TypeDefinitionKind::Integer { size: 1, .. }, false) => {
+                        parse_hex(trimmed).map_or_else(|| bad(&type_def.name), Ok)
+                    }
+                    _ => {
+                        let mut retval = vec![];
+                        for member in node.children().filter(Node::is_element) {
+                            retval.extend(self.encode_within(member, element_type, enclosing)?);
+                        }
+                        Ok(retval)
+                    }
+                }
+            }
+            TypeDefinitionKind::Character => match text.encode_utf16().next() {
+                Some(unit) => Ok(unit.to_le_bytes().to_vec()),
+                None => bad("a Character"),
+            },
+            TypeDefinitionKind::Rename { renamed_type } => match self.resolve_type(renamed_type) {
+                Some(renamed) => self.encode_within(node, renamed, enclosing),
+                None => bad(&type_def.name),
+            },
+            TypeDefinitionKind::Indirect | TypeDefinitionKind::Opaque => {
+                let actual = node
+                    .attributes()
+                    .find(|a| a.name() == "actualType")
+                    .and_then(|a| self.type_by_reference(a.value()));
+                let actual = match actual {
+                    Some(actual) => actual,
+                    None => return bad("a value with a defined actualType"),
+                };
+                let mut retval = vec![LITTLE_ENDIAN];
+                retval.extend(auid_bytes(&actual.identification));
+                if type_def.kind == TypeDefinitionKind::Opaque {
+                    match parse_hex(trimmed) {
+                        Some(bytes) => retval.extend(bytes),
+                        None => return bad("hexadecimal data"),
+                    }
+                } else {
+                    retval.extend(self.encode_within(node, actual, enclosing)?);
+                }
+                Ok(retval)
+            }
+            TypeDefinitionKind::Stream
+            | TypeDefinitionKind::StrongObjectReference { .. }
+            | TypeDefinitionKind::WeakObjectReference { .. } => bad("a data value"),
+        }
+    }
+
+    /// The type `text` refers to, by AUID or symbol.
+    fn type_by_reference(&self, text: &str) -> Option<&TypeDefinition> {
+        let id = auid_from_urn(text)
+            .or_else(|| self.symbols.get(text).copied())
+            .or_else(|| {
+                MetaDictionary::baseline()
+                    .type_by_name(text)
+                    .map(|t| t.identification)
+            })?;
+        self.resolve_type(&id)
+    }
+
+    /// The class `text` refers to, by AUID or symbol.
+    fn class_by_reference(&self, text: &str) -> Option<Uuid> {
+        auid_from_urn(text)
+            .or_else(|| self.symbols.get(text).copied())
+            .or_else(|| self.class_by_name(text).map(|c| c.identification))
+    }
+
+    /// Read the class, property and type definitions of the document's
+    /// extensions.
+    fn read_extensions(&mut self, extensions: Node) -> Result<()> {
+        let definitions: Vec<Node> = extensions
+            .descendants()
+            .filter(|n| {
+                let name = n.tag_name().name();
+                n.is_element()
+                    && (name == "ClassDefinition"
+                        || name == "PropertyDefinition"
+                        || name.starts_with("TypeDefinition"))
+            })
+            .collect();
+
+        // Definitions may refer to ones that follow them.
+        for node in &definitions {
+            let id = self.required_auid(*node, "Identification")?;
+            self.symbols.insert(symbol(*node)?, id);
+        }
+        for node in definitions
+            .iter()
+            .filter(|n| n.tag_name().name().starts_with("TypeDefinition"))
+        {
+            let type_def = self.read_type_definition(*node)?;
+            self.md.insert_type(type_def);
+        }
+        for node in definitions
+            .iter()
+            .filter(|n| n.tag_name().name() == "ClassDefinition")
+        {
+            let class = ClassDefinition {
+                identification: self.required_auid(*node, "Identification")?,
+                name: definition_name(*node)?,
+                description: child_text(*node, "Description"),
+                parent: self.required_class(*node, "ParentClass")?,
+                is_concrete: child_text(*node, "IsConcrete")
+                    .and_then(|t| parse_boolean(&t))
+                    .unwrap_or(true),
+                properties: vec![],
+            };
+            self.md.insert_class(class);
+        }
+
+        let property_nodes: Vec<Node> = definitions
+            .iter()
+            .copied()
+            .filter(|n| n.tag_name().name() == "PropertyDefinition")
+            .collect();
+        // Properties without a pid are given unused dynamic pids, counting
+        // down from 0xffff, once those given explicitly are known.
+        let mut explicit_pids = vec![];
+        let mut used = HashSet::new();
+        for node in &property_nodes {
+            let pid = match child_text(*node, "LocalIdentification") {
+                Some(text) => {
+                    match parse_integer(&text).and_then(|v| OMPropertyId::try_from(v).ok()) {
+                        Some(pid) if used.insert(pid) => Some(pid),
+                        Some(pid) => {
+                            return self.invalid(*node, format!("pid 0x{:04x} is used twice", pid))
+                        }
+                        None => return self.invalid(*node, format!("{:?} is not a pid", text)),
+                    }
+                }
+                None => None,
+            };
+            explicit_pids.push(pid);
+        }
+
+        let mut next_dynamic_pid = Some(0xffff);
+        for (node, explicit_pid) in property_nodes.iter().zip(explicit_pids) {
+            let member_of = self.required_class(*node, "MemberOf")?;
+            let local_identification = match explicit_pid {
+                Some(pid) => pid,
+                None => loop {
+                    match next_dynamic_pid {
+                        Some(pid) if pid >= MIN_DYNAMIC_PID => {
+                            next_dynamic_pid = pid.checked_sub(1);
+                            if used.insert(pid) {
+                                break pid;
+                            }
+                        }
+                        _ => {
+                            return self
+                                .invalid(*node, "there are no dynamic pids left".to_string())
+                        }
+                    }
+                },
+            };
+            let type_id = match child_text(*node, "Type")
+                .and_then(|t| self.type_by_reference(&t).map(|t| t.identification))
+            {
+                Some(id) => id,
+                None => return self.invalid(*node, "a property has an undefined Type".to_string()),
+            };
+            let property = PropertyDefinition {
+                identification: self.required_auid(*node, "Identification")?,
+                name: definition_name(*node)?,
+                description: child_text(*node, "Description"),
+                type_id,
+                is_optional: child_text(*node, "IsOptional")
+                    .and_then(|t| parse_boolean(&t))
+                    .unwrap_or(true),
+                local_identification,
+                is_unique_identifier: child_text(*node, "IsUniqueIdentifier")
+                    .and_then(|t| parse_boolean(&t))
+                    .unwrap_or(false),
+            };
+
+            // Properties added to a baseline class extend a copy of it.
+            let mut class = match self.class(&member_of) {
+                Some(class) => class.clone(),
+                None => return self.invalid(*node, "MemberOf is not a class".to_string()),
+            };
+            class.properties.push(property);
+            self.md.insert_class(class);
+        }
+        Ok(())
+    }
+
+    fn read_type_definition(&self, node: Node) -> Result<TypeDefinition> {
+        let type_ref = |name: &str| -> Result<Uuid> {
+            match child_text(node, name).and_then(|t| self.type_by_reference(&t)) {
+                Some(t) => Ok(t.identification),
+                None => match child_text(node, name)
+                    .and_then(|t| auid_from_urn(&t).or_else(|| self.symbols.get(&t).copied()))
+                {
+                    Some(id) => Ok(id),
+                    None => self.invalid(node, format!("{} is not a defined type", name)),
+                },
+            }
+        };
+        let integer = |name: &str| -> Result<i128> {
+            match child_text(node, name).and_then(|t| parse_integer(&t)) {
+                Some(value) => Ok(value),
+                None => self.invalid(node, format!("{} is not an integer", name)),
+            }
+        };
+
+        let kind = match node.tag_name().name() {
+            "TypeDefinitionInteger" => TypeDefinitionKind::Integer {
+                size: match integer("Size")? {
+                    size @ (1 | 2 | 4 | 8) => size as u8,
+                    size => return self.invalid(node, format!("{} is not an integer size", size)),
+                },
+                is_signed: child_text(node, "IsSigned")
+                    .and_then(|t| parse_boolean(&t))
+                    .unwrap_or(false),
+            },
+            "TypeDefinitionCharacter" => TypeDefinitionKind::Character,
+            "TypeDefinitionStream" => TypeDefinitionKind::Stream,
+            "TypeDefinitionIndirect" => TypeDefinitionKind::Indirect,
+            "TypeDefinitionOpaque" => TypeDefinitionKind::Opaque,
+            "TypeDefinitionString" => TypeDefinitionKind::String {
+                element_type: type_ref("ElementType")?,
+            },
+            "TypeDefinitionFixedArray" => TypeDefinitionKind::FixedArray {
+                element_type: type_ref("ElementType")?,
+                element_count: match u32::try_from(integer("ElementCount")?) {
+                    Ok(count) => count,
+                    Err(_) => {
+                        return self.invalid(node, "ElementCount is out of range".to_string())
+                    }
+                },
+            },
+            "TypeDefinitionVariableArray" => TypeDefinitionKind::VariableArray {
+                element_type: type_ref("ElementType")?,
+            },
+            "TypeDefinitionSet" => TypeDefinitionKind::Set {
+                element_type: type_ref("ElementType")?,
+            },
+            "TypeDefinitionRename" => TypeDefinitionKind::Rename {
+                renamed_type: type_ref("RenamedType")?,
+            },
+            "TypeDefinitionEnumeration" => {
+                let mut elements = vec![];
+                for (name, value) in self.pairs(node, "Elements", "Value")? {
+                    match parse_integer(&value) {
+                        Some(value) => elements.push((name, value as AAFInt64)),
+                        None => {
+                            return self.invalid(node, format!("{:?} is not an integer", value))
+                        }
+                    }
+                }
+                TypeDefinitionKind::Enumeration {
+                    element_type: type_ref("ElementType")?,
+                    elements,
+                }
+            }
+            "TypeDefinitionExtendibleEnumeration" => {
+                let mut elements = vec![];
+                for (name, value) in self.pairs(node, "Elements", "Value")? {
+                    match auid_from_urn(&value) {
+                        Some(value) => elements.push((name, value)),
+                        None => return self.invalid(node, format!("{:?} is not an AUID", value)),
+                    }
+                }
+                TypeDefinitionKind::ExtendibleEnumeration { elements }
+            }
+            "TypeDefinitionRecord" => {
+                let mut members = vec![];
+                for (name, member_type) in self.pairs(node, "Members", "Type")? {
+                    match self
+                        .type_by_reference(&member_type)
+                        .map(|t| t.identification)
+                        .or_else(|| {
+                            auid_from_urn(&member_type)
+                                .or_else(|| self.symbols.get(&member_type).copied())
+                        }) {
+                        Some(id) => members.push((name, id)),
+                        None => {
+                            return self
+                                .invalid(node, format!("{:?} is not a defined type", member_type))
+                        }
+                    }
+                }
+                TypeDefinitionKind::Record { members }
+            }
+            "TypeDefinitionStrongObjectReference" => TypeDefinitionKind::StrongObjectReference {
+                referenced_class: self.required_class(node, "ReferencedType")?,
+            },
+            "TypeDefinitionWeakObjectReference" => {
+                let mut target_set = vec![];
+                let mut class: Option<Uuid> = None;
+                let targets: Vec<Node> = child_element(node, "TargetSet")
+                    .map(|t| t.children().filter(Node::is_element).collect())
+                    .unwrap_or_default();
+                for target in targets {
+                    let text = node_text(target);
+                    let id = match self.target_property(text.trim(), class) {
+                        Some(id) => id,
+                        None => {
+                            return self
+                                .invalid(target, format!("{:?} is not a property", text.trim()))
+                        }
+                    };
+                    class = match id {
+                        ROOT_METADICTIONARY_ID => Some(class_ids::META_DICTIONARY),
+                        ROOT_HEADER_ID => Some(class_ids::HEADER),
+                        _ => match self
+                            .property_by_id(&id)
+                            .and_then(|p| self.resolve_type(&p.type_id))
+                        {
+                            Some(t) => self.referenced_class(target, t)?,
+                            None => None,
+                        },
+                    };
+                    target_set.push(id);
+                }
+                TypeDefinitionKind::WeakObjectReference {
+                    referenced_class: self.required_class(node, "ReferencedType")?,
+                    target_set,
+                }
+            }
+            name => return self.invalid(node, format!("{} is not a kind of type", name)),
+        };
+
+        Ok(TypeDefinition {
+            identification: self.required_auid(node, "Identification")?,
+            name: definition_name(node)?,
+            description: child_text(node, "Description"),
+            kind,
+        })
+    }
+
+    /// The AUID of the property `text` refers to, by AUID or by name as a
+    /// property of the root object, or of `class` when it is given.
+    fn target_property(&self, text: &str, class: Option<Uuid>) -> Option<Uuid> {
+        if let Some(id) = auid_from_urn(text).or_else(|| self.symbols.get(text).copied()) {
+            return Some(id);
+        }
+        match (class, text) {
+            (None, "MetaDictionary") => Some(ROOT_METADICTIONARY_ID),
+            (None, "Header") => Some(ROOT_HEADER_ID),
+            (Some(class), _) => self
+                .property_by_name(&class, text)
+                .map(|p| p.identification),
+            _ => None,
+        }
+    }
+
+    /// The texts of the `Name` children of the element `list` of `node`,
+    /// paired with the texts of the `value` children that follow each.
+    fn pairs(&self, node: Node, list: &str, value: &str) -> Result<Vec<(String, String)>> {
+        let mut retval = vec![];
+        let mut name = None;
+        if let Some(list) = child_element(node, list) {
+            for child in list.children().filter(Node::is_element) {
+                let text = node_text(child).trim().to_string();
+                match child.tag_name().name() {
+                    "Name" => name = Some(text),
+                    n if n == value => match name.take() {
+                        Some(name) => retval.push((name, text)),
+                        None => return self.invalid(child, format!("{} without a Name", value)),
+                    },
+                    _ => {}
+                }
+            }
+        }
+        Ok(retval)
+    }
+
+    fn required_auid(&self, node: Node, name: &str) -> Result<Uuid> {
+        match child_text(node, name).and_then(|t| auid_from_urn(&t)) {
+            Some(id) => Ok(id),
+            None => self.invalid(node, format!("{} is not an AUID", name)),
+        }
+    }
+
+    fn required_class(&self, node: Node, name: &str) -> Result<Uuid> {
+        match child_text(node, name).and_then(|t| self.class_by_reference(t.trim())) {
+            Some(id) => Ok(id),
+            None => self.invalid(node, format!("{} is not a class", name)),
+        }
+    }
+}
+
+/// The objects of a document, stored into a compound file.
+struct Storage {
+    cfb: cfb::CompoundFile<Cursor<Vec<u8>>>,
+    /// The pid paths of the sets weak references refer to, indexed by tag.
+    tags: Vec<Vec<OMPropertyId>>,
+}
+
+impl Storage {
+    fn store_object(&mut self, path: &Path, object: &Object) -> Result<()> {
+        self.cfb
+            .set_storage_clsid(path, object.class)
+            .at_path(path)?;
+
+        let mut stored = vec![];
+        for property in &object.properties {
+            let name = storage_name(&property.name, property.pid);
+            let (stored_form, value) = match &property.value {
+                Value::Data(bytes) => (SF_DATA, bytes.clone()),
+                Value::Stream(bytes) => {
+                    self.write_stream(&path.join(&name), bytes)?;
+                    let mut value = vec![STREAM_UNSPECIFIED_BYTE_ORDER];
+                    value.extend(utf16_string(&name));
+                    (SF_DATA_STREAM, value)
+                }
+                Value::Strong(member) => {
+                    self.store_member(&path.join(&name), member)?;
+                    (SF_STRONG_OBJECT_REF, utf16_string(&name))
+                }
+                Value::StrongVector(members) => {
+                    let mut index = vector_index_header(members.len());
+                    for (local_key, member) in members.iter().enumerate() {
+                        let member_path = path.join(format!("{}{{{:x}}}", name, local_key));
+                        self.store_member(&member_path, member)?;
+                        index.extend((local_key as u32).to_le_bytes());
+                    }
+                    self.write_stream(&path.join(format!("{} index", name)), &index)?;
+                    (SF_STRONG_OBJECT_REF_VECTOR, utf16_string(&name))
+                }
+                Value::StrongSet(key_pid, members) => {
+                    let keys: Vec<Vec<u8>> = members
+                        .iter()
+                        .map(|m| set_key(m, *key_pid))
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| AAFError::MissingProperty {
+                            path: path.join(&name),
+                            pid: *key_pid,
+                        })?;
+                    let key_size = keys.first().map_or(0, |k| k.len());
+                    let mut index = vector_index_header(members.len());
+                    index.extend(key_pid.to_le_bytes());
+                    index.push(key_size as OMKeySize);
+                    for (local_key, (member, key)) in members.iter().zip(&keys).enumerate() {
+                        let member_path = path.join(format!("{}{{{:x}}}", name, local_key));
+                        self.store_member(&member_path, member)?;
+                        index.extend((local_key as u32).to_le_bytes());
+                        index.extend(1u32.to_le_bytes());
+                        index.extend(key);
+                    }
+                    self.write_stream(&path.join(format!("{} index", name)), &index)?;
+                    (SF_STRONG_OBJECT_REF_SET, utf16_string(&name))
+                }
+                Value::Weak(weak) => {
+                    let key = weak.keys.first().cloned().unwrap_or_default();
+                    let mut value = self.tag(&weak.target).to_le_bytes().to_vec();
+                    value.extend(weak.key_pid.to_le_bytes());
+                    value.push(key.len() as OMKeySize);
+                    value.extend(key);
+                    (SF_WEAK_OBJECT_REF, value)
+                }
+                Value::WeakVector(weak) | Value::WeakSet(weak) => {
+                    let key_size = weak.keys.first().map_or(0, |k| k.len());
+                    let mut index = (weak.keys.len() as u32).to_le_bytes().to_vec();
+                    index.extend(self.tag(&weak.target).to_le_bytes());
+                    index.extend(weak.key_pid.to_le_bytes());
+                    index.push(key_size as OMKeySize);
+                    for key in &weak.keys {
+                        index.extend(key);
+                    }
+                    self.write_stream(&path.join(format!("{} index", name)), &index)?;
+                    match property.value {
+                        Value::WeakVector(_) => (SF_WEAK_OBJECT_REF_VECTOR, utf16_string(&name)),
+                        _ => (SF_WEAK_OBJECT_REF_SET, utf16_string(&name)),
+                    }
+                }
+            };
+            stored.push((property.pid, stored_form, value));
+        }
+
+        let properties_path = path.join("properties");
+        let mut stream = vec![LITTLE_ENDIAN, PROPERTIES_VERSION];
+        stream.extend((stored.len() as OMPropertyCount).to_le_bytes());
+        for (pid, stored_form, value) in &stored {
+            let size =
+                OMPropertySize::try_from(value.len()).map_err(|_| AAFError::InvalidValue {
+                    type_name: "a property of at most 65535 bytes",
+                    len: value.len(),
+                })?;
+            stream.extend(pid.to_le_bytes());
+            stream.extend(stored_form.to_le_bytes());
+            stream.extend(size.to_le_bytes());
+        }
+        for (_, _, value) in stored {
+            stream.extend(value);
+        }
+        self.write_stream(&properties_path, &stream)
+    }
+
+    fn store_member(&mut self, path: &Path, member: &Object) -> Result<()> {
+        self.cfb.create_storage(path).at_path(path)?;
+        self.store_object(path, member)
+    }
+
+    fn write_stream(&mut self, path: &Path, data: &[u8]) -> Result<()> {
+        self.cfb
+            .create_stream(path)
+            .and_then(|mut stream| stream.write_all(data))
+            .at_path(path)
+    }
+
+    /// The tag of the weak reference target set at the pid path `target`.
+    fn tag(&mut self, target: &[OMPropertyId]) -> OMPropertyTag {
+        match self.tags.iter().position(|t| t == target) {
+            Some(tag) => tag as OMPropertyTag,
+            None => {
+                self.tags.push(target.to_vec());
+                (self.tags.len() - 1) as OMPropertyTag
+            }
+        }
+    }
+
+    /// Write the referenced properties table and give up the compound file.
+    fn finish(mut self) -> Result<cfb::CompoundFile<Cursor<Vec<u8>>>> {
+        let pid_count: usize = self.tags.iter().map(|t| t.len() + 1).sum();
+        let mut table = vec![LITTLE_ENDIAN];
+        table.extend((self.tags.len() as OMPropertyCount).to_le_bytes());
+        table.extend((pid_count as u32).to_le_bytes());
+        for target in &self.tags {
+            for pid in target.iter().chain([&0]) {
+                table.extend(pid.to_le_bytes());
+            }
+        }
+        self.write_stream(Path::new("/referenced properties"), &table)?;
+        Ok(self.cfb)
+    }
+}
+
+/// Why a value of `type_def`, a type defined in terms of itself, cannot be
+/// read.
+fn self_referential(type_def: &TypeDefinition) -> String {
+    format!("{} is defined in terms of itself", type_def.name)
+}
+
+/// The name of the storage, stream or index of a property, short enough
+/// for the names of its members.
+fn storage_name(name: &str, pid: OMPropertyId) -> String {
+    let suffix = format!("-{:x}", pid);
+    // Leave room for the local key of a member, "{ffffffff}".
+    let len = (MAX_STORAGE_NAME_LEN - 10 - suffix.len()).min(name.len());
+    let prefix: String = name.chars().take(len).collect();
+    prefix + &suffix
+}
+
+/// The entry count and free keys that begin a vector or set index.
+fn vector_index_header(len: usize) -> Vec<u8> {
+    let mut index = (len as u32).to_le_bytes().to_vec();
+    index.extend((len as u32).to_le_bytes());
+    index.extend(u32::MAX.to_le_bytes());
+    index
+}
+
+/// The key a set member is indexed by: the value of its `key_pid`
+/// property, or nothing if the set is not keyed.
+fn set_key(member: &Object, key_pid: OMPropertyId) -> Option<Vec<u8>> {
+    if key_pid == 0 {
+        return Some(vec![]);
+    }
+    member
+        .properties
+        .iter()
+        .find(|p| p.pid == key_pid)
+        .and_then(|p| match &p.value {
+            Value::Data(bytes) => Some(bytes.clone()),
+            _ => None,
+        })
+}
+
+/// `text` as a null-terminated UTF-16LE string.
+fn utf16_string(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain([0])
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+/// The declared files of the unparsed entities of a document's DTD, by
+/// entity name.
+fn unparsed_entities(text: &str) -> HashMap<String, String> {
+    let mut retval = HashMap::new();
+    for declaration in text.split("<!ENTITY").skip(1) {
+        let declaration = declaration.split('>').next().unwrap_or_default();
+        let mut words = declaration.split_whitespace();
+        let (name, keyword) = (words.next(), words.next());
+        if keyword != Some("SYSTEM") {
+            continue;
+        }
+        let rest = declaration[declaration.find("SYSTEM").unwrap_or(0) + 6..].trim_start();
+        let quote = match rest.chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => continue,
+        };
+        if let (Some(name), Some(location)) = (name, rest[1..].split(quote).next()) {
+            retval.insert(name.to_string(), location.to_string());
+        }
+    }
+    retval
+}
+
+fn child_element<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|n| n.is_element() && n.tag_name().name() == name)
+}
+
+/// The text directly inside `node`.
+fn node_text(node: Node) -> String {
+    node.children()
+        .filter(Node::is_text)
+        .filter_map(|n| n.text())
+        .collect()
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    child_element(node, name).map(|n| node_text(n).trim().to_string())
+}
+
+/// The symbol of an extension's definition, which names its elements.
+fn symbol(node: Node) -> Result<String> {
+    match child_text(node, "Symbol").or_else(|| child_text(node, "Name")) {
+        Some(symbol) => Ok(symbol),
+        None => Err(AAFError::InvalidXml {
+            line: node.document().text_pos_at(node.range().start).row,
+            reason: format!("a {} has no Symbol", node.tag_name().name()),
+        }),
+    }
+}
+
+/// The name of an extension's definition, or its symbol if it has none.
+fn definition_name(node: Node) -> Result<String> {
+    match child_text(node, "Name") {
+        Some(name) => Ok(name),
+        None => symbol(node),
+    }
+}
+
+fn parse_boolean(text: &str) -> Option<bool> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// A decimal integer, or a hexadecimal one beginning `0x`.
+fn parse_integer(text: &str) -> Option<i128> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i128>().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// `value` as a little-endian integer of `size` bytes, if it is in range.
+fn encode_integer(value: i128, size: u8, is_signed: bool) -> Option<Vec<u8>> {
+    if !matches!(size, 1 | 2 | 4 | 8) {
+        return None;
+    }
+    let bits = size as u32 * 8;
+    let in_range = match is_signed {
+        true => value >= -(1i128 << (bits - 1)) && value < (1i128 << (bits - 1)),
+        false => value >= 0 && value < (1i128 << bits),
+    };
+    if !in_range {
+        return None;
+    }
+    Some(value.to_le_bytes()[..size as usize].to_vec())
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// A `Rational` written `numerator/denominator`.
+fn parse_rational(text: &str) -> Option<Vec<u8>> {
+    let (numerator, denominator) = text.split_once('/')?;
+    let mut retval = numerator.trim().parse::<i32>().ok()?.to_le_bytes().to_vec();
+    retval.extend(denominator.trim().parse::<i32>().ok()?.to_le_bytes());
+    Some(retval)
+}
+
+/// A `DateStruct` written `YYYY-MM-DD`.
+fn parse_date(text: &str) -> Option<Vec<u8>> {
+    let mut parts = text.trim().splitn(3, '-');
+    let year: i16 = parts.next()?.parse().ok()?;
+    let month: u8 = parts.next()?.parse().ok()?;
+    let day: u8 = parts.next()?.parse().ok()?;
+    let mut retval = year.to_le_bytes().to_vec();
+    retval.extend([month, day]);
+    Some(retval)
+}
+
+/// A `TimeStruct` written `hh:mm:ss.ff`, where `ff` is the fraction as
+/// it is stored, optionally followed by `Z`.
+fn parse_time(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim().trim_end_matches('Z');
+    let (time, fraction) = text.split_once('.').unwrap_or((text, "0"));
+    let mut retval: Vec<u8> = time
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    if retval.len() != 3 {
+        return None;
+    }
+    retval.push(fraction.parse().ok()?);
+    Some(retval)
+}
+
+/// A `TimeStamp` written `YYYY-MM-DDThh:mm:ss.ffZ`.
+fn parse_timestamp(text: &str) -> Option<Vec<u8>> {
+    let (date, time) = text.split_once('T')?;
+    let mut retval = parse_date(date)?;
+    retval.extend(parse_time(time)?);
+    Some(retval)
+}
+
+/// A `VersionType` written `major.minor`.
+fn parse_version(text: &str) -> Option<Vec<u8>> {
+    let (major, minor) = text.split_once('.')?;
+    Some(vec![
+        major.trim().parse::<i8>().ok()? as u8,
+        minor.trim().parse::<i8>().ok()? as u8,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aaf::classes::{Component, ContentStorage, MobSlot, Segment};
+    use crate::aaf::values::AAFValue;
+
+    const COMPOSITION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE AAF [
+  <!ENTITY tone SYSTEM "tone.raw" NDATA DataStream>
+]>
+<AAF xmlns="http://www.smpte-ra.org/schemas/2001-2/2007/aaf"
+     xmlns:aaf="http://www.smpte-ra.org/schemas/2001-1b/2007/aaf" version="1.1">
+  <Extensions>
+    <Extension>
+      <Definitions>
+        <PropertyDefinition>
+          <Identification>urn:uuid:5b0c7a12-3c4e-4b1a-9a1e-2f7d4e6a8c01</Identification>
+          <Symbol>ReviewNote</Symbol>
+          <Type>String</Type>
+          <MemberOf>Mob</MemberOf>
+          <IsOptional>true</IsOptional>
+        </PropertyDefinition>
+      </Definitions>
+    </Extension>
+  </Extensions>
+  <Header>
+    <ByteOrder>0x4949</ByteOrder>
+    <LastModified>2021-11-09T07:27:58.00Z</LastModified>
+    <Version>1.1</Version>
+    <ObjectModelVersion>1</ObjectModelVersion>
+    <IdentificationList>
+      <Identification>
+        <CompanyName>Example</CompanyName>
+        <ProductName>rust-aaf tests</ProductName>
+        <ProductVersionString>1.0</ProductVersionString>
+        <ProductID>urn:uuid:b3b398a5-1c90-11d4-8053-080036210804</ProductID>
+        <Date>2021-11-09T07:27:58.00Z</Date>
+        <GenerationAUID>urn:uuid:00000000-0000-0000-0000-000000000001</GenerationAUID>
+      </Identification>
+    </IdentificationList>
+    <Content>
+      <ContentStorage>
+        <Mobs>
+          <CompositionMob>
+            <MobID>urn:smpte:umid:060a2b34.01010105.01010f20.13000000.00000000.00000000.00000000.00000001</MobID>
+            <Name>Sequence 1</Name>
+            <LastModified>2021-11-09T07:27:58.00Z</LastModified>
+            <CreationTime>2021-11-09T07:27:58.00Z</CreationTime>
+            <ReviewNote>Check the fade</ReviewNote>
+            <Slots>
+              <TimelineMobSlot>
+                <SlotID>1</SlotID>
+                <SlotName>A1</SlotName>
+                <EditRate>48000/1</EditRate>
+                <Origin>0</Origin>
+                <Segment>
+                  <Sequence>
+                    <DataDefinition>DataDef_Sound</DataDefinition>
+                    <Length>2001</Length>
+                    <Components>
+                      <SourceClip>
+                        <DataDefinition>DataDef_Sound</DataDefinition>
+                        <Length>2001</Length>
+                        <StartTime>0</StartTime>
+                        <SourceID>urn:smpte:umid:060a2b34.01010105.01010f20.13000000.00000000.00000000.00000000.00000002</SourceID>
+                        <SourceMobSlotID>1</SourceMobSlotID>
+                      </SourceClip>
+                    </Components>
+                  </Sequence>
+                </Segment>
+              </TimelineMobSlot>
+            </Slots>
+          </CompositionMob>
+        </Mobs>
+        <EssenceData>
+          <EssenceData>
+            <MobID>urn:smpte:umid:060a2b34.01010105.01010f20.13000000.00000000.00000000.00000000.00000002</MobID>
+            <Data aaf:stream="tone"/>
+            <SampleIndex>AAECAw==</SampleIndex>
+          </EssenceData>
+        </EssenceData>
+      </ContentStorage>
+    </Content>
+    <Dictionary>
+      <Dictionary>
+        <DataDefinitions>
+          <DataDefinition>
+            <Identification>urn:smpte:ul:060e2b34.04010101.01030202.01000000</Identification>
+            <Name>Picture</Name>
+            <Description>Picture essence</Description>
+          </DataDefinition>
+        </DataDefinitions>
+      </Dictionary>
+    </Dictionary>
+  </Header>
+</AAF>
+"#;
+
+    /// A directory of its own under the system temp directory, removed
+    /// when it is dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+            let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let dir = std::env::temp_dir().join(format!(
+                "rust-aaf-{}-{}-{}",
+                name,
+                std::process::id(),
+                count
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A directory holding the stream `COMPOSITION` refers to.
+    fn composition_dir() -> TempDir {
+        let dir = TempDir::new("xml");
+        std::fs::write(dir.join("tone.raw"), [1u8, 2, 3, 4]).unwrap();
+        dir
+    }
+
+    fn composition() -> AAFFile<Cursor<Vec<u8>>> {
+        AAFFile::from_xml(COMPOSITION, &composition_dir()).unwrap()
+    }
+
+    #[test]
+    fn test_read_xml() {
+        let file = composition();
+        assert_eq!(file.format().signature, Some(AAFSignature::AafXml));
+        let header = file.header().unwrap();
+        assert_eq!(header.byte_order().unwrap(), 0x4949);
+        assert_eq!(
+            header
+                .last_identification()
+                .unwrap()
+                .company_name()
+                .unwrap(),
+            "Example"
+        );
+
+        let content: ContentStorage<_> = header.content().unwrap();
+        let mobs = content.mobs().unwrap();
+        assert_eq!(mobs.len(), 1);
+        let mob = &mobs[0];
+        assert_eq!(mob.name().unwrap().as_deref(), Some("Sequence 1"));
+        let slots = mob.slots().unwrap();
+        let slot = match &slots[0] {
+            MobSlot::TimelineMobSlot(slot) => slot,
+            _ => panic!("not a TimelineMobSlot"),
+        };
+        assert_eq!(
+            slot.edit_rate().unwrap(),
+            Rational {
+                numerator: 48000,
+                denominator: 1
+            }
+        );
+        let sequence = match slot.segment().unwrap() {
+            Segment::Sequence(sequence) => sequence,
+            _ => panic!("not a Sequence"),
+        };
+        // DataDef_Sound is added to the Dictionary for the references to it.
+        assert_eq!(sequence.data_definition().unwrap().name().unwrap(), "Sound");
+        let clip = match &sequence.components().unwrap()[0] {
+            Component::SourceClip(clip) => clip.clone(),
+            _ => panic!("not a SourceClip"),
+        };
+        assert_eq!(clip.length().unwrap(), Some(2001));
+
+        // Weak references to definitions the document has resolve to them.
+        let dictionary = header.dictionary().unwrap();
+        let names: Vec<String> = dictionary
+            .data_definitions()
+            .unwrap()
+            .iter()
+            .map(|d| d.name().unwrap())
+            .collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"Picture".to_string()));
+    }
+
+    #[test]
+    fn test_xml_values() {
+        let mut file = composition();
+        let mob = file
+            .objects()
+            .map(|entry| entry.unwrap().object)
+            .find(|object| object.auid == class_ids::COMPOSITION_MOB)
+            .unwrap();
+
+        let md = file.meta_dictionary().unwrap();
+        let note = md.class_by_name("Mob").unwrap().properties.last().unwrap();
+        assert_eq!(note.name, "ReviewNote");
+        assert_eq!(note.local_identification, 0xffff);
+        assert_eq!(
+            file.get_typed_value(&mob, 0xffff).unwrap(),
+            Some(AAFValue::String("Check the fade".to_string()))
+        );
+        match file.get_typed_value(&mob, 0x4404).unwrap() {
+            Some(AAFValue::Record(members)) => assert_eq!(members.len(), 2),
+            other => panic!("LastModified decoded as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_xml_streams() {
+        let file = composition();
+        let header = file.header().unwrap();
+        let content = header.content().unwrap();
+        let essence = &content.essence_data().unwrap()[0];
+        let mut data = vec![];
+        std::io::Read::read_to_end(&mut essence.data().unwrap(), &mut data).unwrap();
+        assert_eq!(data, vec![1, 2, 3, 4]);
+        let mut index = vec![];
+        let sample_index = essence.sample_index().unwrap().unwrap();
+        std::io::Read::read_to_end(&mut { sample_index }, &mut index).unwrap();
+        assert_eq!(index, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_invalid_xml() {
+        let not_aaf = AAFFile::from_xml("<NotAAF/>", Path::new("."));
+        assert!(matches!(not_aaf, Err(AAFError::InvalidXml { line: 1, .. })));
+
+        // The essence stream is missing from the directory.
+        let missing = AAFFile::from_xml(COMPOSITION, Path::new("/nonexistent"));
+        assert!(matches!(missing, Err(AAFError::Io { .. })));
+
+        let unknown = COMPOSITION.replace("<SlotName>A1</SlotName>", "<Colour>red</Colour>");
+        match AAFFile::from_xml(&unknown, Path::new(".")) {
+            Err(AAFError::InvalidXml { line, reason }) => {
+                assert!(reason.contains("Colour"), "{}", reason);
+                assert!(line > 1);
+            }
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("read a document with an unknown property"),
+        }
+
+        // Streams outside the document's directory are not read.
+        let dir = composition_dir();
+        let outside_dir = TempDir::new("outside");
+        let outside = outside_dir.join("outside.raw");
+        std::fs::write(&outside, [5u8]).unwrap();
+        for location in [
+            outside.to_string_lossy().into_owned(),
+            format!("file://{}", outside.display()),
+            format!(
+                "../{}/outside.raw",
+                outside_dir.file_name().unwrap().to_string_lossy()
+            ),
+        ] {
+            let document = COMPOSITION.replace("\"tone.raw\"", &format!("\"{}\"", location));
+            match AAFFile::from_xml(&document, &dir) {
+                Err(AAFError::InvalidXml { reason, .. }) => {
+                    assert!(reason.contains("outside"), "{}", reason)
+                }
+                Err(e) => panic!("unexpected error {}", e),
+                Ok(_) => panic!("read the stream {}", location),
+            }
+        }
+
+        let dangling = COMPOSITION.replace(
+            "<DataDefinition>DataDef_Sound</DataDefinition>\n                    <Length>",
+            "<DataDefinition>Smell</DataDefinition>\n                    <Length>",
+        );
+        assert!(matches!(
+            AAFFile::from_xml(&dangling, Path::new(".")),
+            Err(AAFError::InvalidXml { .. })
+        ));
+    }
+
+    #[test]
+    fn test_dynamic_pids() {
+        // A second property on Mob, given the pid ReviewNote would have had
+        let property = |n: u8, pid: &str| {
+            format!(
+                "<PropertyDefinition>\
+                 <Identification>urn:uuid:5b0c7a12-3c4e-4b1a-9a1e-2f7d4e6a8c1{n}</Identification>\
+                 <Symbol>Flag{n}</Symbol><Type>String</Type><MemberOf>Mob</MemberOf>\
+                 <LocalIdentification>{pid}</LocalIdentification>\
+                 <IsOptional>true</IsOptional></PropertyDefinition>\n      </Definitions>"
+            )
+        };
+        let document = COMPOSITION.replace("      </Definitions>", &property(1, "0xffff"));
+        let mut file = AAFFile::from_xml(&document, &composition_dir()).unwrap();
+        let md = file.meta_dictionary().unwrap();
+        let pids: Vec<(String, OMPropertyId)> = md
+            .class_by_name("Mob")
+            .unwrap()
+            .properties
+            .iter()
+            .map(|p| (p.name.clone(), p.local_identification))
+            .filter(|(_, pid)| *pid >= MIN_DYNAMIC_PID)
+            .collect();
+        assert_eq!(
+            pids,
+            vec![
+                ("ReviewNote".to_string(), 0xfffe),
+                ("Flag1".to_string(), 0xffff)
+            ]
+        );
+
+        let twice = document.replace("      </Definitions>", &property(2, "0xffff"));
+        match AAFFile::from_xml(&twice, &composition_dir()) {
+            Err(AAFError::InvalidXml { reason, .. }) => assert!(reason.contains("twice")),
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("read a document with a pid used twice"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_extensions() {
+        // ReviewNote, of an extension type `type_def` defines as Note
+        let with_type = |type_def: &str| {
+            COMPOSITION
+                .replace("<Type>String</Type>", "<Type>Note</Type>")
+                .replace(
+                    "      </Definitions>",
+                    &format!("        {}\n      </Definitions>", type_def),
+                )
+        };
+        let invalid = |document: String| match AAFFile::from_xml(&document, &composition_dir()) {
+            Err(AAFError::InvalidXml { reason, .. }) => reason,
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("read a document with an invalid extension"),
+        };
+        let id = "<Identification>urn:uuid:5b0c7a12-3c4e-4b1a-9a1e-2f7d4e6a8c02</Identification>";
+
+        for size in [0, 3, 16, 257] {
+            let reason = invalid(with_type(&format!(
+                "<TypeDefinitionInteger>{id}<Symbol>Note</Symbol><Size>{size}</Size>\
+                 <IsSigned>false</IsSigned></TypeDefinitionInteger>"
+            )));
+            assert!(reason.contains("size"), "{}", reason);
+        }
+
+        let renamed = invalid(with_type(&format!(
+            "<TypeDefinitionRename>{id}<Symbol>Note</Symbol>\
+             <RenamedType>Note</RenamedType></TypeDefinitionRename>"
+        )));
+        assert!(renamed.contains("itself"), "{}", renamed);
+
+        let array = with_type(&format!(
+            "<TypeDefinitionVariableArray>{id}<Symbol>Note</Symbol>\
+             <ElementType>Note</ElementType></TypeDefinitionVariableArray>"
+        ))
+        .replace(
+            "<ReviewNote>Check the fade</ReviewNote>",
+            "<ReviewNote><Note><Note/></Note></ReviewNote>",
+        );
+        let reason = invalid(array);
+        assert!(reason.contains("itself"), "{}", reason);
+    }
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(parse_integer("-0x10"), Some(-16));
+        assert_eq!(encode_integer(255, 1, false), Some(vec![255]));
+        assert_eq!(encode_integer(256, 1, false), None);
+        assert_eq!(encode_integer(1, 0, false), None);
+        assert_eq!(encode_integer(1, 16, true), None);
+        assert_eq!(encode_integer(-1, 2, true), Some(vec![0xff, 0xff]));
+        assert_eq!(
+            parse_timestamp("2021-11-09T07:27:58.12Z"),
+            Some(vec![0xe5, 0x07, 11, 9, 7, 27, 58, 12])
+        );
+        assert_eq!(parse_version("1.2"), Some(vec![1, 2]));
+        assert_eq!(parse_hex("0a ff"), Some(vec![0x0a, 0xff]));
+        assert_eq!(
+            storage_name("EssenceDescription", 0x4701),
+            "EssenceDescripti-4701"
+        );
+
+        let entities =
+            unparsed_entities(r#"<!DOCTYPE AAF [ <!ENTITY a SYSTEM 'a b.raw' NDATA D> ]>"#);
+        assert_eq!(entities.get("a").map(String::as_str), Some("a b.raw"));
+    }
+}
@@ -0,0 +1,951 @@
+/// xml/writer.rs
+///
+/// Writing an AAF file's object graph as AAF-XML.
+///
+/// The `Header` and everything it owns are written as nested elements
+/// named from the file's `MetaDictionary`. The classes, properties and
+/// types the file defines beyond the baseline are written first, in
+/// `Extensions`, so that the document can be read without the file.
+use std::io::{Read, Seek, Write};
+use std::path::PathBuf;
+
+use base64::Engine;
+use uuid::Uuid;
+
+use crate::aaf::meta_dictionary::{
+    ClassDefinition, MetaDictionary, TypeDefinition, TypeDefinitionKind,
+};
+use crate::aaf::values::AAFValue;
+use crate::aaf::{class_ids, type_ids};
+use crate::error::{AAFError, IoResultExt, Result};
+use crate::file::AAFFile;
+use crate::interchange_object::InterchangeObjectDescriptor;
+use crate::properties::PropertyValue;
+use crate::types::*;
+use crate::xml::{auid_to_urn, KNOWN_DEFINITIONS};
+
+const ROOT_HEADER_PID: OMPropertyId = 0x0002;
+
+/// The namespaces of AAF-XML elements and of its attributes.
+const AAF_NAMESPACE: &str = "http://www.smpte-ra.org/schemas/2001-2/2007/aaf";
+const AAF_ATTRIBUTE_NAMESPACE: &str = "http://www.smpte-ra.org/schemas/2001-1b/2007/aaf";
+
+/// Where `AAFFile::write_xml` puts the contents of stream properties.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlStreams {
+    /// In the document, as base64 text.
+    Inline,
+    /// In files in a directory, which the document declares as unparsed
+    /// entities.
+    ///
+    /// The document names each file relative to the directory, so it
+    /// should be kept in, and read back from, that directory.
+    External(PathBuf),
+}
+
+impl<F: Read + Seek> AAFFile<F> {
+    /// Write the file as an AAF-XML document to `out`, with the contents of
+    /// stream properties put as `streams` says.
+    ///
+    /// Enumeration values are written by name, and weak references by the
+    /// key of the object they refer to, or the symbol of a well-known
+    /// definition.
+    pub fn write_xml<W: Write>(&mut self, mut out: W, streams: &XmlStreams) -> Result<()> {
+        let md = self.meta_dictionary()?.clone();
+        let root = self.root_object();
+        let header = match self.get_reference(&root, ROOT_HEADER_PID)? {
+            Some(header) => header,
+            None => {
+                return Err(AAFError::MissingProperty {
+                    path: root.path,
+                    pid: ROOT_HEADER_PID,
+                })
+            }
+        };
+
+        let mut writer = XmlWriter {
+            file: self,
+            md,
+            streams,
+            entities: vec![],
+        };
+        let mut document = Element::new("AAF");
+        document.attribute("xmlns", AAF_NAMESPACE);
+        document.attribute("xmlns:aaf", AAF_ATTRIBUTE_NAMESPACE);
+        document.attribute("version", "1.1");
+        let extensions = writer.extensions();
+        if let Content::Children(_) = extensions.content {
+            document.child(extensions);
+        }
+        document.child(writer.object_element(&header)?);
+
+        let mut text = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        if !writer.entities.is_empty() {
+            text.push_str("<!DOCTYPE AAF [\n");
+            text.push_str("  <!NOTATION DataStream SYSTEM \"data-stream\">\n");
+            // A system literal is not escaped, and the file names are plain
+            for (name, file_name) in &writer.entities {
+                text.push_str(&format!(
+                    "  <!ENTITY {} SYSTEM \"{}\" NDATA DataStream>\n",
+                    name, file_name
+                ));
+            }
+            text.push_str("]>\n");
+        }
+        document.write(&mut text, 0);
+        out.write_all(text.as_bytes()).at_path(&root.path)
+    }
+
+    /// The file as an AAF-XML document, with the contents of stream
+    /// properties in base64.
+    pub fn to_xml_string(&mut self) -> Result<String> {
+        let mut out = vec![];
+        self.write_xml(&mut out, &XmlStreams::Inline)?;
+        Ok(String::from_utf8(out).expect("the document is UTF-8"))
+    }
+}
+
+/// An element of the document being written.
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    content: Content,
+}
+
+enum Content {
+    Empty,
+    Text(String),
+    Children(Vec<Child>),
+}
+
+enum Child {
+    Element(Element),
+    Comment(String),
+}
+
+impl Element {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            attributes: vec![],
+            content: Content::Empty,
+        }
+    }
+
+    fn text(name: &str, text: String) -> Self {
+        Self {
+            content: Content::Text(text),
+            ..Self::new(name)
+        }
+    }
+
+    fn attribute(&mut self, name: &str, value: &str) {
+        self.attributes.push((name.to_string(), value.to_string()));
+    }
+
+    fn push(&mut self, child: Child) {
+        match &mut self.content {
+            Content::Children(children) => children.push(child),
+            _ => self.content = Content::Children(vec![child]),
+        }
+    }
+
+    fn child(&mut self, element: Element) {
+        self.push(Child::Element(element))
+    }
+
+    /// Make the element's content a list of children, though it may have
+    /// none.
+    fn with_children(mut self) -> Self {
+        if let Content::Empty = self.content {
+            self.content = Content::Children(vec![]);
+        }
+        self
+    }
+
+    fn write(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        out.push_str(&indent);
+        out.push('<');
+        out.push_str(&self.name);
+        for (name, value) in &self.attributes {
+            out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+        }
+        match &self.content {
+            Content::Empty => out.push_str("/>\n"),
+            Content::Text(text) => {
+                out.push('>');
+                out.push_str(&escape(text));
+                out.push_str(&format!("</{}>\n", self.name));
+            }
+            Content::Children(children) if children.is_empty() => out.push_str("/>\n"),
+            Content::Children(children) => {
+                out.push_str(">\n");
+                for child in children {
+                    match child {
+                        Child::Element(element) => element.write(out, depth + 1),
+                        Child::Comment(comment) => {
+                            out.push_str(&format!("{}  <!-- {} -->\n", indent, comment))
+                        }
+                    }
+                }
+                out.push_str(&format!("{}</{}>\n", indent, self.name));
+            }
+        }
+    }
+}
+
+struct XmlWriter<'a, F> {
+    file: &'a mut AAFFile<F>,
+    md: MetaDictionary,
+    streams: &'a XmlStreams,
+    /// The names and file names of the unparsed entities of external
+    /// streams.
+    entities: Vec<(String, String)>,
+}
+
+impl<F: Read + Seek> XmlWriter<'_, F> {
+    /// The class with AUID `id`, from the file or the baseline.
+    fn class(&self, id: &Uuid) -> Option<&ClassDefinition> {
+        self.md
+            .class(id)
+            .or_else(|| MetaDictionary::baseline().class(id))
+    }
+
+    /// How a type is referred to: by symbol if it is defined, else by AUID.
+    fn type_reference(&self, id: &Uuid) -> String {
+        match self.md.resolve_type(id) {
+            Some(type_def) => symbol(&type_def.name),
+            None => auid_to_urn(id),
+        }
+    }
+
+    fn class_reference(&self, id: &Uuid) -> String {
+        match self.class(id) {
+            Some(class) => symbol(&class.name),
+            None => auid_to_urn(id),
+        }
+    }
+
+    /// The definitions of the classes, properties and types the file defines
+    /// that the baseline does not.
+    fn extensions(&self) -> Element {
+        let baseline = MetaDictionary::baseline();
+        let mut definitions = Element::new("Definitions");
+
+        let mut classes: Vec<&ClassDefinition> = self.md.classes().collect();
+        classes.sort_by_key(|c| c.name.clone());
+        for class in &classes {
+            if baseline.class(&class.identification).is_some() {
+                continue;
+            }
+            let mut element = definition_element(
+                "ClassDefinition",
+                &class.identification,
+                &class.name,
+                &class.description,
+            );
+            element.child(Element::text(
+                "ParentClass",
+                self.class_reference(&class.parent),
+            ));
+            element.child(Element::text(
+                "IsConcrete",
+                boolean(class.is_concrete).to_string(),
+            ));
+            definitions.child(element);
+        }
+
+        for class in &classes {
+            let defined = baseline.class(&class.identification);
+            for property in &class.properties {
+                let in_baseline = defined.is_some_and(|c| {
+                    c.properties
+                        .iter()
+                        .any(|p| p.identification == property.identification)
+                });
+                if in_baseline {
+                    continue;
+                }
+                let mut element = definition_element(
+                    "PropertyDefinition",
+                    &property.identification,
+                    &property.name,
+                    &property.description,
+                );
+                element.child(Element::text(
+                    "Type",
+                    self.type_reference(&property.type_id),
+                ));
+                element.child(Element::text("MemberOf", symbol(&class.name)));
+                element.child(Element::text(
+                    "LocalIdentification",
+                    format!("0x{:04x}", property.local_identification),
+                ));
+                element.child(Element::text(
+                    "IsOptional",
+                    boolean(property.is_optional).to_string(),
+                ));
+                if property.is_unique_identifier {
+                    element.child(Element::text("IsUniqueIdentifier", "True".to_string()));
+                }
+                definitions.child(element);
+            }
+        }
+
+        let mut types: Vec<&TypeDefinition> = self
+            .md
+            .types()
+            .filter(|t| baseline.type_def(&t.identification) != Some(*t))
+            .collect();
+        types.sort_by_key(|t| t.name.clone());
+        for type_def in types {
+            definitions.child(self.type_definition_element(type_def));
+        }
+
+        let mut extensions = Element::new("Extensions");
+        if let Content::Children(_) = definitions.content {
+            let mut extension = Element::new("Extension");
+            extension.child(definitions);
+            extensions.child(extension);
+        }
+        extensions
+    }
+
+    fn type_definition_element(&self, type_def: &TypeDefinition) -> Element {
+        let kind = match &type_def.kind {
+            TypeDefinitionKind::Integer { .. } => "Integer",
+            TypeDefinitionKind::Character => "Character",
+            TypeDefinitionKind::String { .. } => "String",
+            TypeDefinitionKind::FixedArray { .. } => "FixedArray",
+            TypeDefinitionKind::VariableArray { .. } => "VariableArray",
+            TypeDefinitionKind::Set { .. } => "Set",
+            TypeDefinitionKind::Rename { .. } => "Rename",
+            TypeDefinitionKind::Enumeration { .. } => "Enumeration",
+            TypeDefinitionKind::ExtendibleEnumeration { .. } => "ExtendibleEnumeration",
+            TypeDefinitionKind::Record { .. } => "Record",
+            TypeDefinitionKind::StrongObjectReference { .. } => "StrongObjectReference",
+            TypeDefinitionKind::WeakObjectReference { .. } => "WeakObjectReference",
+            TypeDefinitionKind::Stream => "Stream",
+            TypeDefinitionKind::Indirect => "Indirect",
+            TypeDefinitionKind::Opaque => "Opaque",
+        };
+        let mut element = definition_element(
+            &format!("TypeDefinition{}", kind),
+            &type_def.identification,
+            &type_def.name,
+            &type_def.description,
+        );
+
+        match &type_def.kind {
+            TypeDefinitionKind::Integer { size, is_signed } => {
+                element.child(Element::text("Size", size.to_string()));
+                element.child(Element::text("IsSigned", boolean(*is_signed).to_string()));
+            }
+            TypeDefinitionKind::String { element_type }
+            | TypeDefinitionKind::VariableArray { element_type }
+            | TypeDefinitionKind::Set { element_type } => {
+                element.child(Element::text(
+                    "ElementType",
+                    self.type_reference(element_type),
+                ));
+            }
+            TypeDefinitionKind::FixedArray {
+                element_type,
+                element_count,
+            } => {
+                element.child(Element::text(
+                    "ElementType",
+                    self.type_reference(element_type),
+                ));
+                element.child(Element::text("ElementCount", element_count.to_string()));
+            }
+            TypeDefinitionKind::Rename { renamed_type } => {
+                element.child(Element::text(
+                    "RenamedType",
+                    self.type_reference(renamed_type),
+                ));
+            }
+            TypeDefinitionKind::Enumeration {
+                element_type,
+                elements,
+            } => {
+                element.child(Element::text(
+                    "ElementType",
+                    self.type_reference(element_type),
+                ));
+                let mut list = Element::new("Elements").with_children();
+                for (name, value) in elements {
+                    list.child(Element::text("Name", name.clone()));
+                    list.child(Element::text("Value", value.to_string()));
+                }
+                element.child(list);
+            }
+            TypeDefinitionKind::ExtendibleEnumeration { elements } => {
+                let mut list = Element::new("Elements").with_children();
+                for (name, value) in elements {
+                    list.child(Element::text("Name", name.clone()));
+                    list.child(Element::text("Value", auid_to_urn(value)));
+                }
+                element.child(list);
+            }
+            TypeDefinitionKind::Record { members } => {
+                let mut list = Element::new("Members").with_children();
+                for (name, member_type) in members {
+                    list.child(Element::text("Name", name.clone()));
+                    list.child(Element::text("Type", self.type_reference(member_type)));
+                }
+                element.child(list);
+            }
+            TypeDefinitionKind::StrongObjectReference { referenced_class } => {
+                element.child(Element::text(
+                    "ReferencedType",
+                    self.class_reference(referenced_class),
+                ));
+            }
+            TypeDefinitionKind::WeakObjectReference {
+                referenced_class,
+                target_set,
+            } => {
+                element.child(Element::text(
+                    "ReferencedType",
+                    self.class_reference(referenced_class),
+                ));
+                let mut list = Element::new("TargetSet").with_children();
+                for target in target_set {
+                    list.child(Element::text("MetaDefRef", auid_to_urn(target)));
+                }
+                element.child(list);
+            }
+            TypeDefinitionKind::Character
+            | TypeDefinitionKind::Stream
+            | TypeDefinitionKind::Indirect
+            | TypeDefinitionKind::Opaque => {}
+        }
+        element
+    }
+
+    /// The element of `object` and the objects it owns.
+    fn object_element(&mut self, object: &InterchangeObjectDescriptor) -> Result<Element> {
+        let class_name = match self.class(&object.auid) {
+            Some(class) => symbol(&class.name),
+            None => {
+                return Err(AAFError::UndefinedClass {
+                    path: object.path.clone(),
+                    class_id: object.auid,
+                })
+            }
+        };
+        let mut element = Element::new(&class_name).with_children();
+
+        for pid in self.file.all_property_ids(object)? {
+            let property = match self.md.resolve_property(&object.auid, pid) {
+                Some(property) => property.clone(),
+                None => {
+                    return Err(AAFError::UndefinedProperty {
+                        path: object.path.clone(),
+                        pid,
+                    })
+                }
+            };
+            let type_def = match self.md.resolve_type(&property.type_id) {
+                Some(type_def) => type_def.clone(),
+                None => {
+                    return Err(AAFError::UndefinedType {
+                        path: object.path.clone(),
+                        pid,
+                        type_id: property.type_id,
+                    })
+                }
+            };
+            let name = symbol(&property.name);
+
+            let value = match self.file.get_value(object, pid)? {
+                Some(value) => value,
+                None => continue,
+            };
+            let child = match value {
+                PropertyValue::Data(_) | PropertyValue::UniqueObjectId(_) => {
+                    match self.file.get_typed_value(object, pid)? {
+                        Some(value) => self.value_element(&name, &value, &type_def)?,
+                        None => continue,
+                    }
+                }
+                PropertyValue::Single(member) => {
+                    let mut child = Element::new(&name);
+                    child.child(self.object_element(&member)?);
+                    child
+                }
+                PropertyValue::Vector(members) | PropertyValue::Set(members) => {
+                    let mut child = Element::new(&name).with_children();
+                    for member in members {
+                        child.child(self.object_element(&member)?);
+                    }
+                    child
+                }
+                PropertyValue::Reference(target) => {
+                    Element::text(&name, self.weak_reference_key(&target)?)
+                }
+                PropertyValue::ReferenceVector(targets) | PropertyValue::ReferenceSet(targets) => {
+                    let member_name = match &type_def.kind {
+                        TypeDefinitionKind::VariableArray { element_type }
+                        | TypeDefinitionKind::Set { element_type } => {
+                            self.type_reference(element_type)
+                        }
+                        _ => "Reference".to_string(),
+                    };
+                    let mut child = Element::new(&name).with_children();
+                    for target in targets {
+                        child.child(Element::text(
+                            &member_name,
+                            self.weak_reference_key(&target)?,
+                        ));
+                    }
+                    child
+                }
+                PropertyValue::StoredObjectId(_) => match self.file.get_typed_value(object, pid)? {
+                    Some(value) => Element::text(&name, key_text(&value)),
+                    None => continue,
+                },
+                PropertyValue::Stream(_) | PropertyValue::OpaqueStream(_) => {
+                    self.stream_element(&name, object, pid)?
+                }
+                PropertyValue::Unknown { stored_form, .. } => {
+                    element.push(Child::Comment(format!(
+                        "{} has stored form 0x{:04x}, which is not written",
+                        name, stored_form
+                    )));
+                    continue;
+                }
+            };
+            element.child(child);
+        }
+        Ok(element)
+    }
+
+    /// The key of `target`, as a weak reference to it is written.
+    fn weak_reference_key(&mut self, target: &InterchangeObjectDescriptor) -> Result<String> {
+        let pid = match self.md.resolve_unique_identifier(&target.auid) {
+            Some(key) => key.local_identification,
+            None => {
+                return Err(AAFError::UnexpectedValue {
+                    path: target.path.clone(),
+                    pid: 0,
+                    expected: "an object with a unique identifier",
+                })
+            }
+        };
+        let is_meta_definition = self
+            .md
+            .resolve_lineage(&target.auid)
+            .iter()
+            .any(|c| c.identification == class_ids::META_DEFINITION);
+        match self.file.get_typed_value(target, pid)? {
+            // Classes and types are referred to by name where the name is a
+            // symbol.
+            Some(AAFValue::AUID(id)) if is_meta_definition => {
+                let name = self
+                    .class(&id)
+                    .map(|c| c.name.clone())
+                    .or_else(|| self.md.resolve_type(&id).map(|t| t.name.clone()));
+                match name {
+                    Some(name) if symbol(&name) == name => Ok(name),
+                    _ => Ok(auid_to_urn(&id)),
+                }
+            }
+            Some(value) => Ok(key_text(&value)),
+            None => Err(AAFError::MissingProperty {
+                path: target.path.clone(),
+                pid,
+            }),
+        }
+    }
+
+    /// The element of a stream property, with its contents in base64 or in
+    /// a file of their own.
+    fn stream_element(
+        &mut self,
+        name: &str,
+        object: &InterchangeObjectDescriptor,
+        pid: OMPropertyId,
+    ) -> Result<Element> {
+        let mut data = vec![];
+        if let Some(mut stream) = self.file.open_stream_property(object, pid)? {
+            let path = stream.path().to_path_buf();
+            stream.read_to_end(&mut data).at_path(&path)?;
+        }
+        match self.streams {
+            XmlStreams::Inline => Ok(Element::text(
+                name,
+                base64::engine::general_purpose::STANDARD.encode(data),
+            )),
+            XmlStreams::External(dir) => {
+                let entity = format!("stream{}", self.entities.len() + 1);
+                let file_name = format!("{}.dat", entity);
+                let path = dir.join(&file_name);
+                std::fs::write(&path, data).at_path(&path)?;
+                self.entities.push((entity.clone(), file_name));
+                let mut element = Element::new(name);
+                element.attribute("aaf:stream", &entity);
+                Ok(element)
+            }
+        }
+    }
+
+    /// The element `name` of a data value of `type_def`.
+    fn value_element(
+        &self,
+        name: &str,
+        value: &AAFValue,
+        type_def: &TypeDefinition,
+    ) -> Result<Element> {
+        if let Some(text) = special_text(type_def, value) {
+            return Ok(Element::text(name, text));
+        }
+
+        let element = match (value, &type_def.kind) {
+            (_, TypeDefinitionKind::Rename { renamed_type }) => {
+                let renamed = self.lookup(renamed_type)?;
+                return self.value_element(name, value, renamed);
+            }
+            (AAFValue::Enumeration { name: n, value: v }, _) => {
+                Element::text(name, n.clone().unwrap_or_else(|| v.to_string()))
+            }
+            (AAFValue::ExtendibleEnumeration { name: n, value: v }, _) => {
+                Element::text(name, n.as_deref().map_or_else(|| auid_to_urn(v), symbol))
+            }
+            (AAFValue::Record(members), TypeDefinitionKind::Record { members: types }) => {
+                let mut element = Element::new(name).with_children();
+                for ((member_name, member), (_, member_type)) in members.iter().zip(types) {
+                    let member_type = self.lookup(member_type)?;
+                    element.child(self.value_element(&symbol(member_name), member, member_type)?);
+                }
+                element
+            }
+            (AAFValue::Array(members) | AAFValue::Set(members), kind) => {
+                let element_type = match kind {
+                    TypeDefinitionKind::FixedArray { element_type, .. }
+                    | TypeDefinitionKind::VariableArray { element_type }
+                    | TypeDefinitionKind::Set { element_type }
+                    | TypeDefinitionKind::String { element_type } => self.lookup(element_type)?,
+                    _ => return invalid(type_def),
+                };
+                let bytes: Option<Vec<u8>> = members
+                    .iter()
+                    .map(|m| match m {
+                        AAFValue::UInt8(b) => Some(*b),
+                        AAFValue::Int8(b) => Some(*b as u8),
+                        _ => None,
+                    })
+                    .collect();
+                match bytes {
+                    Some(bytes) if !members.is_empty() => Element::text(name, hex(&bytes)),
+                    _ => {
+                        let member_name = symbol(&element_type.name);
+                        let mut element = Element::new(name).with_children();
+                        for member in members {
+                            element.child(self.value_element(
+                                &member_name,
+                                member,
+                                element_type,
+                            )?);
+                        }
+                        element
+                    }
+                }
+            }
+            (AAFValue::Indirect { type_id, value }, _) => {
+                let actual = self.lookup(type_id)?;
+                let mut element = self.value_element(name, value, actual)?;
+                element.attribute("aaf:actualType", &self.type_reference(type_id));
+                element
+            }
+            (AAFValue::Opaque { type_id, bytes }, _) => {
+                let mut element = Element::text(name, hex(bytes));
+                element.attribute("aaf:actualType", &self.type_reference(type_id));
+                element
+            }
+            (AAFValue::String(s), _) => Element::text(name, s.clone()),
+            (AAFValue::Character(c), _) => Element::text(name, c.to_string()),
+            (value, _) => match integer(value) {
+                Some(i) => Element::text(name, i.to_string()),
+                None => return invalid(type_def),
+            },
+        };
+        Ok(element)
+    }
+
+    fn lookup(&self, id: &Uuid) -> Result<&TypeDefinition> {
+        self.md.resolve_type(id).ok_or(AAFError::InvalidValue {
+            type_name: "an undefined type",
+            len: 0,
+        })
+    }
+}
+
+fn invalid<T>(type_def: &TypeDefinition) -> Result<T> {
+    Err(AAFError::InvalidValue {
+        type_name: match type_def.kind {
+            TypeDefinitionKind::Record { .. } => "Record",
+            TypeDefinitionKind::FixedArray { .. }
+            | TypeDefinitionKind::VariableArray { .. }
+            | TypeDefinitionKind::Set { .. } => "Array",
+            _ => "a data type",
+        },
+        len: 0,
+    })
+}
+
+/// The element of a definition in `Extensions`.
+fn definition_element(
+    kind: &str,
+    identification: &Uuid,
+    name: &str,
+    description: &Option<String>,
+) -> Element {
+    let mut element = Element::new(kind);
+    element.child(Element::text("Identification", auid_to_urn(identification)));
+    element.child(Element::text("Symbol", symbol(name)));
+    element.child(Element::text("Name", name.to_string()));
+    if let Some(description) = description {
+        element.child(Element::text("Description", description.clone()));
+    }
+    element
+}
+
+/// The text of the values of types AAF-XML writes in a form of their own.
+fn special_text(type_def: &TypeDefinition, value: &AAFValue) -> Option<String> {
+    match (type_def.identification, value) {
+        (type_ids::AUID, AAFValue::AUID(id)) => Some(auid_to_urn(id)),
+        (type_ids::MOB_ID_TYPE, AAFValue::MobID(id)) => Some(id.to_string()),
+        (type_ids::RATIONAL, AAFValue::Rational(r)) => {
+            Some(format!("{}/{}", r.numerator, r.denominator))
+        }
+        (type_ids::TIME_STAMP, AAFValue::Record(members)) => match members.as_slice() {
+            [(_, date), (_, time)] => Some(format!("{}T{}Z", date_text(date)?, time_text(time)?)),
+            _ => None,
+        },
+        (type_ids::DATE_STRUCT, value) => date_text(value),
+        (type_ids::TIME_STRUCT, value) => time_text(value),
+        (type_ids::VERSION_TYPE, AAFValue::Record(members)) => match members.as_slice() {
+            [(_, major), (_, minor)] => Some(format!("{}.{}", integer(major)?, integer(minor)?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn record_integers(value: &AAFValue) -> Option<Vec<i128>> {
+    match value {
+        AAFValue::Record(members) => members.iter().map(|(_, m)| integer(m)).collect(),
+        _ => None,
+    }
+}
+
+fn date_text(value: &AAFValue) -> Option<String> {
+    match record_integers(value)?.as_slice() {
+        [year, month, day] => Some(format!("{:04}-{:02}-{:02}", year, month, day)),
+        _ => None,
+    }
+}
+
+fn time_text(value: &AAFValue) -> Option<String> {
+    match record_integers(value)?.as_slice() {
+        [hour, minute, second, fraction] => Some(format!(
+            "{:02}:{:02}:{:02}.{:02}",
+            hour, minute, second, fraction
+        )),
+        _ => None,
+    }
+}
+
+fn integer(value: &AAFValue) -> Option<i128> {
+    match value {
+        AAFValue::UInt8(v) => Some(*v as i128),
+        AAFValue::UInt16(v) => Some(*v as i128),
+        AAFValue::UInt32(v) => Some(*v as i128),
+        AAFValue::UInt64(v) => Some(*v as i128),
+        AAFValue::Int8(v) => Some(*v as i128),
+        AAFValue::Int16(v) => Some(*v as i128),
+        AAFValue::Int32(v) => Some(*v as i128),
+        AAFValue::Int64(v) => Some(*v as i128),
+        _ => None,
+    }
+}
+
+/// A weak reference key as AAF-XML writes it: the symbol of a well-known
+/// definition, or else the AUID or MobID.
+fn key_text(value: &AAFValue) -> String {
+    match value {
+        AAFValue::AUID(id) => KNOWN_DEFINITIONS
+            .iter()
+            .find(|d| d.identification == *id)
+            .map_or_else(|| auid_to_urn(id), |d| d.symbol.to_string()),
+        AAFValue::MobID(id) => id.to_string(),
+        value => integer(value).map_or_else(String::new, |i| i.to_string()),
+    }
+}
+
+fn boolean(value: bool) -> &'static str {
+    match value {
+        true => "True",
+        false => "False",
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `name` as an XML element name, with the characters an element name may
+/// not have replaced by `_`.
+fn symbol(name: &str) -> String {
+    let mut retval: String = name
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' => c,
+            _ => '_',
+        })
+        .collect();
+    if !retval.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        retval.insert(0, '_');
+    }
+    retval
+}
+
+/// `text` escaped for XML, with the control characters XML 1.0 cannot hold
+/// replaced by U+FFFD.
+fn escape(text: &str) -> String {
+    let mut retval = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => retval.push_str("&amp;"),
+            '<' => retval.push_str("&lt;"),
+            '>' => retval.push_str("&gt;"),
+            '"' => retval.push_str("&quot;"),
+            '\'' => retval.push_str("&apos;"),
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {
+                retval.push(char::REPLACEMENT_CHARACTER)
+            }
+            c => retval.push(c),
+        }
+    }
+    retval
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aaf::classes::Header;
+    use crate::timeline::{flatten_mob, Timeline};
+    use std::path::Path;
+
+    const TEST_PATH: &str = "testmedia/AAF_Test_1/AAF_Test_1.aaf";
+
+    /// The name and slot count of each mob.
+    fn mobs<F: Read + Seek>(header: &Header<F>) -> Vec<(String, usize)> {
+        header
+            .content()
+            .unwrap()
+            .mobs()
+            .unwrap()
+            .iter()
+            .map(|m| {
+                (
+                    m.name().unwrap().unwrap_or_default(),
+                    m.slots().unwrap().len(),
+                )
+            })
+            .collect()
+    }
+
+    fn timeline<F: Read + Seek>(header: &Header<F>) -> Vec<Timeline> {
+        let mobs = header.content().unwrap().composition_mobs().unwrap();
+        flatten_mob(&mobs[0]).unwrap()
+    }
+
+    fn essence<F: Read + Seek>(header: &Header<F>) -> Vec<u8> {
+        let data = &header.content().unwrap().essence_data().unwrap()[0];
+        let mut bytes = vec![];
+        data.data().unwrap().read_to_end(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_write_xml() {
+        let mut file = AAFFile::open(TEST_PATH).unwrap();
+        let text = file.to_xml_string().unwrap();
+        assert!(text.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<AAF "));
+        assert!(text.contains("<DataDefinition>DataDef_LegacySound</DataDefinition>"));
+        assert!(text.contains("<FileDescriptorClass>AIFCDescriptor</FileDescriptorClass>"));
+        assert!(text.contains("<EditRate>"));
+    }
+
+    #[test]
+    fn test_xml_round_trip() {
+        let text = AAFFile::open(TEST_PATH).unwrap().to_xml_string().unwrap();
+        let original = AAFFile::open(TEST_PATH).unwrap().header().unwrap();
+        let copy = AAFFile::from_xml(&text, Path::new("."))
+            .unwrap()
+            .header()
+            .unwrap();
+        assert_eq!(mobs(&original), mobs(&copy));
+        assert_eq!(timeline(&original), timeline(&copy));
+
+        // Writing the document's own object graph gives the same document.
+        let mut copy = AAFFile::from_xml(&text, Path::new(".")).unwrap();
+        assert_eq!(copy.to_xml_string().unwrap(), text);
+    }
+
+    #[test]
+    fn test_external_streams() {
+        // A directory name that would need escaping in XML text
+        let dir = std::env::temp_dir().join(format!("rust-aaf-xml-out-R&D-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let embedded = "testmedia/AAF_Test_1/AAF_Test_1_Embedded.aaf";
+        let mut text = vec![];
+        AAFFile::open(embedded)
+            .unwrap()
+            .write_xml(&mut text, &XmlStreams::External(dir.clone()))
+            .unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("<!ENTITY stream1 SYSTEM \"stream1.dat\" NDATA DataStream>"));
+        assert!(text.contains("aaf:stream=\"stream1\""));
+
+        let copy = AAFFile::from_xml(&text, &dir).unwrap().header().unwrap();
+        let original = AAFFile::open(embedded).unwrap().header().unwrap();
+        assert_eq!(essence(&copy), essence(&original));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_symbols() {
+        assert_eq!(symbol("Avid Bin Data"), "Avid_Bin_Data");
+        assert_eq!(symbol("3D"), "_3D");
+        assert_eq!(escape("a<b & \"c\""), "a&lt;b &amp; &quot;c&quot;");
+        assert_eq!(escape("a\u{1}b\tc"), "a\u{fffd}b\tc");
+    }
+
+    #[test]
+    fn test_control_characters() {
+        // AAF_Test_1 with the `_` of the master mob's name, "Audio 1_01",
+        // replaced by a U+0001 in place.
+        let utf16 =
+            |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect() };
+        let mut bytes = std::fs::read(TEST_PATH).unwrap();
+        let name = utf16("Audio 1_01");
+        let start = bytes.windows(name.len()).position(|w| w == name).unwrap();
+        bytes[start..start + name.len()].copy_from_slice(&utf16("Audio 1\u{1}01"));
+
+        let text = AAFFile::from_reader(std::io::Cursor::new(bytes))
+            .unwrap()
+            .to_xml_string()
+            .unwrap();
+        let copy = AAFFile::from_xml(&text, Path::new("."))
+            .unwrap()
+            .header()
+            .unwrap();
+        assert!(mobs(&copy).contains(&("Audio 1\u{fffd}01".to_string(), 1)));
+    }
+}